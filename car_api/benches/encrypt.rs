@@ -0,0 +1,50 @@
+//! Cost of encrypting and decrypting an IBAN, `cargo bench --bench encrypt`.
+//!
+//! The key of each record was derived from the secret with PBKDF2 until the master keys were
+//! derived once per keyring, these values are still decrypted the slow way.
+
+#[path = "../tests/setup.rs"]
+mod setup;
+
+use car_api::encrypt::{decrypt_data_with, encrypt_data_with, Keyring};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use crate::setup::{LEGACY_CIPHERTEXT, PBKDF2_CIPHERTEXT};
+
+const IBAN: &str = "FR7630006000011234567890189";
+
+fn keyring() -> Keyring {
+    setup::keyring(
+        "2026",
+        &[("2025", "previous secret"), ("2026", "rotated secret")],
+    )
+    .with_legacy_secret("legacy secret")
+}
+
+fn encrypt(c: &mut Criterion) {
+    let keyring = keyring();
+
+    c.bench_function("encrypt", |b| {
+        b.iter(|| encrypt_data_with(&keyring, black_box(IBAN)).unwrap())
+    });
+}
+
+fn decrypt(c: &mut Criterion) {
+    let keyring = keyring();
+    let ciphertext = encrypt_data_with(&keyring, IBAN).unwrap();
+
+    let mut group = c.benchmark_group("decrypt");
+    group.bench_function("hkdf record key", |b| {
+        b.iter(|| decrypt_data_with(&keyring, black_box(&ciphertext)).unwrap())
+    });
+    group.bench_function("pbkdf2 record key", |b| {
+        b.iter(|| decrypt_data_with(&keyring, black_box(PBKDF2_CIPHERTEXT)).unwrap())
+    });
+    group.bench_function("legacy", |b| {
+        b.iter(|| decrypt_data_with(&keyring, black_box(LEGACY_CIPHERTEXT)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, encrypt, decrypt);
+criterion_main!(benches);
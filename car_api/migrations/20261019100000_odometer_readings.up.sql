@@ -0,0 +1,16 @@
+-- Add up migration script here
+
+CREATE TABLE odometer_readings (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	mileage BIGINT NOT NULL CHECK (mileage >= 0),
+	-- set when the odometer was replaced, the mileage may then go down
+	meter_replacement BOOLEAN NOT NULL DEFAULT false,
+	recorded_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX odometer_readings_car_id_recorded_at_idx ON odometer_readings (car_id, recorded_at);
+
+SELECT diesel_manage_updated_at('odometer_readings');
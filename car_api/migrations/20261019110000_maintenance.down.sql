@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE service_intervals;
+DROP TABLE maintenance_records;
@@ -0,0 +1,32 @@
+-- Add up migration script here
+
+CREATE TABLE maintenance_records (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	service_type VARCHAR NOT NULL,
+	performed_on DATE NOT NULL,
+	mileage BIGINT CHECK (mileage >= 0),
+	cost_cents BIGINT CHECK (cost_cents >= 0),
+	garage VARCHAR,
+	notes TEXT,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX maintenance_records_car_id_performed_on_idx ON maintenance_records (car_id, performed_on);
+
+-- a service is due every interval_months or every interval_km, whichever comes first
+
+CREATE TABLE service_intervals (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	service_type VARCHAR NOT NULL,
+	interval_months INTEGER CHECK (interval_months > 0),
+	interval_km BIGINT CHECK (interval_km > 0),
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp,
+	UNIQUE (car_id, service_type),
+	CHECK (interval_months IS NOT NULL OR interval_km IS NOT NULL)
+);
+
+SELECT diesel_manage_updated_at('service_intervals');
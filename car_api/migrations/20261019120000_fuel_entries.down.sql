@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE fuel_entries;
+DROP TYPE energy_type;
@@ -0,0 +1,19 @@
+-- Add up migration script here
+
+CREATE TYPE energy_type AS ENUM ('fuel', 'electric');
+
+-- quantity is in litres for fuel and in kWh for electric
+CREATE TABLE fuel_entries (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	energy_type energy_type NOT NULL,
+	quantity DOUBLE PRECISION NOT NULL CHECK (quantity > 0),
+	price_cents BIGINT NOT NULL CHECK (price_cents >= 0),
+	odometer BIGINT NOT NULL CHECK (odometer >= 0),
+	full_tank BOOLEAN NOT NULL DEFAULT true,
+	filled_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX fuel_entries_car_id_filled_at_idx ON fuel_entries (car_id, filled_at);
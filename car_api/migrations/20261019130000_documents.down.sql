@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE documents;
+DROP TYPE document_kind;
@@ -0,0 +1,18 @@
+-- Add up migration script here
+
+CREATE TYPE document_kind AS ENUM ('registration_certificate', 'insurance_card', 'other');
+
+-- the content itself is encrypted in the storage backend under storage_key
+CREATE TABLE documents (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	kind document_kind NOT NULL,
+	file_name VARCHAR NOT NULL,
+	content_type VARCHAR NOT NULL,
+	size_bytes BIGINT NOT NULL,
+	storage_key VARCHAR UNIQUE NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX documents_car_id_idx ON documents (car_id);
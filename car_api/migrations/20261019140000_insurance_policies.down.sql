@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE insurance_policies;
+DROP TYPE coverage_type;
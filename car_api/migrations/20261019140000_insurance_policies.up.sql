@@ -0,0 +1,20 @@
+-- Add up migration script here
+
+CREATE TYPE coverage_type AS ENUM ('third_party', 'third_party_fire_theft', 'comprehensive');
+
+-- policy_number is encrypted
+CREATE TABLE insurance_policies (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	insurer VARCHAR NOT NULL,
+	policy_number VARCHAR NOT NULL,
+	coverage_type coverage_type NOT NULL,
+	starts_on DATE NOT NULL,
+	ends_on DATE NOT NULL,
+	premium_cents BIGINT NOT NULL CHECK (premium_cents >= 0),
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp,
+	CHECK (ends_on >= starts_on)
+);
+
+CREATE INDEX insurance_policies_car_id_ends_on_idx ON insurance_policies (car_id, ends_on);
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP TABLE car_invitations;
+DROP TABLE car_members;
+DROP TYPE invitation_status;
+DROP TYPE car_role;
@@ -0,0 +1,43 @@
+-- Add up migration script here
+
+CREATE TYPE car_role AS ENUM ('owner', 'co_owner', 'driver');
+
+CREATE TYPE invitation_status AS ENUM ('pending', 'accepted', 'declined', 'revoked');
+
+-- users other than the owner (car.user_id) having access to a car, viewing is always allowed
+CREATE TABLE car_members (
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	role car_role NOT NULL CHECK (role <> 'owner'),
+	can_log_trips BOOLEAN NOT NULL,
+	can_edit BOOLEAN NOT NULL,
+	can_delete BOOLEAN NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp,
+	PRIMARY KEY (car_id, user_id)
+);
+
+CREATE INDEX car_members_user_id_idx ON car_members (user_id);
+
+SELECT diesel_manage_updated_at('car_members');
+
+-- invitations are sent by email, the account may not exist yet
+CREATE TABLE car_invitations (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	invited_by uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	email VARCHAR NOT NULL,
+	role car_role NOT NULL CHECK (role <> 'owner'),
+	can_log_trips BOOLEAN NOT NULL,
+	can_edit BOOLEAN NOT NULL,
+	can_delete BOOLEAN NOT NULL,
+	status invitation_status NOT NULL DEFAULT 'pending',
+	responded_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+CREATE UNIQUE INDEX car_invitations_pending_idx ON car_invitations (car_id, lower(email)) WHERE status = 'pending';
+CREATE INDEX car_invitations_email_idx ON car_invitations (lower(email));
+
+SELECT diesel_manage_updated_at('car_invitations');
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP TABLE car_transfers;
+DROP TYPE transfer_status;
+DELETE FROM car WHERE archived_at IS NOT NULL;
+ALTER TABLE car DROP COLUMN archived_at;
@@ -0,0 +1,25 @@
+-- Add up migration script here
+
+-- set on the read-only copy of a car kept by its previous owner after a transfer
+ALTER TABLE car ADD COLUMN archived_at TIMESTAMP;
+
+CREATE TYPE transfer_status AS ENUM ('pending', 'accepted', 'declined', 'cancelled');
+
+CREATE TABLE car_transfers (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	from_user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	to_email VARCHAR NOT NULL,
+	to_user_id uuid REFERENCES users (id) ON DELETE SET NULL,
+	-- the read-only copy created for the previous owner once accepted
+	snapshot_car_id uuid REFERENCES car (id) ON DELETE SET NULL,
+	status transfer_status NOT NULL DEFAULT 'pending',
+	responded_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+CREATE UNIQUE INDEX car_transfers_pending_idx ON car_transfers (car_id) WHERE status = 'pending';
+CREATE INDEX car_transfers_to_email_idx ON car_transfers (lower(to_email));
+
+SELECT diesel_manage_updated_at('car_transfers');
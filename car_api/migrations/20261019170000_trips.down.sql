@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE trip_points;
+DROP TABLE trips;
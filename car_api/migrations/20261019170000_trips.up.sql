@@ -0,0 +1,31 @@
+-- Add up migration script here
+
+CREATE TABLE trips (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	name VARCHAR,
+	started_at TIMESTAMP NOT NULL,
+	ended_at TIMESTAMP,
+	start_odometer BIGINT CHECK (start_odometer >= 0),
+	end_odometer BIGINT CHECK (end_odometer >= 0),
+	-- haversine length of the track, updated when points are added
+	distance_m DOUBLE PRECISION NOT NULL DEFAULT 0,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp,
+	CHECK (ended_at >= started_at),
+	CHECK (end_odometer >= start_odometer)
+);
+
+CREATE INDEX trips_car_id_started_at_idx ON trips (car_id, started_at);
+
+SELECT diesel_manage_updated_at('trips');
+
+CREATE TABLE trip_points (
+	trip_id uuid NOT NULL REFERENCES trips (id) ON DELETE CASCADE,
+	seq INTEGER NOT NULL,
+	latitude DOUBLE PRECISION NOT NULL CHECK (latitude BETWEEN -90 AND 90),
+	longitude DOUBLE PRECISION NOT NULL CHECK (longitude BETWEEN -180 AND 180),
+	elevation DOUBLE PRECISION,
+	recorded_at TIMESTAMP,
+	PRIMARY KEY (trip_id, seq)
+);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+DROP TABLE geofence_events;
+DROP TABLE geofence_states;
+DROP TABLE geofences;
+DROP TYPE geofence_event_kind;
+DROP TYPE geofence_kind;
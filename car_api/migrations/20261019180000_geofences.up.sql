@@ -0,0 +1,47 @@
+-- Add up migration script here
+
+CREATE TYPE geofence_kind AS ENUM ('circle', 'polygon');
+CREATE TYPE geofence_event_kind AS ENUM ('enter', 'exit');
+
+-- a geofence applies to the cars owned by its user
+CREATE TABLE geofences (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	name VARCHAR NOT NULL,
+	kind geofence_kind NOT NULL,
+	center_latitude DOUBLE PRECISION,
+	center_longitude DOUBLE PRECISION,
+	radius_m DOUBLE PRECISION CHECK (radius_m > 0),
+	-- vertices of the polygon, in order
+	latitudes DOUBLE PRECISION[],
+	longitudes DOUBLE PRECISION[],
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp,
+	CHECK (kind <> 'circle' OR (center_latitude IS NOT NULL AND center_longitude IS NOT NULL AND radius_m IS NOT NULL)),
+	CHECK (kind <> 'polygon' OR cardinality(latitudes) >= 3 AND cardinality(latitudes) = cardinality(longitudes))
+);
+
+SELECT diesel_manage_updated_at('geofences');
+
+-- whether the car was inside the geofence at its last known location
+CREATE TABLE geofence_states (
+	geofence_id uuid NOT NULL REFERENCES geofences (id) ON DELETE CASCADE,
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	inside BOOLEAN NOT NULL,
+	located_at TIMESTAMP NOT NULL,
+	PRIMARY KEY (geofence_id, car_id)
+);
+
+CREATE TABLE geofence_events (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	geofence_id uuid NOT NULL REFERENCES geofences (id) ON DELETE CASCADE,
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	kind geofence_event_kind NOT NULL,
+	latitude DOUBLE PRECISION NOT NULL,
+	longitude DOUBLE PRECISION NOT NULL,
+	occurred_at TIMESTAMP NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX geofence_events_geofence_id_occurred_at_idx ON geofence_events (geofence_id, occurred_at);
+CREATE INDEX geofence_events_car_id_occurred_at_idx ON geofence_events (car_id, occurred_at);
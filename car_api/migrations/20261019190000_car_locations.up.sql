@@ -0,0 +1,17 @@
+-- Add up migration script here
+
+-- where the car was parked, kept for LOCATION_RETENTION_DAYS except the latest one of each car
+CREATE TABLE car_locations (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	latitude DOUBLE PRECISION NOT NULL CHECK (latitude BETWEEN -90 AND 90),
+	longitude DOUBLE PRECISION NOT NULL CHECK (longitude BETWEEN -180 AND 180),
+	level VARCHAR,
+	spot_note VARCHAR,
+	photo_document_id uuid REFERENCES documents (id) ON DELETE SET NULL,
+	meter_expires_at TIMESTAMP,
+	parked_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX car_locations_car_id_parked_at_idx ON car_locations (car_id, parked_at);
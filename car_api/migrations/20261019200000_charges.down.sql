@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE charges;
+DROP TYPE charge_kind;
@@ -0,0 +1,25 @@
+-- Add up migration script here
+
+CREATE TYPE charge_kind AS ENUM ('fine', 'toll');
+
+-- traffic fines and toll charges of a car, the driver is whoever has to pay it
+CREATE TABLE charges (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	kind charge_kind NOT NULL,
+	occurred_at TIMESTAMP NOT NULL,
+	location VARCHAR,
+	amount_cents BIGINT NOT NULL CHECK (amount_cents >= 0),
+	currency CHAR(3) NOT NULL,
+	reference VARCHAR,
+	due_on DATE,
+	paid_at TIMESTAMP,
+	driver_id uuid REFERENCES users (id) ON DELETE SET NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX charges_car_id_occurred_at_idx ON charges (car_id, occurred_at);
+CREATE INDEX charges_driver_id_idx ON charges (driver_id) WHERE paid_at IS NULL;
+
+SELECT diesel_manage_updated_at('charges');
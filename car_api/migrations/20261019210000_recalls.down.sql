@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+DROP TABLE recall_completions;
+DROP FUNCTION recall_matches_car;
+DROP TABLE recalls;
+
+ALTER TABLE car DROP COLUMN model_year;
+ALTER TABLE car DROP COLUMN vin;
+ALTER TABLE car DROP COLUMN manufacturer;
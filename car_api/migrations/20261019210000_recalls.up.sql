@@ -0,0 +1,53 @@
+-- Add up migration script here
+
+-- identification of the car used to match the recall campaigns
+ALTER TABLE car ADD COLUMN manufacturer VARCHAR;
+ALTER TABLE car ADD COLUMN vin VARCHAR(17);
+ALTER TABLE car ADD COLUMN model_year INTEGER;
+
+-- recall campaigns imported from the manufacturers feeds, a campaign applies to the cars
+-- of the manufacturer (and model when set) inside its VIN range and its model year range.
+-- Both ends of a VIN range have the same WMI and VDS, the first 8 characters.
+CREATE TABLE recalls (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	campaign VARCHAR NOT NULL,
+	manufacturer VARCHAR NOT NULL,
+	model VARCHAR,
+	vin_from VARCHAR(17),
+	vin_to VARCHAR(17),
+	year_from INTEGER,
+	year_to INTEGER,
+	description VARCHAR NOT NULL,
+	published_on DATE,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp,
+	CHECK ((vin_from IS NULL) = (vin_to IS NULL)),
+	CHECK (left(vin_from, 8) = left(vin_to, 8)),
+	CHECK ((year_from IS NULL) = (year_to IS NULL)),
+	CHECK (vin_from IS NOT NULL OR year_from IS NOT NULL)
+);
+
+CREATE UNIQUE INDEX recalls_campaign_idx ON recalls (lower(manufacturer), campaign);
+
+SELECT diesel_manage_updated_at('recalls');
+
+-- the WMI and VDS of the car must be the ones of the VIN range, then the VIS (characters 10
+-- to 17, ending with the serial number) is compared byte-wise. The check digit is skipped.
+CREATE OR REPLACE FUNCTION recall_matches_car(recall recalls, car car) RETURNS BOOLEAN AS $$
+    SELECT lower(recall.manufacturer) = lower(car.manufacturer)
+        AND (recall.model IS NULL OR lower(recall.model) = lower(car.model))
+        AND (recall.vin_from IS NULL OR (
+            left(car.vin, 8) = left(recall.vin_from, 8)
+            AND substr(car.vin, 10) COLLATE "C" BETWEEN substr(recall.vin_from, 10) AND substr(recall.vin_to, 10)
+        ))
+        AND (recall.year_from IS NULL OR car.model_year BETWEEN recall.year_from AND recall.year_to)
+$$ LANGUAGE sql STABLE;
+
+-- a recall is open for a matching car until the repair is recorded
+CREATE TABLE recall_completions (
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	recall_id uuid NOT NULL REFERENCES recalls (id) ON DELETE CASCADE,
+	completed_on DATE NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	PRIMARY KEY (car_id, recall_id)
+);
@@ -0,0 +1,19 @@
+-- Add up migration script here
+
+-- the variants of a photo are stored under photos/<car_id>/<photo_id>/<variant>
+CREATE TABLE car_photos (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	content_type VARCHAR NOT NULL,
+	width INTEGER NOT NULL,
+	height INTEGER NOT NULL,
+	size_bytes BIGINT NOT NULL,
+	is_primary BOOLEAN NOT NULL DEFAULT false,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX car_photos_car_id_idx ON car_photos (car_id);
+CREATE UNIQUE INDEX car_photos_primary_idx ON car_photos (car_id) WHERE is_primary;
+
+SELECT diesel_manage_updated_at('car_photos');
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP TABLE soc_readings;
+DROP TABLE car_ev_specs;
+DROP TYPE connector_type;
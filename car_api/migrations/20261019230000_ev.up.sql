@@ -0,0 +1,28 @@
+-- Add up migration script here
+
+CREATE TYPE connector_type AS ENUM ('type1', 'type2', 'ccs1', 'ccs2', 'chademo', 'nacs', 'gb_t');
+
+-- specifications of the electric cars, other cars have no row
+CREATE TABLE car_ev_specs (
+	car_id uuid PRIMARY KEY REFERENCES car (id) ON DELETE CASCADE,
+	battery_capacity_kwh DOUBLE PRECISION NOT NULL CHECK (battery_capacity_kwh > 0),
+	connector_types connector_type[] NOT NULL,
+	max_ac_power_kw DOUBLE PRECISION CHECK (max_ac_power_kw > 0),
+	max_dc_power_kw DOUBLE PRECISION CHECK (max_dc_power_kw > 0),
+	wltp_range_km INTEGER CHECK (wltp_range_km > 0),
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+SELECT diesel_manage_updated_at('car_ev_specs');
+
+-- state of charge reported by the car, energy_kwh is the energy left in the battery when the car reports it
+CREATE TABLE soc_readings (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	car_id uuid NOT NULL REFERENCES car (id) ON DELETE CASCADE,
+	recorded_at TIMESTAMP NOT NULL,
+	soc_percent DOUBLE PRECISION NOT NULL CHECK (soc_percent BETWEEN 0 AND 100),
+	energy_kwh DOUBLE PRECISION CHECK (energy_kwh >= 0),
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	UNIQUE (car_id, recorded_at)
+);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+ALTER TABLE bank_details DROP COLUMN bic;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+
+ALTER TABLE bank_details ADD COLUMN bic VARCHAR;
@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+DROP TABLE sepa_mandates;
+
+DROP TRIGGER set_updated_at ON bank_details;
+DROP INDEX bank_details_primary_idx;
+DROP INDEX bank_details_user_id_idx;
+ALTER TABLE bank_details DROP COLUMN deleted_at;
+ALTER TABLE bank_details DROP COLUMN is_primary;
@@ -0,0 +1,35 @@
+-- Add up migration script here
+
+-- the account paid to when no other one is given, the first account of a user is the primary one
+ALTER TABLE bank_details ADD COLUMN is_primary BOOLEAN NOT NULL DEFAULT false;
+
+UPDATE bank_details
+SET is_primary = true
+WHERE id IN (
+	SELECT DISTINCT ON (user_id) id
+	FROM bank_details
+	ORDER BY user_id, created_at
+);
+
+-- a removed account is only marked as deleted, the records of its mandates and payments are kept
+ALTER TABLE bank_details ADD COLUMN deleted_at TIMESTAMP;
+
+CREATE INDEX bank_details_user_id_idx ON bank_details (user_id);
+CREATE UNIQUE INDEX bank_details_primary_idx ON bank_details (user_id) WHERE is_primary;
+
+SELECT diesel_manage_updated_at('bank_details');
+
+-- a mandate is active until it is revoked, its bank account cannot be removed before
+CREATE TABLE sepa_mandates (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	bank_details_id uuid NOT NULL REFERENCES bank_details (id) ON DELETE RESTRICT,
+	reference VARCHAR UNIQUE NOT NULL,
+	signed_on DATE NOT NULL,
+	revoked_at TIMESTAMP,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX sepa_mandates_bank_details_id_idx ON sepa_mandates (bank_details_id);
+
+SELECT diesel_manage_updated_at('sepa_mandates');
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE iban_reveals;
+DROP TABLE reauthentications;
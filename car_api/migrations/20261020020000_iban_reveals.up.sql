@@ -0,0 +1,23 @@
+-- Add up migration script here
+
+-- password confirmations of logged in users, the failed ones are kept to rate limit them.
+-- A confirmation only unlocks the session it was made in.
+CREATE TABLE reauthentications (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	session_id VARCHAR NOT NULL,
+	succeeded BOOLEAN NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX reauthentications_user_id_idx ON reauthentications (user_id, created_at);
+
+-- every time a full IBAN is shown
+CREATE TABLE iban_reveals (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	bank_details_id uuid NOT NULL REFERENCES bank_details (id) ON DELETE CASCADE,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX iban_reveals_user_id_idx ON iban_reveals (user_id, created_at);
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP TABLE sepa_collections;
+DROP TABLE sepa_batches;
+ALTER TABLE sepa_mandates DROP COLUMN sequence_type;
+DROP TYPE sepa_sequence_type;
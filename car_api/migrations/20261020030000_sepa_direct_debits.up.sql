@@ -0,0 +1,31 @@
+-- Add up migration script here
+
+CREATE TYPE sepa_sequence_type AS ENUM ('frst', 'rcur');
+
+-- the sequence type of the next collection, a mandate is recurrent once it has been collected
+ALTER TABLE sepa_mandates ADD COLUMN sequence_type sepa_sequence_type NOT NULL DEFAULT 'frst';
+
+-- a pain.008 message sent to the bank
+CREATE TABLE sepa_batches (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	message_id VARCHAR UNIQUE NOT NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+-- amounts are in euro cents, a collection is pending until it is part of a batch
+CREATE TABLE sepa_collections (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	mandate_id uuid NOT NULL REFERENCES sepa_mandates (id) ON DELETE RESTRICT,
+	amount_cents BIGINT NOT NULL CHECK (amount_cents > 0),
+	remittance_information VARCHAR NOT NULL,
+	due_on DATE NOT NULL,
+	batch_id uuid REFERENCES sepa_batches (id) ON DELETE SET NULL,
+	sequence_type sepa_sequence_type,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX sepa_collections_mandate_id_idx ON sepa_collections (mandate_id);
+CREATE INDEX sepa_collections_pending_idx ON sepa_collections (due_on) WHERE batch_id IS NULL;
+
+SELECT diesel_manage_updated_at('sepa_collections');
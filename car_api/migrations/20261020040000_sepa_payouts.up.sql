@@ -0,0 +1,19 @@
+-- Add up migration script here
+
+-- expenses reimbursed to a bank account of a user, in euro cents, a payout is pending until it is part of a batch
+CREATE TABLE sepa_payouts (
+	id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	bank_details_id uuid NOT NULL REFERENCES bank_details (id) ON DELETE RESTRICT,
+	amount_cents BIGINT NOT NULL CHECK (amount_cents > 0),
+	remittance_information VARCHAR NOT NULL,
+	execution_date DATE NOT NULL,
+	batch_id uuid REFERENCES sepa_batches (id) ON DELETE SET NULL,
+	created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+	updated_at TIMESTAMP  NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX sepa_payouts_user_id_idx ON sepa_payouts (user_id);
+CREATE INDEX sepa_payouts_pending_idx ON sepa_payouts (execution_date) WHERE batch_id IS NULL;
+
+SELECT diesel_manage_updated_at('sepa_payouts');
@@ -1,39 +1,238 @@
 /**
- *  password-based encryption, then you should use a key derivation function, such as PBKDF2. A key derivation function
- *  takes a salt and a supplied user password, and produces a key that can be used with a cipher like AES.
+ * The values are encrypted with AES-256-GCM using a key derived for each record.
  *
- *  To encrypt, you would prompt for a password, generate a random salt, and derive a key using the KDF.
- *  You would then use that key with AES in a suitable block cipher mode to encrypt the data, and store only the salt and the encrypted
- *  data (and whatever IV the cipher mode requires).
+ * Each secret is stretched once into a master key with PBKDF2, when the keyring is created, as a
+ * key derivation function is designed to be slow to make a bruteforce dictionary attack on the
+ * secret infeasible. The key of a record is then derived from the master key and a random salt with
+ * HKDF, which is fast, so a value is never encrypted twice with the same key and nonce.
  *
- *  To decrypt, you would prompt for a password, load the salt from the file, and re-derive the key. You would then use that key
- *  to decrypt the file.
+ * The ciphertexts start with a header holding the format version and the id of the key they are
+ * encrypted with, so the secret can be rotated: new values are encrypted with the current key while
+ * the previous ones are still read with the key named in their header. The header is authenticated
+ * by the tag, a value cannot be passed as encrypted with another key.
  *
- *  The purpose of the salt is to prevent precomputation optimisations from being applied to a dictionary attack.
- *  It is indeed possible to perform a bruteforce dictionary attack once the salt is known,
- *  but the KDF is designed to be slow enough to make this infeasible without precomputation.
+ *   0xCA 0xFE | 2 | key id length | key id | salt (16) | nonce (12) | tag (16) | data
  *
- * The encryption algorithm used is AES-256-GCM.
+ * The previous formats derived the key of each record from the secret with PBKDF2, they are still
+ * decrypted:
+ *
+ *   0xCA 0xFE | 1 | key id length | key id | salt (64) | iv (16) | tag (16) | data
+ *   salt (64) | iv (16) | tag (16) | data, decrypted with `ENCRYPTION_SECRET`
  */
 use openssl::{
+    md::Md,
     pkcs5::pbkdf2_hmac,
+    pkey::Id,
+    pkey_ctx::PkeyCtx,
     rand::rand_bytes,
     symm::{decrypt_aead, encrypt_aead, Cipher},
 };
-use std::env;
+use std::{env, sync::OnceLock};
 
 use crate::errors::Error;
 
-fn get_key(salt: &[u8], key: &mut [u8]) -> Result<(), Error> {
-    let pass = match env::var("ENCRYPTION_SECRET") {
-        Ok(var) => var,
-        _ => panic!("No ENCRYPTION_SECRET value in env"),
-    };
+const HEADER_MAGIC: [u8; 2] = [0xCA, 0xFE];
+/// Key of each record derived from the secret with PBKDF2.
+const PBKDF2_FORMAT_VERSION: u8 = 1;
+/// Key of each record derived from the master key with HKDF.
+const FORMAT_VERSION: u8 = 2;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+const PBKDF2_SALT_LENGTH: usize = 64;
+const PBKDF2_IV_LENGTH: usize = 16;
+const PBKDF2_ITERATIONS: usize = 10000;
+
+const MASTER_KEY_SALT: &[u8] = b"car_api encryption master key";
+const MASTER_KEY_ITERATIONS: usize = 210_000;
+
+/// The keys loaded from the key provider when the api starts.
+static KEYRING: OnceLock<Keyring> = OnceLock::new();
+
+/// Id of `ENCRYPTION_SECRET` among the keys, unless `ENCRYPTION_KEYS` lists a key with this id.
+pub const DEFAULT_KEY_ID: &str = "default";
+
+#[derive(Clone)]
+struct Key {
+    id: String,
+    /// Only used to decrypt the values of the PBKDF2 format.
+    secret: String,
+    master_key: [u8; 32],
+}
+
+impl Key {
+    fn new(id: String, secret: String) -> Result<Key, Error> {
+        let mut master_key = [0; 32];
+        get_key(
+            &secret,
+            MASTER_KEY_SALT,
+            MASTER_KEY_ITERATIONS,
+            &mut master_key,
+        )?;
+
+        Ok(Key {
+            id,
+            secret,
+            master_key,
+        })
+    }
+}
+
+/**
+ * The secrets used to encrypt and decrypt the values, identified by the key id written in the
+ * header of the ciphertexts. New values are encrypted with a single key, all of them decrypt.
+ */
+#[derive(Clone)]
+pub struct Keyring {
+    encryption_key_id: String,
+    keys: Vec<Key>,
+    /// Secret of the values written before the ciphertexts had a header.
+    legacy_secret: Option<String>,
+}
+
+impl Keyring {
+    /// Fails if a key id is invalid or if the encryption key is not one of the keys. The
+    /// `default` key also decrypts the values written before the ciphertexts had a header.
+    /// The master keys are derived here, a keyring should be created once.
+    pub fn new(encryption_key_id: &str, keys: Vec<(String, String)>) -> Result<Keyring, Error> {
+        if let Some((key_id, _)) = keys.iter().find(|(key_id, _)| !is_valid_key_id(key_id)) {
+            return Err(Error::MissingEncryptionKey(format!(
+                "invalid encryption key id {}",
+                key_id
+            )));
+        }
+        if !keys.iter().any(|(key_id, _)| key_id == encryption_key_id) {
+            return Err(Error::MissingEncryptionKey(format!(
+                "failed to find the encryption key {}",
+                encryption_key_id
+            )));
+        }
+
+        let legacy_secret = keys
+            .iter()
+            .find(|(key_id, _)| key_id == DEFAULT_KEY_ID)
+            .map(|(_, secret)| secret.clone());
+        let keys = keys
+            .into_iter()
+            .map(|(id, secret)| Key::new(id, secret))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Keyring {
+            encryption_key_id: encryption_key_id.to_owned(),
+            keys,
+            legacy_secret,
+        })
+    }
+
+    pub fn with_legacy_secret(mut self, secret: &str) -> Keyring {
+        self.legacy_secret = Some(secret.to_owned());
+        self
+    }
+
+    /// Keeps the values of `ENCRYPTION_SECRET` readable once the keys are listed, the secret is the
+    /// `default` key unless a key already has this id and it decrypts the values without header.
+    pub fn with_default_secret(mut self, secret: &str) -> Result<Keyring, Error> {
+        if self.key(DEFAULT_KEY_ID).is_none() {
+            self.keys
+                .push(Key::new(DEFAULT_KEY_ID.to_owned(), secret.to_owned())?);
+        }
+
+        Ok(self.with_legacy_secret(secret))
+    }
+
+    /**
+     * `ENCRYPTION_KEYS` lists the keys as `id:secret` separated by commas and `ENCRYPTION_KEY_ID`
+     * names the one new values are encrypted with, the first one by default. `ENCRYPTION_SECRET`
+     * is the key with the `default` id, unless `ENCRYPTION_KEYS` has one, and also decrypts the
+     * values written before the keys had ids.
+     */
+    pub fn from_env() -> Result<Keyring, Error> {
+        let legacy_secret = env::var("ENCRYPTION_SECRET").ok();
+
+        let keys = match env::var("ENCRYPTION_KEYS") {
+            Ok(keys) => keys
+                .split(',')
+                .filter(|key| !key.trim().is_empty())
+                .map(|key| match key.split_once(':') {
+                    Some((key_id, secret)) => Ok((key_id.trim().to_owned(), secret.to_owned())),
+                    None => Err(Error::MissingEncryptionKey(
+                        "ENCRYPTION_KEYS entries must be id:secret".to_owned(),
+                    )),
+                })
+                .collect::<Result<Vec<_>, Error>>()?,
+            _ => match &legacy_secret {
+                Some(secret) => vec![(DEFAULT_KEY_ID.to_owned(), secret.clone())],
+                None => {
+                    return Err(Error::MissingEncryptionKey(
+                        "failed to find ENCRYPTION_SECRET in env".to_owned(),
+                    ))
+                }
+            },
+        };
+
+        let encryption_key_id = match env::var("ENCRYPTION_KEY_ID") {
+            Ok(key_id) => key_id,
+            _ => keys
+                .first()
+                .map(|(key_id, _)| key_id.clone())
+                .ok_or_else(|| {
+                    Error::MissingEncryptionKey("ENCRYPTION_KEYS is empty".to_owned())
+                })?,
+        };
+
+        let keyring = Keyring::new(&encryption_key_id, keys)?;
+        match legacy_secret {
+            Some(secret) => keyring.with_default_secret(&secret),
+            None => Ok(keyring),
+        }
+    }
+
+    pub fn encryption_key_id(&self) -> &str {
+        &self.encryption_key_id
+    }
+
+    fn key(&self, key_id: &str) -> Option<&Key> {
+        self.keys.iter().find(|key| key.id == key_id)
+    }
+}
+
+/// Key ids fit in the one byte length of the header and cannot be confused with the separators
+/// of `ENCRYPTION_KEYS`.
+fn is_valid_key_id(key_id: &str) -> bool {
+    !key_id.is_empty() && key_id.len() <= u8::MAX as usize && !key_id.contains([':', ','])
+}
+
+/**
+ * Makes the keyring the one used by `encrypt_data` and the other functions without keyring
+ * argument, it can only be installed once. Otherwise the keys are read from the env when
+ * they are first used.
+ */
+pub fn install_keyring(keyring: Keyring) -> Result<(), Error> {
+    KEYRING
+        .set(keyring)
+        .map_err(|_| Error::Conflict("the encryption keys are already installed".to_owned()))
+}
+
+fn current_keyring() -> Result<&'static Keyring, Error> {
+    if let Some(keyring) = KEYRING.get() {
+        return Ok(keyring);
+    }
+    // an invalid configuration is reported as a missing key, its details only go to the logs
+    let keyring = Keyring::from_env().map_err(|err| match err {
+        Error::MissingEncryptionKey(_) => err,
+        err => Error::MissingEncryptionKey(format!("invalid encryption keys: {:?}", err)),
+    })?;
+
+    Ok(KEYRING.get_or_init(|| keyring))
+}
 
+fn get_key(secret: &str, salt: &[u8], iterations: usize, key: &mut [u8]) -> Result<(), Error> {
     pbkdf2_hmac(
-        pass.as_bytes(),
+        secret.as_bytes(),
         salt,
-        10000,
+        iterations,
         openssl::hash::MessageDigest::sha512(),
         key,
     )?;
@@ -41,76 +240,233 @@ fn get_key(salt: &[u8], key: &mut [u8]) -> Result<(), Error> {
     Ok(())
 }
 
+/// HKDF-SHA256 of the master key, the header is the context so each key id and format gets its own keys.
+fn get_record_key(master_key: &[u8], salt: &[u8], header: &[u8]) -> Result<[u8; 32], Error> {
+    let mut ctx = PkeyCtx::new_id(Id::HKDF)?;
+    ctx.derive_init()?;
+    ctx.set_hkdf_md(Md::sha256())?;
+    ctx.set_hkdf_key(master_key)?;
+    ctx.set_hkdf_salt(salt)?;
+    ctx.add_hkdf_info(header)?;
+
+    let mut key = [0; 32];
+    ctx.derive(Some(&mut key))?;
+
+    Ok(key)
+}
+
 /**
  * Decode base64  strings.
  */
 fn decode(text: &str) -> Result<Vec<u8>, Error> {
     match openssl::base64::decode_block(text) {
         Ok(val) => Ok(val),
-        Err(err) => Err(Error::Openssl(err)),
+        Err(err) => Err(Error::MalformedCiphertext(format!(
+            "invalid base64: {}",
+            err
+        ))),
     }
 }
 
-fn encrypt(text: &[u8]) -> Result<String, Error> {
+fn encrypt_raw(keyring: &Keyring, text: &[u8]) -> Result<Vec<u8>, Error> {
     let cipher = Cipher::aes_256_gcm();
 
-    let mut tag = vec![0; 16];
-    let mut iv = vec![0; 16];
-    rand_bytes(&mut iv)?;
-    let mut salt = vec![0; 64];
+    let key_id = keyring.encryption_key_id();
+    let key = keyring.key(key_id).ok_or_else(|| {
+        Error::MissingEncryptionKey(format!("failed to find the encryption key {}", key_id))
+    })?;
+    let header = [
+        &HEADER_MAGIC[..],
+        &[FORMAT_VERSION, key_id.len() as u8],
+        key_id.as_bytes(),
+    ]
+    .concat();
+
+    let mut tag = [0; TAG_LENGTH];
+    let mut nonce = [0; NONCE_LENGTH];
+    rand_bytes(&mut nonce)?;
+    let mut salt = [0; SALT_LENGTH];
     rand_bytes(&mut salt)?;
-    let mut key = [0; 32];
-    get_key(&salt, &mut key)?;
+    let record_key = get_record_key(&key.master_key, &salt, &header)?;
 
-    let encrypted = encrypt_aead(cipher, &key, Some(&iv), &[], text, &mut tag)?;
+    let encrypted = encrypt_aead(cipher, &record_key, Some(&nonce), &header, text, &mut tag)?;
 
-    Ok(openssl::base64::encode_block(
-        &[salt, iv, tag, encrypted].concat(),
+    Ok([&header[..], &salt, &nonce, &tag, &encrypted].concat())
+}
+
+/// Splits the header from the ciphertext as its version, key id, header and body,
+/// `None` for the values written before the header existed.
+fn split_header(ciphertext: &[u8]) -> Option<(u8, &str, &[u8], &[u8])> {
+    let version = *ciphertext.get(HEADER_MAGIC.len())?;
+    if ciphertext.get(..HEADER_MAGIC.len())? != HEADER_MAGIC
+        || !matches!(version, PBKDF2_FORMAT_VERSION | FORMAT_VERSION)
+    {
+        return None;
+    }
+    let key_id_position = HEADER_MAGIC.len() + 2;
+    let header_length = key_id_position + *ciphertext.get(key_id_position - 1)? as usize;
+    let key_id = std::str::from_utf8(ciphertext.get(key_id_position..header_length)?).ok()?;
+
+    Some((
+        version,
+        key_id,
+        &ciphertext[..header_length],
+        &ciphertext[header_length..],
     ))
 }
 
-pub fn encrypt_data(data: String) -> Result<String, Error> {
-    match env::var("ENCRYPTION_SECRET") {
-        Ok(..) => encrypt(data.as_bytes()),
-        _ => Err(Error::Conflict(
-            "failed to find ENCRYPTION_SECRET in env".to_owned(),
-        )),
+fn decrypt_raw(keyring: &Keyring, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+    if let Some((version, key_id, header, body)) = split_header(ciphertext) {
+        let value = match keyring.key(key_id) {
+            Some(key) if version == FORMAT_VERSION => decrypt_body(&key.master_key, header, body),
+            Some(key) => decrypt_pbkdf2_body(&key.secret, header, body),
+            None => Err(Error::MissingEncryptionKey(format!(
+                "failed to find the encryption key {}",
+                key_id
+            ))),
+        };
+        // the random salt of a value without header can start like a header
+        return match (value, &keyring.legacy_secret) {
+            (Err(err), Some(secret)) => decrypt_pbkdf2_body(secret, &[], ciphertext).or(Err(err)),
+            (value, _) => value,
+        };
     }
+
+    // a value too short for the format is malformed, whether its secret is known or not
+    split_body(ciphertext, PBKDF2_SALT_LENGTH, PBKDF2_IV_LENGTH)?;
+    let secret = keyring.legacy_secret.as_deref().ok_or_else(|| {
+        Error::MissingEncryptionKey(
+            "failed to find ENCRYPTION_SECRET for a value without key id".to_owned(),
+        )
+    })?;
+    decrypt_pbkdf2_body(secret, &[], ciphertext)
 }
 
-fn decrypt(text: String) -> Result<String, Error> {
-    let ciphertext = decode(&text)?;
+/// The parts of a ciphertext following its header.
+struct Body<'a> {
+    salt: &'a [u8],
+    nonce: &'a [u8],
+    tag: &'a [u8],
+    encrypted: &'a [u8],
+}
+
+/// Splits the body of a ciphertext, it must at least hold the salt, nonce and tag.
+fn split_body(body: &[u8], salt_length: usize, nonce_length: usize) -> Result<Body<'_>, Error> {
+    let tag_position = salt_length + nonce_length;
+    let encrypted_position = tag_position + TAG_LENGTH;
+    if body.len() < encrypted_position {
+        return Err(Error::MalformedCiphertext(format!(
+            "{} bytes are too short for a ciphertext",
+            body.len()
+        )));
+    }
+
+    Ok(Body {
+        salt: &body[..salt_length],
+        nonce: &body[salt_length..tag_position],
+        tag: &body[tag_position..encrypted_position],
+        encrypted: &body[encrypted_position..],
+    })
+}
+
+fn decrypt_body(master_key: &[u8], header: &[u8], body: &[u8]) -> Result<Vec<u8>, Error> {
     let cipher = Cipher::aes_256_gcm();
 
-    let iv_length = 16;
-    let salt_length = 64;
-    let tag_length = 16;
-    let tag_position = salt_length + iv_length;
-    let encrypted_position = tag_position + tag_length;
+    let body = split_body(body, SALT_LENGTH, NONCE_LENGTH)?;
 
-    let salt: &[u8] = &ciphertext[0..salt_length];
-    let iv: &[u8] = &ciphertext[salt_length..tag_position];
-    let tag: &[u8] = &ciphertext[tag_position..encrypted_position];
-    let encrypted: &[u8] = &ciphertext[encrypted_position..];
+    let record_key = get_record_key(master_key, body.salt, header)?;
+
+    decrypt_aead(
+        cipher,
+        &record_key,
+        Some(body.nonce),
+        header,
+        body.encrypted,
+        body.tag,
+    )
+    .map_err(|_| Error::CiphertextAuthentication)
+}
+
+fn decrypt_pbkdf2_body(secret: &str, header: &[u8], body: &[u8]) -> Result<Vec<u8>, Error> {
+    let cipher = Cipher::aes_256_gcm();
+
+    // the iv takes the place of the nonce
+    let body = split_body(body, PBKDF2_SALT_LENGTH, PBKDF2_IV_LENGTH)?;
 
     let mut key = [0; 32];
-    get_key(salt, &mut key)?;
+    get_key(secret, body.salt, PBKDF2_ITERATIONS, &mut key)?;
+
+    decrypt_aead(
+        cipher,
+        &key,
+        Some(body.nonce),
+        header,
+        body.encrypted,
+        body.tag,
+    )
+    .map_err(|_| Error::CiphertextAuthentication)
+}
+
+/// Whether the value is not encrypted with the encryption key of the keyring in the current format.
+pub fn needs_reencryption(keyring: &Keyring, value: &str) -> bool {
+    let Ok(ciphertext) = decode(value) else {
+        return true;
+    };
+
+    !matches!(
+        split_header(&ciphertext),
+        Some((FORMAT_VERSION, key_id, _, _)) if key_id == keyring.encryption_key_id()
+    )
+}
+
+/**
+ * Runs the encryption or decryption on the blocking thread pool, the values of the PBKDF2 formats
+ * derive a key per record and large documents would stall the async executor.
+ */
+pub async fn spawn_crypto<T, F>(f: F) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|err| Error::Io(std::io::Error::other(err)))?
+}
+
+/// `decrypt_data` of each value on the blocking thread pool.
+pub async fn decrypt_all(values: Vec<String>) -> Result<Vec<String>, Error> {
+    spawn_crypto(move || values.into_iter().map(decrypt_data).collect()).await
+}
+
+pub fn encrypt_data_with(keyring: &Keyring, data: &str) -> Result<String, Error> {
+    Ok(openssl::base64::encode_block(&encrypt_raw(
+        keyring,
+        data.as_bytes(),
+    )?))
+}
 
-    let value = decrypt_aead(cipher, &key, Some(iv), &[], encrypted, tag)?;
+pub fn decrypt_data_with(keyring: &Keyring, value: &str) -> Result<String, Error> {
+    let value = decrypt_raw(keyring, &decode(value)?)?;
 
     Ok(String::from_utf8_lossy(&value).to_string())
 }
 
+pub fn encrypt_data(data: String) -> Result<String, Error> {
+    encrypt_data_with(current_keyring()?, &data)
+}
+
+/// Fails if the keys are not configured, the value is never returned as is.
 pub fn decrypt_data(value: String) -> Result<String, Error> {
-    match env::var("ENCRYPTION_SECRET") {
-        Ok(..) => {
-            let value = decrypt(value)?;
-            Ok(value)
-        }
-        _ => {
-            // send error if ENCRYPTION_SECRET is not set
-            let value = value;
-            Ok(value)
-        }
-    }
+    decrypt_data_with(current_keyring()?, &value)
+}
+
+/**
+ * Same as `encrypt_data` for binary content, the result is not base64 encoded.
+ */
+pub fn encrypt_bytes(data: &[u8]) -> Result<Vec<u8>, Error> {
+    encrypt_raw(current_keyring()?, data)
+}
+
+pub fn decrypt_bytes(data: &[u8]) -> Result<Vec<u8>, Error> {
+    decrypt_raw(current_keyring()?, data)
 }
@@ -3,6 +3,7 @@ use axum::response::{IntoResponse, Response};
 use axum::Json;
 
 use serde_with::DisplayFromStr;
+use tracing::error;
 use validator::ValidationErrors;
 
 /// An API-friendly error type.
@@ -11,6 +12,9 @@ pub enum Error {
     #[error("an internal database error occurred")]
     Sqlx(#[from] sqlx::Error),
 
+    #[error("an internal storage error occurred")]
+    Io(#[from] std::io::Error),
+
     #[error("OpenSSL error occurred")]
     Openssl(#[from] openssl::error::ErrorStack),
 
@@ -19,6 +23,35 @@ pub enum Error {
 
     #[error("{0}")]
     Conflict(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("{0}")]
+    UnsupportedMediaType(String),
+
+    #[error("{0}")]
+    TooManyRequests(String),
+
+    /// A variable the endpoint needs is not set, only the logs tell which one.
+    #[error("an internal configuration error occurred")]
+    MissingConfiguration(String),
+
+    /// No key to encrypt or decrypt the value, the keys are not configured, their configuration
+    /// is invalid or the key id of the value is unknown. The crypto errors share their message, only the logs tell them apart.
+    #[error("an internal encryption error occurred")]
+    MissingEncryptionKey(String),
+
+    /// The value is not a ciphertext, it is not base64 or too short.
+    #[error("an internal encryption error occurred")]
+    MalformedCiphertext(String),
+
+    /// The tag does not match, the value was altered or encrypted with another secret.
+    #[error("an internal encryption error occurred")]
+    CiphertextAuthentication,
 }
 
 impl IntoResponse for Error {
@@ -34,6 +67,10 @@ impl IntoResponse for Error {
             errors: Option<&'a ValidationErrors>,
         }
 
+        if self.is_crypto_error() || matches!(self, Error::MissingConfiguration(_)) {
+            error!("{:?}", self);
+        }
+
         let errors = match &self {
             Error::InvalidEntity(errors) => Some(errors),
             _ => None,
@@ -57,8 +94,26 @@ impl Error {
         match self {
             Openssl(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Sqlx(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
             InvalidEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
             Conflict(_) => StatusCode::CONFLICT,
+            NotFound(_) => StatusCode::NOT_FOUND,
+            Forbidden(_) => StatusCode::FORBIDDEN,
+            UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            MissingConfiguration(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            MissingEncryptionKey(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            MalformedCiphertext(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            CiphertextAuthentication => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn is_crypto_error(&self) -> bool {
+        matches!(
+            self,
+            Error::MissingEncryptionKey(_)
+                | Error::MalformedCiphertext(_)
+                | Error::CiphertextAuthentication
+        )
+    }
 }
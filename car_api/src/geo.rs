@@ -0,0 +1,268 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+/// Mean radius of the earth used by the haversine formula, in metres.
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+const GPX_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Validate, sqlx::FromRow)]
+pub struct TrackPoint {
+    #[validate(range(min = -90.0, max = 90.0))]
+    pub latitude: f64,
+    #[validate(range(min = -180.0, max = 180.0))]
+    pub longitude: f64,
+    /// Metres above sea level.
+    pub elevation: Option<f64>,
+    /// UTC time of the fix.
+    pub recorded_at: Option<NaiveDateTime>,
+}
+
+impl TrackPoint {
+    pub fn coordinates(&self) -> Coordinates {
+        Coordinates {
+            latitude: self.latitude,
+            longitude: self.longitude,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Validate)]
+pub struct Coordinates {
+    #[validate(range(min = -90.0, max = 90.0))]
+    pub latitude: f64,
+    #[validate(range(min = -180.0, max = 180.0))]
+    pub longitude: f64,
+}
+
+/// A zone of the map, polygons are drawn with straight lines between the coordinates
+/// so they are meant for areas of a few kilometres at most.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Area {
+    Circle { center: Coordinates, radius_m: f64 },
+    Polygon { vertices: Vec<Coordinates> },
+}
+
+impl Area {
+    pub fn contains(&self, point: Coordinates) -> bool {
+        match self {
+            Area::Circle { center, radius_m } => {
+                haversine_distance(
+                    (center.latitude, center.longitude),
+                    (point.latitude, point.longitude),
+                ) <= *radius_m
+            }
+            // ray casting, counts the edges crossed by a ray going east of the point
+            Area::Polygon { vertices } => {
+                let mut inside = false;
+                let mut previous = match vertices.last() {
+                    Some(vertex) => vertex,
+                    None => return false,
+                };
+
+                for vertex in vertices {
+                    if (vertex.latitude > point.latitude) != (previous.latitude > point.latitude) {
+                        let crossing_longitude = vertex.longitude
+                            + (point.latitude - vertex.latitude)
+                                * (previous.longitude - vertex.longitude)
+                                / (previous.latitude - vertex.latitude);
+                        if point.longitude < crossing_longitude {
+                            inside = !inside;
+                        }
+                    }
+                    previous = vertex;
+                }
+
+                inside
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub name: Option<String>,
+    pub points: Vec<TrackPoint>,
+}
+
+/// Great-circle distance in metres between two `(latitude, longitude)` in degrees.
+pub fn haversine_distance(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// Length in metres of the track going through the points in order.
+pub fn track_distance(points: &[TrackPoint]) -> f64 {
+    points
+        .windows(2)
+        .map(|pair| {
+            haversine_distance(
+                (pair[0].latitude, pair[0].longitude),
+                (pair[1].latitude, pair[1].longitude),
+            )
+        })
+        .sum()
+}
+
+/// Reads the track points of a GPX 1.0 or 1.1 document, the segments of all its tracks are joined.
+pub fn parse_gpx(content: &str) -> Result<Track, ValidationError> {
+    let document =
+        roxmltree::Document::parse(content).map_err(|_| invalid_gpx("not an xml document"))?;
+
+    let root = document.root_element();
+    if root.tag_name().name() != "gpx" {
+        return Err(invalid_gpx("the root element must be gpx"));
+    }
+
+    let name = root
+        .descendants()
+        .find(|node| node.has_tag_name("trk"))
+        .and_then(|track| track.children().find(|node| node.has_tag_name("name")))
+        .and_then(|node| node.text())
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty());
+
+    let points = root
+        .descendants()
+        .filter(|node| node.has_tag_name("trkpt"))
+        .map(|node| {
+            let coordinate = |attribute| {
+                node.attribute(attribute)
+                    .and_then(|value| value.trim().parse::<f64>().ok())
+                    // NaN passes the range checks and `inf` parses too
+                    .filter(|value| value.is_finite())
+                    .ok_or_else(|| invalid_gpx("track points need a lat and a lon"))
+            };
+            let child_text = |tag| {
+                node.children()
+                    .find(|child| child.has_tag_name(tag))
+                    .and_then(|child| child.text())
+                    .map(str::trim)
+            };
+
+            let elevation = match child_text("ele") {
+                Some(ele) => Some(
+                    ele.parse::<f64>()
+                        .ok()
+                        .filter(|ele| ele.is_finite())
+                        .ok_or_else(|| invalid_gpx("invalid elevation"))?,
+                ),
+                None => None,
+            };
+            let recorded_at = match child_text("time") {
+                Some(time) => Some(
+                    chrono::DateTime::parse_from_rfc3339(time)
+                        .map_err(|_| invalid_gpx("invalid time"))?
+                        .naive_utc(),
+                ),
+                None => None,
+            };
+
+            let point = TrackPoint {
+                latitude: coordinate("lat")?,
+                longitude: coordinate("lon")?,
+                elevation,
+                recorded_at,
+            };
+            point
+                .validate()
+                .map_err(|_| invalid_gpx("coordinates out of range"))?;
+
+            Ok(point)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if points.is_empty() {
+        return Err(invalid_gpx("no track point"));
+    }
+
+    Ok(Track { name, points })
+}
+
+fn invalid_gpx(message: &'static str) -> ValidationError {
+    let mut error = ValidationError::new("invalid_gpx");
+    error.message = Some(message.into());
+    error
+}
+
+/// Writes the points as a single segment GPX 1.1 track.
+pub fn write_gpx(track: &Track) -> String {
+    let mut gpx = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<gpx version="1.1" creator="car_api" xmlns="http://www.topografix.com/GPX/1/1">"#,
+        "\n<trk>\n"
+    ));
+
+    if let Some(name) = &track.name {
+        gpx.push_str(&format!("<name>{}</name>\n", escape_xml(name)));
+    }
+
+    gpx.push_str("<trkseg>\n");
+    for point in &track.points {
+        gpx.push_str(&format!(
+            r#"<trkpt lat="{}" lon="{}">"#,
+            point.latitude, point.longitude
+        ));
+        if let Some(elevation) = point.elevation {
+            gpx.push_str(&format!("<ele>{}</ele>", elevation));
+        }
+        if let Some(recorded_at) = point.recorded_at {
+            gpx.push_str(&format!(
+                "<time>{}</time>",
+                recorded_at.format(GPX_TIME_FORMAT)
+            ));
+        }
+        gpx.push_str("</trkpt>\n");
+    }
+    gpx.push_str("</trkseg>\n</trk>\n</gpx>\n");
+
+    gpx
+}
+
+/// Writes the points as a KML line string.
+pub fn write_kml(track: &Track) -> String {
+    let name = escape_xml(track.name.as_deref().unwrap_or("trip"));
+
+    let coordinates: Vec<String> = track
+        .points
+        .iter()
+        .map(|point| {
+            format!(
+                "{},{},{}",
+                point.longitude,
+                point.latitude,
+                point.elevation.unwrap_or(0.0)
+            )
+        })
+        .collect();
+
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n",
+            r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#,
+            "\n<Document>\n<name>{name}</name>\n<Placemark>\n<name>{name}</name>\n",
+            "<LineString>\n<tessellate>1</tessellate>\n<coordinates>\n{coordinates}\n</coordinates>\n",
+            "</LineString>\n</Placemark>\n</Document>\n</kml>\n"
+        ),
+        name = name,
+        coordinates = coordinates.join("\n")
+    )
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,236 @@
+use validator::ValidationError;
+
+/// A country of the ISO 13616 IBAN registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IbanCountry {
+    /// ISO 3166-1 alpha-2 code, the first two characters of the IBAN.
+    pub code: &'static str,
+    pub name: &'static str,
+    /// Structure of the account number following the check digits, in the notation of the
+    /// registry: `5n` is five digits, `4a` four upper case letters and `11c` eleven alphanumerics.
+    bban: &'static str,
+}
+
+impl IbanCountry {
+    /// Length of the IBANs of the country, country code and check digits included.
+    pub fn iban_length(&self) -> usize {
+        4 + bban_segments(self.bban).map(|(len, _)| len).sum::<usize>()
+    }
+}
+
+const IBAN_COUNTRIES: [IbanCountry; 76] = [
+    country("AD", "Andorra", "4n4n12c"),
+    country("AE", "United Arab Emirates", "3n16n"),
+    country("AL", "Albania", "8n16c"),
+    country("AT", "Austria", "5n11n"),
+    country("AZ", "Azerbaijan", "4a20c"),
+    country("BA", "Bosnia and Herzegovina", "3n3n8n2n"),
+    country("BE", "Belgium", "3n7n2n"),
+    country("BG", "Bulgaria", "4a4n2n8c"),
+    country("BH", "Bahrain", "4a14c"),
+    country("BR", "Brazil", "8n5n10n1a1c"),
+    country("BY", "Belarus", "4c4n16c"),
+    country("CH", "Switzerland", "5n12c"),
+    country("CR", "Costa Rica", "4n14n"),
+    country("CY", "Cyprus", "3n5n16c"),
+    country("CZ", "Czechia", "4n6n10n"),
+    country("DE", "Germany", "8n10n"),
+    country("DK", "Denmark", "4n9n1n"),
+    country("DO", "Dominican Republic", "4c20n"),
+    country("EE", "Estonia", "2n2n11n1n"),
+    country("EG", "Egypt", "4n4n17n"),
+    country("ES", "Spain", "4n4n1n1n10n"),
+    country("FI", "Finland", "3n11n"),
+    country("FO", "Faroe Islands", "4n9n1n"),
+    country("FR", "France", "5n5n11c2n"),
+    country("GB", "United Kingdom", "4a6n8n"),
+    country("GE", "Georgia", "2a16n"),
+    country("GI", "Gibraltar", "4a15c"),
+    country("GL", "Greenland", "4n9n1n"),
+    country("GR", "Greece", "3n4n16c"),
+    country("GT", "Guatemala", "4c20c"),
+    country("HR", "Croatia", "7n10n"),
+    country("HU", "Hungary", "3n4n1n15n1n"),
+    country("IE", "Ireland", "4a6n8n"),
+    country("IL", "Israel", "3n3n13n"),
+    country("IQ", "Iraq", "4a3n12n"),
+    country("IS", "Iceland", "4n2n6n10n"),
+    country("IT", "Italy", "1a5n5n12c"),
+    country("JO", "Jordan", "4a4n18c"),
+    country("KW", "Kuwait", "4a22c"),
+    country("KZ", "Kazakhstan", "3n13c"),
+    country("LB", "Lebanon", "4n20c"),
+    country("LC", "Saint Lucia", "4a24c"),
+    country("LI", "Liechtenstein", "5n12c"),
+    country("LT", "Lithuania", "5n11n"),
+    country("LU", "Luxembourg", "3n13c"),
+    country("LV", "Latvia", "4a13c"),
+    country("MC", "Monaco", "5n5n11c2n"),
+    country("MD", "Moldova", "2c18c"),
+    country("ME", "Montenegro", "3n13n2n"),
+    country("MK", "North Macedonia", "3n10c2n"),
+    country("MR", "Mauritania", "5n5n11n2n"),
+    country("MT", "Malta", "4a5n18c"),
+    country("MU", "Mauritius", "4a2n2n12n3n3a"),
+    country("NL", "Netherlands", "4a10n"),
+    country("NO", "Norway", "4n6n1n"),
+    country("PK", "Pakistan", "4a16c"),
+    country("PL", "Poland", "8n16n"),
+    country("PS", "Palestine", "4a21c"),
+    country("PT", "Portugal", "4n4n11n2n"),
+    country("QA", "Qatar", "4a21c"),
+    country("RO", "Romania", "4a16c"),
+    country("RS", "Serbia", "3n13n2n"),
+    country("SA", "Saudi Arabia", "2n18c"),
+    country("SC", "Seychelles", "4a2n2n16n3a"),
+    country("SE", "Sweden", "3n16n1n"),
+    country("SI", "Slovenia", "5n8n2n"),
+    country("SK", "Slovakia", "4n6n10n"),
+    country("SM", "San Marino", "1a5n5n12c"),
+    country("ST", "Sao Tome and Principe", "4n4n11n2n"),
+    country("SV", "El Salvador", "4a20n"),
+    country("TL", "Timor-Leste", "3n14n2n"),
+    country("TN", "Tunisia", "2n3n13n2n"),
+    country("TR", "Turkey", "5n1n16c"),
+    country("UA", "Ukraine", "6n19c"),
+    country("VA", "Vatican City", "3n15n"),
+    country("XK", "Kosovo", "4n10n2n"),
+];
+
+const fn country(code: &'static str, name: &'static str, bban: &'static str) -> IbanCountry {
+    IbanCountry { code, name, bban }
+}
+
+/// The country matching an alpha-2 code or an english name, case is ignored.
+pub fn find_iban_country(country: &str) -> Option<&'static IbanCountry> {
+    let country = country.trim();
+    IBAN_COUNTRIES.iter().find(|iban_country| {
+        iban_country.code.eq_ignore_ascii_case(country)
+            || iban_country.name.eq_ignore_ascii_case(country)
+    })
+}
+
+/// Removes the spaces of the printed format and upper cases the letters,
+/// IBANs and BICs are stored and validated in this form.
+pub fn normalize_account_identifier(identifier: &str) -> String {
+    identifier
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Hides the IBAN but its country code, check digits and last four characters,
+/// `FR76 **** **** 1234`. IBANs too short to be valid are hidden entirely.
+pub fn mask_iban(iban: &str) -> String {
+    let iban = normalize_account_identifier(iban);
+
+    match (iban.get(..4), iban.get(iban.len().saturating_sub(4)..)) {
+        (Some(start), Some(end)) if iban.len() >= 12 => format!("{} **** **** {}", start, end),
+        _ => "****".into(),
+    }
+}
+
+/// Checks the length and structure registered for the country of the IBAN and its
+/// ISO 13616 mod-97 check digits. The IBAN is normalized first.
+pub fn validate_iban(iban: &str) -> Result<(), ValidationError> {
+    let iban = normalize_account_identifier(iban);
+
+    if !iban.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ValidationError::new("invalid_iban"));
+    }
+    let iban_country = iban
+        .get(..2)
+        .and_then(|code| IBAN_COUNTRIES.iter().find(|country| country.code == code))
+        .ok_or_else(|| ValidationError::new("unsupported_iban_country"))?;
+    if iban.len() != iban_country.iban_length() {
+        return Err(ValidationError::new("invalid_iban_length"));
+    }
+    if !iban[2..4].chars().all(|c| c.is_ascii_digit())
+        || !matches_bban_structure(&iban[4..], iban_country.bban)
+    {
+        return Err(ValidationError::new("invalid_iban_format"));
+    }
+    if iban_remainder(&iban) != 1 {
+        return Err(ValidationError::new("invalid_iban_checksum"));
+    }
+
+    Ok(())
+}
+
+/// ISO 9362 format: a 4 letter institution code, the 2 letter country code,
+/// a 2 character location code and an optional 3 character branch code.
+pub fn validate_bic(bic: &str) -> Result<(), ValidationError> {
+    let bic = normalize_account_identifier(bic);
+
+    let valid = matches!(bic.len(), 8 | 11)
+        && bic.is_ascii()
+        && bic[..6].chars().all(|c| c.is_ascii_uppercase())
+        && bic[6..].chars().all(|c| c.is_ascii_alphanumeric());
+
+    if !valid {
+        return Err(ValidationError::new("invalid_bic"));
+    }
+
+    Ok(())
+}
+
+/// The IBAN with its first four characters moved to the end and its letters replaced
+/// by numbers (A = 10 ... Z = 35), modulo 97. The number is reduced digit by digit
+/// as it is far larger than any integer type.
+fn iban_remainder(iban: &str) -> u32 {
+    iban[4..]
+        .chars()
+        .chain(iban[..4].chars())
+        .filter_map(|c| c.to_digit(36))
+        .fold(0, |remainder, value| {
+            let shift = if value < 10 { 10 } else { 100 };
+            (remainder * shift + value) % 97
+        })
+}
+
+fn matches_bban_structure(bban: &str, structure: &'static str) -> bool {
+    let mut chars = bban.chars();
+
+    let segments_match = bban_segments(structure).all(|(len, kind)| {
+        chars
+            .by_ref()
+            .take(len)
+            .filter(|c| kind.matches(*c))
+            .count()
+            == len
+    });
+
+    segments_match && chars.next().is_none()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BbanCharacter {
+    Digit,
+    Letter,
+    Alphanumeric,
+}
+
+impl BbanCharacter {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            BbanCharacter::Digit => c.is_ascii_digit(),
+            BbanCharacter::Letter => c.is_ascii_uppercase(),
+            BbanCharacter::Alphanumeric => c.is_ascii_digit() || c.is_ascii_uppercase(),
+        }
+    }
+}
+
+fn bban_segments(structure: &'static str) -> impl Iterator<Item = (usize, BbanCharacter)> {
+    structure
+        .split_inclusive(|c: char| c.is_ascii_alphabetic())
+        .map(|segment| {
+            let (len, kind) = segment.split_at(segment.len() - 1);
+            let kind = match kind {
+                "n" => BbanCharacter::Digit,
+                "a" => BbanCharacter::Letter,
+                _ => BbanCharacter::Alphanumeric,
+            };
+            (len.parse().unwrap_or_default(), kind)
+        })
+}
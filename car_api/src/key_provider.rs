@@ -0,0 +1,290 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::encrypt::Keyring;
+use crate::errors::Error;
+
+/// Where the encryption keys come from, they are loaded once when the api starts.
+#[async_trait]
+pub trait KeyProvider: Send + Sync {
+    async fn keyring(&self) -> Result<Keyring, Error>;
+}
+
+/// Returns the provider named by `ENCRYPTION_KEY_PROVIDER`: `env` (the default), `file` or `vault`.
+pub fn key_provider_from_env() -> Result<Box<dyn KeyProvider>, Error> {
+    match std::env::var("ENCRYPTION_KEY_PROVIDER").as_deref() {
+        Ok("env") | Err(_) => Ok(Box::new(EnvKeyProvider)),
+        Ok("file") => Ok(Box::new(SecretFileKeyProvider::from_env()?)),
+        Ok("vault") => Ok(Box::new(VaultTransitKeyProvider::from_env()?)),
+        Ok(provider) => Err(Error::MissingEncryptionKey(format!(
+            "unknown ENCRYPTION_KEY_PROVIDER {}",
+            provider
+        ))),
+    }
+}
+
+/// Reads the keys from `ENCRYPTION_KEYS` or `ENCRYPTION_SECRET`, see `Keyring::from_env`.
+pub struct EnvKeyProvider;
+
+#[async_trait]
+impl KeyProvider for EnvKeyProvider {
+    async fn keyring(&self) -> Result<Keyring, Error> {
+        Keyring::from_env()
+    }
+}
+
+/// Reads the keys from a directory of mounted secrets (Docker or Kubernetes), each file is
+/// a key named by its file name. Hidden files, such as the `..data` links of Kubernetes,
+/// are ignored.
+pub struct SecretFileKeyProvider {
+    directory: PathBuf,
+    encryption_key_id: Option<String>,
+    default_secret: Option<String>,
+}
+
+impl SecretFileKeyProvider {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            encryption_key_id: None,
+            default_secret: None,
+        }
+    }
+
+    /// The key new values are encrypted with, it can be left out when the directory has a single key.
+    pub fn with_encryption_key_id(mut self, key_id: &str) -> Self {
+        self.encryption_key_id = Some(key_id.to_owned());
+        self
+    }
+
+    /// The secret of the values written before the keys were in files, see `Keyring::with_default_secret`.
+    pub fn with_default_secret(mut self, secret: &str) -> Self {
+        self.default_secret = Some(secret.to_owned());
+        self
+    }
+
+    /// Returns a provider reading `ENCRYPTION_KEYS_DIR` with the `ENCRYPTION_KEY_ID` key,
+    /// `ENCRYPTION_SECRET` still decrypts the previous values.
+    pub fn from_env() -> Result<Self, Error> {
+        let directory = std::env::var("ENCRYPTION_KEYS_DIR").map_err(|_| {
+            Error::MissingEncryptionKey("failed to find ENCRYPTION_KEYS_DIR in env".to_owned())
+        })?;
+        let mut provider = Self::new(directory);
+        if let Ok(key_id) = std::env::var("ENCRYPTION_KEY_ID") {
+            provider = provider.with_encryption_key_id(&key_id);
+        }
+        if let Ok(secret) = std::env::var("ENCRYPTION_SECRET") {
+            provider = provider.with_default_secret(&secret);
+        }
+
+        Ok(provider)
+    }
+}
+
+#[async_trait]
+impl KeyProvider for SecretFileKeyProvider {
+    async fn keyring(&self) -> Result<Keyring, Error> {
+        let mut keys = Vec::new();
+
+        let mut entries = tokio::fs::read_dir(&self.directory).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let key_id = entry.file_name().to_string_lossy().into_owned();
+            // the metadata follows the links the secrets are mounted with
+            if key_id.starts_with('.') || !tokio::fs::metadata(entry.path()).await?.is_file() {
+                continue;
+            }
+            let secret = tokio::fs::read_to_string(entry.path()).await?;
+            keys.push((key_id, secret.trim_end_matches(['\n', '\r']).to_owned()));
+        }
+        keys.sort();
+
+        let encryption_key_id = match (&self.encryption_key_id, keys.as_slice()) {
+            (Some(key_id), _) => key_id.clone(),
+            (None, [(key_id, _)]) => key_id.clone(),
+            _ => {
+                return Err(Error::MissingEncryptionKey(format!(
+                    "ENCRYPTION_KEY_ID must name one of the keys of {}",
+                    self.directory.display()
+                )))
+            }
+        };
+
+        let keyring = Keyring::new(&encryption_key_id, keys)?;
+        match &self.default_secret {
+            Some(secret) => keyring.with_default_secret(secret),
+            None => Ok(keyring),
+        }
+    }
+}
+
+/// Keeps the keys wrapped by a HashiCorp Vault transit key, they are unwrapped with the
+/// `/v1/<mount>/decrypt/<key>` endpoint when the api starts. The secret of a key is the
+/// base64 plaintext returned by Vault, so keys created with `/datakey/wrapped` can be used as is.
+pub struct VaultTransitKeyProvider {
+    address: String,
+    token: String,
+    mount: String,
+    key_name: String,
+    /// Key ids with their Vault ciphertext, `vault:v1:...`.
+    wrapped_keys: Vec<(String, String)>,
+    encryption_key_id: Option<String>,
+    default_secret: Option<String>,
+    client: reqwest::Client,
+}
+
+#[derive(Serialize)]
+struct TransitDecryptRequest<'a> {
+    ciphertext: &'a str,
+}
+
+#[derive(Deserialize)]
+struct TransitDecryptResponse {
+    data: TransitDecryptData,
+}
+
+#[derive(Deserialize)]
+struct TransitDecryptData {
+    plaintext: String,
+}
+
+impl VaultTransitKeyProvider {
+    pub fn new(
+        address: &str,
+        token: &str,
+        key_name: &str,
+        wrapped_keys: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            address: address.trim_end_matches('/').to_owned(),
+            token: token.to_owned(),
+            mount: "transit".to_owned(),
+            key_name: key_name.to_owned(),
+            wrapped_keys,
+            encryption_key_id: None,
+            default_secret: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Path the transit secrets engine is mounted at, `transit` by default.
+    pub fn with_mount(mut self, mount: &str) -> Self {
+        self.mount = mount.trim_matches('/').to_owned();
+        self
+    }
+
+    /// The key new values are encrypted with, the first one by default.
+    pub fn with_encryption_key_id(mut self, key_id: &str) -> Self {
+        self.encryption_key_id = Some(key_id.to_owned());
+        self
+    }
+
+    /// The secret of the values written before the keys were in Vault, see `Keyring::with_default_secret`.
+    pub fn with_default_secret(mut self, secret: &str) -> Self {
+        self.default_secret = Some(secret.to_owned());
+        self
+    }
+
+    /**
+     * Returns a provider for the Vault of `VAULT_ADDR` authenticated by `VAULT_TOKEN`. The
+     * transit key is `VAULT_TRANSIT_KEY`, `car_api` by default, mounted at `VAULT_TRANSIT_MOUNT`.
+     * `ENCRYPTION_KEYS` lists the wrapped keys as `id:vault:v1:...` separated by commas and
+     * `ENCRYPTION_KEY_ID` names the one new values are encrypted with. `ENCRYPTION_SECRET` still
+     * decrypts the previous values.
+     */
+    pub fn from_env() -> Result<Self, Error> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .map_err(|_| Error::MissingEncryptionKey(format!("failed to find {} in env", name)))
+        };
+
+        let wrapped_keys = var("ENCRYPTION_KEYS")?
+            .split(',')
+            .filter(|key| !key.trim().is_empty())
+            .map(|key| match key.split_once(':') {
+                Some((key_id, ciphertext)) => {
+                    Ok((key_id.trim().to_owned(), ciphertext.trim().to_owned()))
+                }
+                None => Err(Error::MissingEncryptionKey(
+                    "ENCRYPTION_KEYS entries must be id:vault:v1:...".to_owned(),
+                )),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let key_name = var("VAULT_TRANSIT_KEY").unwrap_or_else(|_| "car_api".to_owned());
+
+        let mut provider = Self::new(
+            &var("VAULT_ADDR")?,
+            &var("VAULT_TOKEN")?,
+            &key_name,
+            wrapped_keys,
+        );
+        if let Ok(mount) = var("VAULT_TRANSIT_MOUNT") {
+            provider = provider.with_mount(&mount);
+        }
+        if let Ok(key_id) = var("ENCRYPTION_KEY_ID") {
+            provider = provider.with_encryption_key_id(&key_id);
+        }
+        if let Ok(secret) = var("ENCRYPTION_SECRET") {
+            provider = provider.with_default_secret(&secret);
+        }
+
+        Ok(provider)
+    }
+
+    async fn unwrap_key(&self, key_id: &str, ciphertext: &str) -> Result<String, Error> {
+        let url = format!(
+            "{}/v1/{}/decrypt/{}",
+            self.address, self.mount, self.key_name
+        );
+        let failed = |reason: String| {
+            Error::MissingEncryptionKey(format!(
+                "failed to unwrap the encryption key {} with Vault: {}",
+                key_id, reason
+            ))
+        };
+
+        let response = self
+            .client
+            .post(&url)
+            .header("X-Vault-Token", &self.token)
+            .json(&TransitDecryptRequest { ciphertext })
+            .send()
+            .await
+            .map_err(|err| failed(err.to_string()))?;
+        if !response.status().is_success() {
+            return Err(failed(response.status().to_string()));
+        }
+        let response = response
+            .json::<TransitDecryptResponse>()
+            .await
+            .map_err(|err| failed(err.to_string()))?;
+
+        Ok(response.data.plaintext)
+    }
+}
+
+#[async_trait]
+impl KeyProvider for VaultTransitKeyProvider {
+    async fn keyring(&self) -> Result<Keyring, Error> {
+        let mut keys = Vec::with_capacity(self.wrapped_keys.len());
+        for (key_id, ciphertext) in &self.wrapped_keys {
+            keys.push((key_id.clone(), self.unwrap_key(key_id, ciphertext).await?));
+        }
+
+        let encryption_key_id = match &self.encryption_key_id {
+            Some(key_id) => key_id.clone(),
+            None => keys
+                .first()
+                .map(|(key_id, _)| key_id.clone())
+                .ok_or_else(|| {
+                    Error::MissingEncryptionKey("ENCRYPTION_KEYS is empty".to_owned())
+                })?,
+        };
+
+        let keyring = Keyring::new(&encryption_key_id, keys)?;
+        match &self.default_secret {
+            Some(secret) => keyring.with_default_secret(secret),
+            None => Ok(keyring),
+        }
+    }
+}
@@ -3,4 +3,10 @@ pub mod startup;
 
 pub mod encrypt;
 pub mod errors;
+pub mod geo;
+pub mod iban;
+pub mod key_provider;
+pub mod photo;
 pub mod routes;
+pub mod sepa;
+pub mod storage;
@@ -3,14 +3,24 @@ mod startup;
 
 mod encrypt;
 mod errors;
+mod geo;
+mod iban;
+mod key_provider;
+mod photo;
 mod routes;
+mod sepa;
+mod storage;
 
 use database::get_pg_pool;
+use encrypt::install_keyring;
+use key_provider::key_provider_from_env;
+use routes::bank_details::reencrypt_bank_details;
+use routes::recall::import_recalls_file;
 use startup::run;
 
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::{env, net::TcpListener};
-use tracing::debug;
+use std::{env, net::TcpListener, path::Path};
+use tracing::{debug, error, info};
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
@@ -18,6 +28,65 @@ async fn main() -> std::io::Result<()> {
     // initialize tracing
     tracing_subscriber::fmt::init();
 
+    // `car_api import-recalls <file>` imports a CSV or JSON recall feed instead of serving the api
+    if env::args().nth(1).as_deref() == Some("import-recalls") {
+        let path = env::args().nth(2).unwrap_or_else(|| {
+            error!("usage: car_api import-recalls <file>");
+            std::process::exit(1);
+        });
+        let pg_pool = get_pg_pool().await;
+
+        match import_recalls_file(&pg_pool, Path::new(&path)).await {
+            Ok(imported) => {
+                info!("imported {} recalls from {}", imported, path);
+                return Ok(());
+            }
+            Err(err) => {
+                error!("failed to import {}: {:?}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // the keys are loaded once, from the provider named by ENCRYPTION_KEY_PROVIDER
+    let keyring = match key_provider_from_env() {
+        Ok(provider) => provider.keyring().await,
+        Err(err) => Err(err),
+    };
+    let keyring = keyring.unwrap_or_else(|err| {
+        error!("failed to load the encryption keys: {:?}", err);
+        std::process::exit(1);
+    });
+    install_keyring(keyring.clone()).expect("the encryption keys are loaded once");
+
+    // `car_api reencrypt-bank-details [batch size]` encrypts the IBANs with the current key
+    if env::args().nth(1).as_deref() == Some("reencrypt-bank-details") {
+        let batch_size = match env::args().nth(2).map(|size| size.parse::<i64>()) {
+            Some(Ok(batch_size)) if batch_size > 0 => batch_size,
+            None => 500,
+            _ => {
+                error!("usage: car_api reencrypt-bank-details [batch size]");
+                std::process::exit(1);
+            }
+        };
+        let pg_pool = get_pg_pool().await;
+
+        match reencrypt_bank_details(&pg_pool, &keyring, batch_size).await {
+            Ok(reencrypted) => {
+                info!(
+                    "re-encrypted {} IBANs with the key {}",
+                    reencrypted,
+                    keyring.encryption_key_id()
+                );
+                return Ok(());
+            }
+            Err(err) => {
+                error!("failed to re-encrypt the IBANs: {:?}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
     let port = match env::var("PORT") {
         Ok(p) => p.parse::<u16>().unwrap(),
         _ => 8080,
@@ -0,0 +1,159 @@
+use image::{imageops::FilterType, io::Limits, DynamicImage, ImageFormat, ImageOutputFormat};
+use serde::{Deserialize, Serialize};
+use validator::ValidationError;
+
+use std::io::Cursor;
+
+/// Largest width or height of an uploaded photo, in pixels.
+const MAX_DIMENSION: u32 = 8_192;
+
+/// Largest number of pixels of an uploaded photo, a 36 megapixels camera.
+const MAX_PIXELS: u64 = 36_000_000;
+
+/// Largest buffer the decoded photo may take, its pixels in 8 bits RGBA.
+const MAX_DECODED_BYTES: u64 = MAX_PIXELS * 4;
+
+const JPEG_QUALITY: u8 = 85;
+
+/// The sizes a photo is stored in, the original keeps the uploaded dimensions.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PhotoVariant {
+    Original,
+    Medium,
+    Thumbnail,
+}
+
+impl PhotoVariant {
+    pub const ALL: [PhotoVariant; 3] = [
+        PhotoVariant::Original,
+        PhotoVariant::Medium,
+        PhotoVariant::Thumbnail,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PhotoVariant::Original => "original",
+            PhotoVariant::Medium => "medium",
+            PhotoVariant::Thumbnail => "thumbnail",
+        }
+    }
+
+    /// Largest width or height of the variant, the photo is never enlarged.
+    fn max_dimension(&self) -> Option<u32> {
+        match self {
+            PhotoVariant::Original => None,
+            PhotoVariant::Medium => Some(1024),
+            PhotoVariant::Thumbnail => Some(256),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessedPhoto {
+    /// `image/jpeg` or `image/png`, the variants keep the format of the upload.
+    pub content_type: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub variants: Vec<(PhotoVariant, Vec<u8>)>,
+}
+
+/// Decodes a JPEG or PNG photo and encodes it again in every variant. The EXIF orientation is
+/// applied to the pixels, the metadata itself (GPS position, camera...) is not written back.
+pub fn process_photo(content: &[u8]) -> Result<ProcessedPhoto, ValidationError> {
+    let mut reader = image::io::Reader::new(Cursor::new(content))
+        .with_guessed_format()
+        .map_err(|_| invalid_image("unreadable image"))?;
+
+    let (format, content_type, output_format) = match reader.format() {
+        Some(ImageFormat::Jpeg) => (
+            ImageFormat::Jpeg,
+            "image/jpeg",
+            ImageOutputFormat::Jpeg(JPEG_QUALITY),
+        ),
+        Some(ImageFormat::Png) => (ImageFormat::Png, "image/png", ImageOutputFormat::Png),
+        _ => return Err(invalid_image("the image must be a jpeg or a png")),
+    };
+
+    // the size is read from the header, before anything is decoded
+    let (width, height) = image::io::Reader::with_format(Cursor::new(content), format)
+        .into_dimensions()
+        .map_err(|_| invalid_image("the image cannot be decoded"))?;
+    if u64::from(width) * u64::from(height) > MAX_PIXELS {
+        return Err(invalid_image("the image has too many pixels"));
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODED_BYTES);
+    reader.limits(limits);
+
+    let image = reader
+        .decode()
+        .map_err(|_| invalid_image("the image cannot be decoded"))?;
+    let image = apply_orientation(image, exif_orientation(content));
+
+    let variants = PhotoVariant::ALL
+        .iter()
+        .map(|variant| {
+            // the original is encoded as is rather than copied
+            let encoded = match variant.max_dimension() {
+                Some(max) if image.width() > max || image.height() > max => encode(
+                    &image.resize(max, max, FilterType::Triangle),
+                    output_format.clone(),
+                ),
+                _ => encode(&image, output_format.clone()),
+            }?;
+
+            Ok((*variant, encoded))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ProcessedPhoto {
+        content_type,
+        width: image.width(),
+        height: image.height(),
+        variants,
+    })
+}
+
+fn encode(image: &DynamicImage, format: ImageOutputFormat) -> Result<Vec<u8>, ValidationError> {
+    let mut encoded = Cursor::new(Vec::new());
+    image
+        .write_to(&mut encoded, format)
+        .map_err(|_| invalid_image("the image cannot be encoded"))?;
+
+    Ok(encoded.into_inner())
+}
+
+/// The EXIF orientation of the image, 1 (upright) when it has none.
+fn exif_orientation(content: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(content))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .unwrap_or(1)
+}
+
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+fn invalid_image(message: &'static str) -> ValidationError {
+    let mut error = ValidationError::new("invalid_image");
+    error.message = Some(message.into());
+    error
+}
@@ -1,33 +1,44 @@
 use super::{
     authenticate::User,
+    bank_details::{
+        get_account_bank_details, insert_bank_details_in_table, BankDetailsInfo, NewBankDetails,
+    },
+    car::CarRole,
+    ev::EvSpecs,
+    parking::ParkedLocation,
+    photo::Photo,
+    recall::{validate_vin, Recall},
     user::{insert_user_in_table, NewUser},
     AppState,
 };
 use crate::{
-    encrypt::{decrypt_data, encrypt_data},
+    encrypt::{encrypt_data, spawn_crypto},
     errors::Error,
 };
 
 use axum::{extract::State, http::StatusCode, Extension, Json};
+use axum_extra::extract::Query;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use serde::{Deserialize, Serialize};
-use sqlx::types::uuid::Uuid;
-use sqlx::PgPool;
+use sqlx::types::{uuid::Uuid, Json as SqlJson};
+use sqlx::{PgExecutor, PgPool, Postgres, QueryBuilder};
+use validator::{Validate, ValidationError, ValidationErrors};
 
 pub type Result<T, E = Error> = ::std::result::Result<T, E>;
 
 use std::sync::Arc;
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Validate)]
 pub struct NewCar {
     pub car_model: String,
     pub car_plate: String,
-}
-
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
-pub struct NewBankDetails {
-    pub account_holder: String,
-    pub bank_country: String,
-    pub iban: String,
+    /// The manufacturer, VIN and model year are used to find the recalls of the car.
+    #[validate(length(min = 1, max = 255))]
+    pub car_manufacturer: Option<String>,
+    #[validate(custom = "validate_vin")]
+    pub car_vin: Option<String>,
+    #[validate(range(min = 1886, max = 2100))]
+    pub car_year: Option<i32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -41,8 +52,13 @@ pub async fn create_account(
     State(state): State<Arc<AppState>>,
     Json(mut new_account): Json<CreateAccount>,
 ) -> Result<StatusCode> {
-    new_account.user.password = encrypt_data(new_account.user.password)?;
-    new_account.bank_details.iban = encrypt_data(new_account.bank_details.iban)?;
+    new_account.car_info.validate()?;
+    new_account.bank_details.normalize();
+    new_account.bank_details.validate()?;
+    let password = new_account.user.password.clone();
+    let iban = new_account.bank_details.iban.clone();
+    (new_account.user.password, new_account.bank_details.iban) =
+        spawn_crypto(move || Ok((encrypt_data(password)?, encrypt_data(iban)?))).await?;
 
     let user_id = insert_user_in_table(&state.pg_pool, &new_account.user).await?;
 
@@ -58,38 +74,23 @@ pub async fn create_account(
     Ok(StatusCode::CREATED)
 }
 
-pub async fn insert_car_in_table(pg_pool: &PgPool, user_id: &Uuid, account: &NewCar) -> Result<()> {
+pub async fn insert_car_in_table<'c, E>(executor: E, user_id: &Uuid, account: &NewCar) -> Result<()>
+where
+    E: PgExecutor<'c>,
+{
     sqlx::query(
         r#"
-        INSERT INTO car(user_id, plate, model)
-        VALUES ($1, $2, $3)
+        INSERT INTO car(user_id, plate, model, manufacturer, vin, model_year)
+        VALUES ($1, $2, $3, $4, $5, $6)
     "#,
     )
     .bind(user_id)
     .bind(&account.car_plate)
     .bind(&account.car_model)
-    .execute(pg_pool)
-    .await?;
-
-    Ok(())
-}
-
-pub async fn insert_bank_details_in_table(
-    pg_pool: &PgPool,
-    user_id: &Uuid,
-    account: &NewBankDetails,
-) -> Result<()> {
-    sqlx::query(
-        r#"
-        INSERT INTO bank_details(user_id, country, iban, account_holder)
-        VALUES ($1, $2, $3, $4)
-    "#,
-    )
-    .bind(user_id)
-    .bind(&account.bank_country)
-    .bind(&account.iban)
-    .bind(&account.account_holder)
-    .execute(pg_pool)
+    .bind(account.car_manufacturer.as_deref().map(str::trim))
+    .bind(account.car_vin.as_deref().map(str::to_uppercase))
+    .bind(account.car_year)
+    .execute(executor)
     .await?;
 
     Ok(())
@@ -97,76 +98,366 @@ pub async fn insert_bank_details_in_table(
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AccountDetails {
-    user: User,
-    cars_info: Vec<CarInfo>,
-    bank_details: BankDetailsInfo,
+    pub user: User,
+    /// The first page of the cars, the following ones are listed by `/api/cars`.
+    pub cars_info: Vec<CarInfo>,
+    pub cars_next: Option<String>,
+    /// The bank accounts of the user, the primary one first.
+    pub bank_details: Vec<BankDetailsInfo>,
 }
 
 pub async fn get_account_details(
     Extension(user): Extension<User>,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<AccountDetails>> {
-    let cars = get_account_cars_info(&user, &state.pg_pool);
+    let query = CarsQuery::default();
+    let cars = get_account_cars_info(&user, &query, &state.pg_pool);
     let bank_details = get_account_bank_details(&user, &state.pg_pool);
 
-    let (cars_info, mut bank_details) = tokio::try_join!(cars, bank_details).unwrap();
-
-    bank_details.iban = decrypt_data(bank_details.iban)?;
+    let (cars_page, bank_details) = tokio::try_join!(cars, bank_details)?;
 
     let account_details = AccountDetails {
         user,
-        cars_info,
+        cars_info: cars_page.cars,
+        cars_next: cars_page.next,
         bank_details,
     };
 
     Ok(Json(account_details))
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CarInfo {
     pub id: Uuid,
     pub model: String,
     pub plate: String,
+    pub manufacturer: Option<String>,
+    pub vin: Option<String>,
+    pub model_year: Option<i32>,
+    /// Cars shared with the user are listed along the ones they own.
+    pub role: CarRole,
+    /// Mileage of the latest odometer reading.
+    pub current_mileage: Option<i64>,
+    /// Set on the read-only snapshot kept after transferring the car to another account.
+    pub archived_at: Option<chrono::NaiveDateTime>,
+    /// Where the car was parked last.
+    pub location: Option<ParkedLocation>,
+    /// Set on electric cars.
+    pub ev_specs: Option<EvSpecs>,
+    pub primary_photo: Option<Photo>,
+    /// Recalls of the manufacturer matching the car which were not repaired yet.
+    pub open_recalls: Vec<Recall>,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
 
-pub async fn get_account_cars_info(user: &User, pg_pool: &PgPool) -> Result<Vec<CarInfo>> {
-    let cars_info = sqlx::query_as::<_, CarInfo>(
-        r#"
-        SELECT * 
-        FROM car
-        WHERE user_id=$1
-    "#,
-    )
-    .bind(user.id)
-    .fetch_all(pg_pool)
-    .await?;
+/// A row of the car listing, the json columns are unwrapped into a `CarInfo`.
+#[derive(sqlx::FromRow)]
+struct CarInfoRow {
+    id: Uuid,
+    model: String,
+    plate: String,
+    manufacturer: Option<String>,
+    vin: Option<String>,
+    model_year: Option<i32>,
+    role: CarRole,
+    current_mileage: Option<i64>,
+    archived_at: Option<chrono::NaiveDateTime>,
+    location: Option<SqlJson<ParkedLocation>>,
+    ev_specs: Option<SqlJson<EvSpecs>>,
+    primary_photo: Option<SqlJson<Photo>>,
+    open_recalls: SqlJson<Vec<Recall>>,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+}
 
-    Ok(cars_info)
+impl From<CarInfoRow> for CarInfo {
+    fn from(row: CarInfoRow) -> Self {
+        Self {
+            id: row.id,
+            model: row.model,
+            plate: row.plate,
+            manufacturer: row.manufacturer,
+            vin: row.vin,
+            model_year: row.model_year,
+            role: row.role,
+            current_mileage: row.current_mileage,
+            archived_at: row.archived_at,
+            location: row.location.map(|location| location.0),
+            ev_specs: row.ev_specs.map(|specs| specs.0),
+            primary_photo: row.primary_photo.map(|photo| photo.0),
+            open_recalls: row.open_recalls.0,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
-pub struct BankDetailsInfo {
-    pub id: Uuid,
-    pub country: String,
-    pub iban: String,
-    pub account_holder: String,
-    pub created_at: chrono::NaiveDateTime,
-    pub updated_at: chrono::NaiveDateTime,
+/// Filters, sorting and page of the car listing. When a cursor is given the other parameters
+/// are ignored, the cursor carries the query it was created for.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Validate)]
+pub struct CarsQuery {
+    /// Repeated to list several models.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub model: Vec<String>,
+    /// Part of the plate, case insensitive.
+    #[validate(length(min = 1, max = 255))]
+    pub plate: Option<String>,
+    pub created_from: Option<chrono::NaiveDateTime>,
+    pub created_to: Option<chrono::NaiveDateTime>,
+    #[serde(default)]
+    pub sort: CarSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    #[validate(range(min = 1, max = 200))]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing)]
+    pub cursor: Option<String>,
+}
+
+const DEFAULT_CARS_LIMIT: i64 = 50;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CarSort {
+    #[default]
+    CreatedAt,
+    Model,
+    Plate,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Position in the listing, encoded as url safe base64 json in the `cursor` parameter.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PageCursor {
+    query: CarsQuery,
+    /// Lists the cars before the position instead of after it.
+    backward: bool,
+    /// Sort value and id of the car at the position.
+    value: String,
+    id: Uuid,
 }
 
-pub async fn get_account_bank_details(user: &User, pg_pool: &PgPool) -> Result<BankDetailsInfo> {
-    let bank_details = sqlx::query_as::<_, BankDetailsInfo>(
+impl PageCursor {
+    fn at(query: &CarsQuery, car: &CarInfo, backward: bool) -> Self {
+        let value = match query.sort {
+            CarSort::CreatedAt => car.created_at.to_string(),
+            CarSort::Model => car.model.clone(),
+            CarSort::Plate => car.plate.clone(),
+        };
+
+        Self {
+            query: query.clone(),
+            backward,
+            value,
+            id: car.id,
+        }
+    }
+
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(cursor: &str) -> Result<Self> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Self>(&json).ok())
+            .ok_or_else(|| {
+                let mut errors = ValidationErrors::new();
+                errors.add("cursor", ValidationError::new("invalid_cursor"));
+                errors.into()
+            })
+    }
+
+    fn link(&self) -> String {
+        format!("/api/cars?cursor={}", self.encode())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CarsPage {
+    pub cars: Vec<CarInfo>,
+    /// Links to the following and previous pages, missing at the ends of the listing.
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+pub async fn get_cars(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CarsQuery>,
+) -> Result<Json<CarsPage>> {
+    let page = get_account_cars_info(&user, &query, &state.pg_pool).await?;
+
+    Ok(Json(page))
+}
+
+/// The cars owned by or shared with the user, a page at a time.
+pub async fn get_account_cars_info(
+    user: &User,
+    query: &CarsQuery,
+    pg_pool: &PgPool,
+) -> Result<CarsPage> {
+    let cursor = match &query.cursor {
+        Some(cursor) => Some(PageCursor::decode(cursor)?),
+        None => None,
+    };
+    let query = match &cursor {
+        Some(cursor) => &cursor.query,
+        None => query,
+    };
+    query.validate()?;
+
+    let backward = matches!(&cursor, Some(cursor) if cursor.backward);
+    let limit = query.limit.unwrap_or(DEFAULT_CARS_LIMIT);
+    let column = match query.sort {
+        CarSort::CreatedAt => "car.created_at",
+        CarSort::Model => "car.model",
+        CarSort::Plate => "car.plate",
+    };
+    // the page before a cursor is read in the reverse order then put back in order
+    let descending = (query.order == SortOrder::Desc) != backward;
+
+    let mut builder = QueryBuilder::<Postgres>::new(
         r#"
-    SELECT * 
-    FROM bank_details
-    WHERE user_id=$1
-"#,
-    )
-    .bind(user.id)
-    .fetch_one(pg_pool)
-    .await?;
+        SELECT car.*,
+            CASE WHEN car.user_id="#,
+    );
+    builder.push_bind(user.id);
+    builder.push(
+        r#" THEN 'owner'::car_role ELSE member.role END AS role,
+            odometer.mileage AS current_mileage,
+            (
+                SELECT to_jsonb(location)
+                FROM car_locations location
+                WHERE location.car_id=car.id
+                ORDER BY location.parked_at DESC, location.created_at DESC
+                LIMIT 1
+            ) AS location,
+            (
+                SELECT to_jsonb(specs)
+                FROM car_ev_specs specs
+                WHERE specs.car_id=car.id
+            ) AS ev_specs,
+            (
+                SELECT to_jsonb(photo)
+                FROM car_photos photo
+                WHERE photo.car_id=car.id AND photo.is_primary
+            ) AS primary_photo,
+            (
+                SELECT COALESCE(jsonb_agg(recall ORDER BY recall.published_on DESC NULLS LAST), '[]')
+                FROM recalls recall
+                WHERE recall_matches_car(recall, car)
+                    AND NOT EXISTS (
+                        SELECT 1
+                        FROM recall_completions completion
+                        WHERE completion.car_id=car.id AND completion.recall_id=recall.id
+                    )
+            ) AS open_recalls
+        FROM car
+        LEFT JOIN car_members member ON member.car_id=car.id AND member.user_id="#,
+    );
+    builder.push_bind(user.id);
+    builder.push(
+        r#"
+        LEFT JOIN LATERAL (
+            SELECT mileage
+            FROM odometer_readings
+            WHERE car_id=car.id
+            ORDER BY recorded_at DESC, created_at DESC
+            LIMIT 1
+        ) odometer ON true
+        WHERE (car.user_id="#,
+    );
+    builder.push_bind(user.id);
+    builder.push(" OR member.user_id=");
+    builder.push_bind(user.id);
+    builder.push(")");
+
+    if !query.model.is_empty() {
+        let models: Vec<String> = query
+            .model
+            .iter()
+            .map(|model| model.trim().to_lowercase())
+            .collect();
+        builder.push(" AND lower(car.model) = ANY(");
+        builder.push_bind(models);
+        builder.push(")");
+    }
+    if let Some(plate) = &query.plate {
+        let pattern = plate
+            .trim()
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        builder.push(" AND car.plate ILIKE '%' || ");
+        builder.push_bind(pattern);
+        builder.push(" || '%'");
+    }
+    if let Some(created_from) = query.created_from {
+        builder.push(" AND car.created_at >= ");
+        builder.push_bind(created_from);
+    }
+    if let Some(created_to) = query.created_to {
+        builder.push(" AND car.created_at < ");
+        builder.push_bind(created_to);
+    }
+    if let Some(cursor) = &cursor {
+        builder.push(format!(
+            " AND ({}, car.id) {} (",
+            column,
+            if descending { "<" } else { ">" }
+        ));
+        builder.push_bind(cursor.value.clone());
+        if query.sort == CarSort::CreatedAt {
+            builder.push("::timestamp");
+        }
+        builder.push(", ");
+        builder.push_bind(cursor.id);
+        builder.push(")");
+    }
+
+    let direction = if descending { "DESC" } else { "ASC" };
+    builder.push(format!(
+        " ORDER BY {} {}, car.id {} LIMIT ",
+        column, direction, direction
+    ));
+    builder.push_bind(limit + 1);
+
+    let mut cars: Vec<CarInfo> = builder
+        .build_query_as::<CarInfoRow>()
+        .fetch_all(pg_pool)
+        .await?
+        .into_iter()
+        .map(CarInfo::from)
+        .collect();
+
+    let has_more = cars.len() as i64 > limit;
+    cars.truncate(limit as usize);
+    if backward {
+        cars.reverse();
+    }
+
+    let next = match cars.last() {
+        Some(car) if (has_more && !backward) || (cursor.is_some() && backward) => {
+            Some(PageCursor::at(query, car, false).link())
+        }
+        _ => None,
+    };
+    let prev = match cars.first() {
+        Some(car) if (has_more && backward) || (cursor.is_some() && !backward) => {
+            Some(PageCursor::at(query, car, true).link())
+        }
+        _ => None,
+    };
 
-    Ok(bank_details)
+    Ok(CarsPage { cars, next, prev })
 }
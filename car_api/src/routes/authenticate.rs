@@ -1,11 +1,27 @@
 use super::AppState;
+use crate::{
+    encrypt::{decrypt_data, spawn_crypto},
+    errors::Error,
+};
 
 use std::sync::Arc;
 
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use axum_login::{secrecy::SecretVec, AuthUser};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use axum_login::{axum_sessions::extractors::ReadableSession, secrecy::SecretVec, AuthUser};
 use serde::{Deserialize, Serialize};
 use sqlx::types::uuid::Uuid;
+use sqlx::PgPool;
+
+/// Minutes a password confirmation allows sensitive data to be shown.
+const REAUTHENTICATION_VALIDITY_MINUTES: i32 = 5;
+
+/// Wrong passwords allowed in `REAUTHENTICATION_VALIDITY_MINUTES` before the user has to wait.
+const MAX_FAILED_REAUTHENTICATIONS: i64 = 5;
 
 type AuthContext = axum_login::extractors::AuthContext<User, axum_login::PostgresStore<User>>;
 
@@ -56,3 +72,125 @@ pub async fn login_handler(
 pub async fn logout_handler(mut auth: AuthContext) {
     auth.logout().await;
 }
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Reauthentication {
+    pub password: String,
+}
+
+/// Confirms the password of the logged in user before showing sensitive data, in the
+/// current session only.
+pub async fn reauthenticate(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    session: ReadableSession,
+    Json(reauthentication): Json<Reauthentication>,
+) -> Result<StatusCode, Error> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    // the attempts of a user are counted one at a time, parallel ones cannot pass the limit
+    sqlx::query(
+        r#"
+        SELECT id
+        FROM users
+        WHERE id=$1
+        FOR UPDATE
+    "#,
+    )
+    .bind(user.id)
+    .execute(&mut tx)
+    .await?;
+
+    let (failures,): (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*)
+        FROM reauthentications
+        WHERE user_id=$1 AND NOT succeeded
+            AND created_at > current_timestamp - make_interval(mins => $2)
+    "#,
+    )
+    .bind(user.id)
+    .bind(REAUTHENTICATION_VALIDITY_MINUTES)
+    .fetch_one(&mut tx)
+    .await?;
+    if failures >= MAX_FAILED_REAUTHENTICATIONS {
+        return Err(Error::TooManyRequests(
+            "too many failed attempts, try again later".into(),
+        ));
+    }
+
+    let password = spawn_crypto(move || decrypt_data(user.password_hash)).await?;
+    let succeeded = password.len() == reauthentication.password.len()
+        && openssl::memcmp::eq(password.as_bytes(), reauthentication.password.as_bytes());
+
+    sqlx::query(
+        r#"
+        INSERT INTO reauthentications(user_id, session_id, succeeded)
+        VALUES ($1, $2, $3)
+    "#,
+    )
+    .bind(user.id)
+    .bind(session.id())
+    .bind(succeeded)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    if !succeeded {
+        return Err(Error::Forbidden("invalid password".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Fails unless the user confirmed their password in the session in the last minutes.
+pub async fn check_recent_reauthentication(
+    pg_pool: &PgPool,
+    user: &User,
+    session: &ReadableSession,
+) -> Result<(), Error> {
+    let (reauthenticated,): (bool,) = sqlx::query_as(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM reauthentications
+            WHERE user_id=$1 AND session_id=$2 AND succeeded
+                AND created_at > current_timestamp - make_interval(mins => $3)
+        )
+    "#,
+    )
+    .bind(user.id)
+    .bind(session.id())
+    .bind(REAUTHENTICATION_VALIDITY_MINUTES)
+    .fetch_one(pg_pool)
+    .await?;
+
+    if !reauthenticated {
+        return Err(Error::Forbidden(
+            "the password must be confirmed again".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Authenticates the machine to machine endpoints, the bearer token must equal the value of the
+/// `env_var` variable and these endpoints are closed while it is not set.
+pub fn check_bearer_token(headers: &HeaderMap, env_var: &str) -> Result<(), Error> {
+    let expected = std::env::var(env_var).unwrap_or_default();
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+
+    if expected.is_empty()
+        || token.len() != expected.len()
+        || !openssl::memcmp::eq(token.as_bytes(), expected.as_bytes())
+    {
+        return Err(Error::Forbidden("invalid bearer token".into()));
+    }
+
+    Ok(())
+}
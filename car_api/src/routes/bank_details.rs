@@ -0,0 +1,462 @@
+use super::{
+    authenticate::{check_recent_reauthentication, User},
+    AppState,
+};
+use crate::{
+    encrypt::{
+        decrypt_data, decrypt_data_with, encrypt_data, encrypt_data_with, needs_reencryption,
+        spawn_crypto, Keyring,
+    },
+    errors::Error,
+    iban::{
+        find_iban_country, mask_iban, normalize_account_identifier, validate_bic, validate_iban,
+    },
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use axum_login::axum_sessions::extractors::ReadableSession;
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use validator::{Validate, ValidationError};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+/// Full IBANs a user can see in an hour.
+const MAX_IBAN_REVEALS_PER_HOUR: i64 = 5;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Validate)]
+#[validate(schema(function = "validate_bank_country"))]
+pub struct NewBankDetails {
+    #[validate(length(min = 1, max = 255))]
+    pub account_holder: String,
+    /// Alpha-2 code or english name of the country, it must be the country of the IBAN.
+    pub bank_country: String,
+    #[validate(custom = "validate_iban")]
+    pub iban: String,
+    #[validate(custom = "validate_bic")]
+    pub bic: Option<String>,
+}
+
+impl NewBankDetails {
+    /// Stores the IBAN and BIC without spaces in upper case and the country as its alpha-2 code.
+    pub fn normalize(&mut self) {
+        self.iban = normalize_account_identifier(&self.iban);
+        self.bic = self.bic.as_deref().map(normalize_account_identifier);
+        if let Some(country) = find_iban_country(&self.bank_country) {
+            self.bank_country = country.code.into();
+        }
+    }
+}
+
+fn validate_bank_country(bank_details: &NewBankDetails) -> Result<(), ValidationError> {
+    let iban_country = normalize_account_identifier(&bank_details.iban);
+
+    match find_iban_country(&bank_details.bank_country) {
+        Some(country) if iban_country.starts_with(country.code) => Ok(()),
+        Some(_) => Err(ValidationError::new("bank_country_mismatch")),
+        None => Err(ValidationError::new("unsupported_bank_country")),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct BankDetailsInfo {
+    pub id: Uuid,
+    pub country: String,
+    /// Masked as `FR76 **** **** 1234`, the full IBAN is only returned by `reveal_iban`.
+    pub iban: String,
+    pub bic: Option<String>,
+    pub account_holder: String,
+    /// The account used when no other one is given, a user has a single primary account.
+    pub is_primary: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Inserts bank details with an encrypted IBAN, the first account of the user becomes primary.
+pub async fn insert_bank_details_in_table<'c, E>(
+    executor: E,
+    user_id: &Uuid,
+    account: &NewBankDetails,
+) -> Result<BankDetailsInfo>
+where
+    E: PgExecutor<'c>,
+{
+    let bank_details = sqlx::query_as::<_, BankDetailsInfo>(
+        r#"
+        INSERT INTO bank_details(user_id, country, iban, bic, account_holder, is_primary)
+        VALUES ($1, $2, $3, $4, $5, NOT EXISTS (SELECT 1 FROM bank_details WHERE user_id=$1 AND is_primary AND deleted_at IS NULL))
+        RETURNING *
+    "#,
+    )
+    .bind(user_id)
+    .bind(&account.bank_country)
+    .bind(&account.iban)
+    .bind(&account.bic)
+    .bind(&account.account_holder)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(bank_details)
+}
+
+/// The bank accounts of the user with their IBAN masked, the primary one first.
+pub async fn get_account_bank_details<'c, E>(
+    user: &User,
+    executor: E,
+) -> Result<Vec<BankDetailsInfo>>
+where
+    E: PgExecutor<'c>,
+{
+    let bank_details = sqlx::query_as::<_, BankDetailsInfo>(
+        r#"
+        SELECT *
+        FROM bank_details
+        WHERE user_id=$1 AND deleted_at IS NULL
+        ORDER BY is_primary DESC, created_at
+    "#,
+    )
+    .bind(user.id)
+    .fetch_all(executor)
+    .await?;
+
+    spawn_crypto(move || bank_details.into_iter().map(mask_bank_details).collect()).await
+}
+
+fn mask_bank_details(mut bank_details: BankDetailsInfo) -> Result<BankDetailsInfo> {
+    bank_details.iban = mask_iban(&decrypt_data(bank_details.iban)?);
+    Ok(bank_details)
+}
+
+pub async fn get_bank_details(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<BankDetailsInfo>>> {
+    let bank_details = get_account_bank_details(&user, &state.pg_pool).await?;
+
+    Ok(Json(bank_details))
+}
+
+pub async fn add_bank_details(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Json(mut new_bank_details): Json<NewBankDetails>,
+) -> Result<(StatusCode, Json<BankDetailsInfo>)> {
+    new_bank_details.normalize();
+    new_bank_details.validate()?;
+    let iban = new_bank_details.iban.clone();
+    new_bank_details.iban = spawn_crypto(move || encrypt_data(iban)).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_user_bank_details(&mut tx, user.id).await?;
+
+    let bank_details = insert_bank_details_in_table(&mut tx, &user.id, &new_bank_details).await?;
+
+    tx.commit().await?;
+
+    let bank_details = spawn_crypto(move || mask_bank_details(bank_details)).await?;
+
+    Ok((StatusCode::CREATED, Json(bank_details)))
+}
+
+/// Makes the account the one used when no other one is given.
+pub async fn set_primary_bank_details(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(bank_details_id): Path<Uuid>,
+) -> Result<Json<BankDetailsInfo>> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_user_bank_details(&mut tx, user.id).await?;
+
+    let bank_details = get_user_bank_details(&mut tx, &user, &bank_details_id).await?;
+
+    // a single account of the user can be primary, the previous one is unset first
+    sqlx::query(
+        r#"
+        UPDATE bank_details
+        SET is_primary=false
+        WHERE user_id=$1 AND is_primary AND id<>$2
+    "#,
+    )
+    .bind(user.id)
+    .bind(bank_details.id)
+    .execute(&mut tx)
+    .await?;
+
+    let bank_details = sqlx::query_as::<_, BankDetailsInfo>(
+        r#"
+        UPDATE bank_details
+        SET is_primary=true
+        WHERE id=$1
+        RETURNING *
+    "#,
+    )
+    .bind(bank_details.id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let bank_details = spawn_crypto(move || mask_bank_details(bank_details)).await?;
+
+    Ok(Json(bank_details))
+}
+
+/// Removes the account, it is only marked as deleted so the records of its mandates and
+/// payments are kept. The latest account left becomes primary if needed. An account
+/// with active mandates is kept, even the last one, until its mandates are revoked, and
+/// an account with pending payouts is kept until they are paid.
+pub async fn delete_bank_details(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(bank_details_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_user_bank_details(&mut tx, user.id).await?;
+
+    let bank_details = get_user_bank_details(&mut tx, &user, &bank_details_id).await?;
+
+    let (active_mandates,): (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*)
+        FROM sepa_mandates
+        WHERE bank_details_id=$1 AND revoked_at IS NULL
+    "#,
+    )
+    .bind(bank_details.id)
+    .fetch_one(&mut tx)
+    .await?;
+    if active_mandates > 0 {
+        return Err(Error::Conflict(
+            "the bank account has active mandates".into(),
+        ));
+    }
+
+    let (pending_payouts,): (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*)
+        FROM sepa_payouts
+        WHERE bank_details_id=$1 AND batch_id IS NULL
+    "#,
+    )
+    .bind(bank_details.id)
+    .fetch_one(&mut tx)
+    .await?;
+    if pending_payouts > 0 {
+        return Err(Error::Conflict(
+            "the bank account has pending payouts".into(),
+        ));
+    }
+
+    // the account is kept for the records of its mandates and payments
+    sqlx::query(
+        r#"
+        UPDATE bank_details
+        SET deleted_at=current_timestamp, is_primary=false
+        WHERE id=$1
+    "#,
+    )
+    .bind(bank_details.id)
+    .execute(&mut tx)
+    .await?;
+
+    if bank_details.is_primary {
+        sqlx::query(
+            r#"
+            UPDATE bank_details
+            SET is_primary=true
+            WHERE id=(
+                SELECT id
+                FROM bank_details
+                WHERE user_id=$1 AND deleted_at IS NULL
+                ORDER BY created_at DESC
+                LIMIT 1
+            )
+        "#,
+        )
+        .bind(user.id)
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RevealedIban {
+    pub bank_details_id: Uuid,
+    pub iban: String,
+}
+
+/// Returns the full IBAN of the account. The user must have confirmed their password
+/// with `/api/account/reauthenticate` recently and the reveals are rate limited.
+pub async fn reveal_iban(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(bank_details_id): Path<Uuid>,
+    session: ReadableSession,
+) -> Result<Json<RevealedIban>> {
+    check_recent_reauthentication(&state.pg_pool, &user, &session).await?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_user_bank_details(&mut tx, user.id).await?;
+
+    let bank_details = get_user_bank_details(&mut tx, &user, &bank_details_id).await?;
+
+    let (reveals,): (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*)
+        FROM iban_reveals
+        WHERE user_id=$1 AND created_at > current_timestamp - interval '1 hour'
+    "#,
+    )
+    .bind(user.id)
+    .fetch_one(&mut tx)
+    .await?;
+    if reveals >= MAX_IBAN_REVEALS_PER_HOUR {
+        return Err(Error::TooManyRequests(
+            "too many IBAN reveals, try again later".into(),
+        ));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO iban_reveals(user_id, bank_details_id)
+        VALUES ($1, $2)
+    "#,
+    )
+    .bind(user.id)
+    .bind(bank_details.id)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(RevealedIban {
+        bank_details_id: bank_details.id,
+        iban: spawn_crypto(move || decrypt_data(bank_details.iban)).await?,
+    }))
+}
+
+/// Locks the user row so the changes of the primary account and the reveals are serialized.
+pub async fn lock_user_bank_details(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        SELECT id
+        FROM users
+        WHERE id=$1
+        FOR UPDATE
+    "#,
+    )
+    .bind(user_id)
+    .execute(tx)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_user_bank_details<'c, E>(
+    executor: E,
+    user: &User,
+    bank_details_id: &Uuid,
+) -> Result<BankDetailsInfo>
+where
+    E: PgExecutor<'c>,
+{
+    sqlx::query_as::<_, BankDetailsInfo>(
+        r#"
+        SELECT *
+        FROM bank_details
+        WHERE id=$1 AND user_id=$2 AND deleted_at IS NULL
+    "#,
+    )
+    .bind(bank_details_id)
+    .bind(user.id)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| Error::NotFound("bank details not found".into()))
+}
+
+/// Encrypts the IBANs with the current encryption key of the keyring, `batch_size` accounts
+/// per transaction so the accounts are not all locked during the migration. The IBANs
+/// already encrypted with the key in the current format are left untouched, returns the
+/// number of IBANs migrated. Only the IBANs are migrated, the passwords, insurance policy
+/// numbers and documents stay encrypted with their key, the previous keys must be kept.
+pub async fn reencrypt_bank_details(
+    pg_pool: &PgPool,
+    keyring: &Keyring,
+    batch_size: i64,
+) -> Result<u64> {
+    let mut last_id = Uuid::nil();
+    let mut reencrypted = 0;
+
+    loop {
+        let mut tx = pg_pool.begin().await?;
+
+        let ibans: Vec<(Uuid, String)> = sqlx::query_as(
+            r#"
+            SELECT id, iban
+            FROM bank_details
+            WHERE id > $1
+            ORDER BY id
+            LIMIT $2
+            FOR UPDATE
+        "#,
+        )
+        .bind(last_id)
+        .bind(batch_size)
+        .fetch_all(&mut tx)
+        .await?;
+        let Some((id, _)) = ibans.last() else {
+            break;
+        };
+        last_id = *id;
+
+        let keyring = keyring.clone();
+        let ibans = spawn_crypto(move || {
+            ibans
+                .into_iter()
+                .filter(|(_, iban)| needs_reencryption(&keyring, iban))
+                .map(|(id, iban)| {
+                    let iban = encrypt_data_with(&keyring, &decrypt_data_with(&keyring, &iban)?)?;
+                    Ok((id, iban))
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .await?;
+
+        for (id, iban) in ibans {
+            sqlx::query(
+                r#"
+                UPDATE bank_details
+                SET iban=$2
+                WHERE id=$1
+            "#,
+            )
+            .bind(id)
+            .bind(iban)
+            .execute(&mut tx)
+            .await?;
+            reencrypted += 1;
+        }
+
+        tx.commit().await?;
+    }
+
+    Ok(reencrypted)
+}
@@ -0,0 +1,180 @@
+use super::authenticate::User;
+use crate::errors::Error;
+
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::{PgExecutor, PgPool};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+/// How a user has access to a car, the owner is the `car.user_id`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "car_role", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CarRole {
+    Owner,
+    CoOwner,
+    Driver,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    View,
+    LogTrips,
+    Edit,
+    Delete,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::View => "view",
+            Permission::LogTrips => "log_trips",
+            Permission::Edit => "edit",
+            Permission::Delete => "delete",
+        }
+    }
+}
+
+/// Permissions of a car member, viewing the car is implied by the membership.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::FromRow)]
+pub struct Permissions {
+    pub can_log_trips: bool,
+    pub can_edit: bool,
+    pub can_delete: bool,
+}
+
+impl Permissions {
+    pub fn all() -> Self {
+        Self {
+            can_log_trips: true,
+            can_edit: true,
+            can_delete: true,
+        }
+    }
+
+    /// Permissions given to a role when the invitation does not list them.
+    pub fn for_role(role: CarRole) -> Self {
+        match role {
+            CarRole::Owner | CarRole::CoOwner => Self::all(),
+            CarRole::Driver => Self {
+                can_log_trips: true,
+                can_edit: false,
+                can_delete: false,
+            },
+        }
+    }
+
+    pub fn from_list(permissions: &[Permission]) -> Self {
+        Self {
+            can_log_trips: permissions.contains(&Permission::LogTrips),
+            can_edit: permissions.contains(&Permission::Edit),
+            can_delete: permissions.contains(&Permission::Delete),
+        }
+    }
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::View => true,
+            Permission::LogTrips => self.can_log_trips,
+            Permission::Edit => self.can_edit,
+            Permission::Delete => self.can_delete,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CarAccess {
+    pub role: CarRole,
+    /// Snapshot kept by the previous owner after a transfer, it can only be viewed.
+    pub archived: bool,
+    #[sqlx(flatten)]
+    pub permissions: Permissions,
+}
+
+/// Returns an error if the car does not exist or the user is not allowed `permission` on it.
+pub async fn check_car_permission(
+    pg_pool: &PgPool,
+    user: &User,
+    car_id: &Uuid,
+    permission: Permission,
+) -> Result<CarAccess> {
+    find_car_access(pg_pool, user, car_id, permission, false).await
+}
+
+/// Same as `check_car_permission` but locks the car row until the end of the transaction,
+/// used to serialize writes that depend on the car history.
+pub async fn lock_car<'c, E>(
+    executor: E,
+    user: &User,
+    car_id: &Uuid,
+    permission: Permission,
+) -> Result<CarAccess>
+where
+    E: PgExecutor<'c>,
+{
+    find_car_access(executor, user, car_id, permission, true).await
+}
+
+/// Returns an error if the car does not exist, does not belong to the user or is archived.
+pub async fn check_car_owner(pg_pool: &PgPool, user: &User, car_id: &Uuid) -> Result<()> {
+    let access = check_car_permission(pg_pool, user, car_id, Permission::Edit).await?;
+
+    if access.role != CarRole::Owner {
+        return Err(Error::Forbidden(
+            "only the owner of the car can do this".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn find_car_access<'c, E>(
+    executor: E,
+    user: &User,
+    car_id: &Uuid,
+    permission: Permission,
+    lock: bool,
+) -> Result<CarAccess>
+where
+    E: PgExecutor<'c>,
+{
+    let query = format!(
+        r#"
+        SELECT
+            CASE WHEN car.user_id=$2 THEN 'owner'::car_role ELSE member.role END AS role,
+            car.archived_at IS NOT NULL AS archived,
+            car.user_id=$2 OR member.can_log_trips AS can_log_trips,
+            car.user_id=$2 OR member.can_edit AS can_edit,
+            car.user_id=$2 OR member.can_delete AS can_delete
+        FROM car
+        LEFT JOIN car_members member ON member.car_id=car.id AND member.user_id=$2
+        WHERE car.id=$1 AND (car.user_id=$2 OR member.user_id IS NOT NULL)
+        {}
+    "#,
+        if lock { "FOR UPDATE OF car" } else { "" }
+    );
+
+    let access = sqlx::query_as::<_, CarAccess>(&query)
+        .bind(car_id)
+        .bind(user.id)
+        .fetch_optional(executor)
+        .await?
+        .ok_or_else(|| Error::NotFound("car not found".into()))?;
+
+    if access.archived && permission != Permission::View {
+        return Err(Error::Forbidden(
+            "this car is a read-only snapshot of a transferred car".into(),
+        ));
+    }
+
+    if !access.permissions.allows(permission) {
+        return Err(Error::Forbidden(format!(
+            "missing the {} permission on this car",
+            permission.as_str()
+        )));
+    }
+
+    Ok(access)
+}
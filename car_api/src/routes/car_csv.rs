@@ -0,0 +1,267 @@
+use super::{
+    account::{insert_car_in_table, NewCar},
+    authenticate::User,
+    car::CarRole,
+    recall::validate_vin,
+    AppState,
+};
+use crate::errors::Error;
+
+use axum::{
+    body::{Bytes, StreamBody},
+    extract::State,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::extract::Query;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+/// Maximum size of an imported CSV file.
+pub const MAX_CAR_IMPORT_SIZE: usize = 5 * 1024 * 1024;
+
+const EXPORT_HEADER: [&str; 9] = [
+    "id",
+    "plate",
+    "model",
+    "manufacturer",
+    "vin",
+    "model_year",
+    "role",
+    "archived_at",
+    "created_at",
+];
+
+/// A line of an imported file, the columns are named by the header line.
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct CarRow {
+    #[validate(length(min = 1, max = 255))]
+    pub plate: String,
+    #[validate(length(min = 1, max = 255))]
+    pub model: String,
+    #[validate(length(min = 1, max = 255))]
+    pub manufacturer: Option<String>,
+    #[validate(custom = "validate_vin")]
+    pub vin: Option<String>,
+    #[validate(range(min = 1886, max = 2100))]
+    pub model_year: Option<i32>,
+}
+
+impl From<CarRow> for NewCar {
+    fn from(row: CarRow) -> Self {
+        Self {
+            car_model: row.model,
+            car_plate: row.plate,
+            car_manufacturer: row.manufacturer,
+            car_vin: row.vin,
+            car_year: row.model_year,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CarImportQuery {
+    /// Only validates the file.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CarImportRowErrors {
+    /// Line of the row in the file, the header is line 1.
+    pub line: u64,
+    pub errors: ValidationErrors,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CarImport {
+    pub dry_run: bool,
+    pub valid: usize,
+    pub imported: usize,
+    /// The invalid rows, they are never imported.
+    pub rows: Vec<CarImportRowErrors>,
+}
+
+/// Creates the cars of a `text/csv` body, the valid rows are imported in a single transaction
+/// and the errors of the invalid ones are reported.
+pub async fn import_cars(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CarImportQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<CarImport>> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_lowercase())
+        .unwrap_or_default();
+    if content_type != "text/csv" {
+        return Err(Error::UnsupportedMediaType(
+            "content type must be text/csv".into(),
+        ));
+    }
+
+    let (cars, rows) = parse_car_rows(&body)?;
+
+    let mut import = CarImport {
+        dry_run: query.dry_run,
+        valid: cars.len(),
+        imported: 0,
+        rows,
+    };
+    if query.dry_run || cars.is_empty() {
+        return Ok(Json(import));
+    }
+
+    let mut tx = state.pg_pool.begin().await?;
+    for car in cars {
+        insert_car_in_table(&mut tx, &user.id, &car.into()).await?;
+        import.imported += 1;
+    }
+    tx.commit().await?;
+
+    Ok(Json(import))
+}
+
+/// Returns the valid rows and the errors of the others.
+fn parse_car_rows(content: &[u8]) -> Result<(Vec<CarRow>, Vec<CarImportRowErrors>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content);
+
+    let headers = reader.headers().cloned().map_err(|_| {
+        let mut errors = ValidationErrors::new();
+        errors.add("body", ValidationError::new("invalid_csv"));
+        Error::from(errors)
+    })?;
+
+    let mut cars = Vec::new();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let (line, row) = match record {
+            Ok(record) => (
+                record.position().map(|position| position.line()),
+                record
+                    .deserialize::<CarRow>(Some(&headers))
+                    .map_err(|err| invalid_row(err.to_string())),
+            ),
+            Err(err) => (
+                err.position().map(|position| position.line()),
+                Err(invalid_row(err.to_string())),
+            ),
+        };
+
+        match row.and_then(|row| row.validate().map(|_| row)) {
+            Ok(row) => cars.push(row),
+            Err(errors) => rows.push(CarImportRowErrors {
+                line: line.unwrap_or_default(),
+                errors,
+            }),
+        }
+    }
+
+    Ok((cars, rows))
+}
+
+fn invalid_row(message: String) -> ValidationErrors {
+    let mut error = ValidationError::new("invalid_row");
+    error.message = Some(message.into());
+
+    let mut errors = ValidationErrors::new();
+    errors.add("row", error);
+    errors
+}
+
+#[derive(Serialize, Debug, Clone, sqlx::FromRow)]
+struct CarExportRow {
+    id: Uuid,
+    plate: String,
+    model: String,
+    manufacturer: Option<String>,
+    vin: Option<String>,
+    model_year: Option<i32>,
+    role: CarRole,
+    archived_at: Option<chrono::NaiveDateTime>,
+    created_at: chrono::NaiveDateTime,
+}
+
+/// Streams the cars owned by or shared with the user as CSV, the columns of the import are included.
+pub async fn export_cars(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response> {
+    let (sender, receiver) = tokio::sync::mpsc::channel::<Result<Bytes>>(16);
+    let pg_pool = state.pg_pool.clone();
+
+    // the rows are written while they are read so the whole listing is never held in memory
+    tokio::spawn(async move {
+        let header = csv_line(|writer| writer.write_record(EXPORT_HEADER));
+        if sender.send(header).await.is_err() {
+            return;
+        }
+
+        let mut cars = sqlx::query_as::<_, CarExportRow>(
+            r#"
+            SELECT car.id, car.plate, car.model, car.manufacturer, car.vin, car.model_year,
+                CASE WHEN car.user_id=$1 THEN 'owner'::car_role ELSE member.role END AS role,
+                car.archived_at, car.created_at
+            FROM car
+            LEFT JOIN car_members member ON member.car_id=car.id AND member.user_id=$1
+            WHERE car.user_id=$1 OR member.user_id=$1
+            ORDER BY car.created_at, car.id
+        "#,
+        )
+        .bind(user.id)
+        .fetch(&pg_pool);
+
+        while let Some(car) = cars.next().await {
+            let line = match car {
+                Ok(car) => csv_line(|writer| writer.serialize(&car)),
+                Err(err) => Err(err.into()),
+            };
+            let failed = line.is_err();
+            if sender.send(line).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|line| (line, receiver))
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"cars.csv\"",
+            ),
+        ],
+        StreamBody::new(body),
+    )
+        .into_response())
+}
+
+fn csv_line<F>(write: F) -> Result<Bytes>
+where
+    F: FnOnce(&mut csv::Writer<Vec<u8>>) -> csv::Result<()>,
+{
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    write(&mut writer).map_err(std::io::Error::from)?;
+
+    let line = writer.into_inner().map_err(|err| err.into_error())?;
+
+    Ok(Bytes::from(line))
+}
@@ -0,0 +1,264 @@
+use super::{
+    authenticate::User,
+    car::{check_car_permission, Permission},
+    AppState,
+};
+use crate::errors::Error;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::PgPool;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "charge_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ChargeKind {
+    Fine,
+    Toll,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewCharge {
+    pub kind: ChargeKind,
+    pub occurred_at: chrono::NaiveDateTime,
+    #[validate(length(min = 1, max = 255))]
+    pub location: Option<String>,
+    #[validate(range(min = 0))]
+    pub amount_cents: i64,
+    /// ISO 4217 code.
+    #[validate(custom = "validate_currency")]
+    pub currency: String,
+    #[validate(length(min = 1, max = 255))]
+    pub reference: Option<String>,
+    pub due_on: Option<NaiveDate>,
+    /// The owner or a member of the car who has to pay.
+    pub driver_id: Option<Uuid>,
+}
+
+fn validate_currency(currency: &str) -> Result<(), ValidationError> {
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(ValidationError::new("invalid_currency"));
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChargeDriver {
+    /// Unassigns the charge when missing.
+    pub driver_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ChargesQuery {
+    /// Only lists the unpaid charges.
+    #[serde(default)]
+    pub outstanding: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct Charge {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub kind: ChargeKind,
+    pub occurred_at: chrono::NaiveDateTime,
+    pub location: Option<String>,
+    pub amount_cents: i64,
+    pub currency: String,
+    pub reference: Option<String>,
+    pub due_on: Option<NaiveDate>,
+    pub paid_at: Option<chrono::NaiveDateTime>,
+    pub driver_id: Option<Uuid>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+pub async fn create_charge(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(new_charge): Json<NewCharge>,
+) -> Result<(StatusCode, Json<Charge>)> {
+    new_charge.validate()?;
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Edit).await?;
+    if let Some(driver_id) = new_charge.driver_id {
+        check_car_driver(&state.pg_pool, &car_id, &driver_id).await?;
+    }
+
+    let charge = sqlx::query_as::<_, Charge>(
+        r#"
+        INSERT INTO charges(car_id, kind, occurred_at, location, amount_cents, currency, reference, due_on, driver_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(new_charge.kind)
+    .bind(new_charge.occurred_at)
+    .bind(new_charge.location.as_deref().map(str::trim))
+    .bind(new_charge.amount_cents)
+    .bind(new_charge.currency.to_uppercase())
+    .bind(new_charge.reference.as_deref().map(str::trim))
+    .bind(new_charge.due_on)
+    .bind(new_charge.driver_id)
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(charge)))
+}
+
+/// Returns a validation error if the user is neither the owner nor a member of the car.
+async fn check_car_driver(pg_pool: &PgPool, car_id: &Uuid, driver_id: &Uuid) -> Result<()> {
+    let driver = sqlx::query_as::<_, (Uuid,)>(
+        r#"
+        SELECT id
+        FROM car
+        WHERE id=$1 AND user_id=$2
+        UNION ALL
+        SELECT car_id
+        FROM car_members
+        WHERE car_id=$1 AND user_id=$2
+    "#,
+    )
+    .bind(car_id)
+    .bind(driver_id)
+    .fetch_optional(pg_pool)
+    .await?;
+
+    if driver.is_none() {
+        let mut errors = ValidationErrors::new();
+        errors.add("driver_id", ValidationError::new("not_a_car_member"));
+        return Err(errors.into());
+    }
+
+    Ok(())
+}
+
+pub async fn get_charges(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Query(query): Query<ChargesQuery>,
+) -> Result<Json<Vec<Charge>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let charges = sqlx::query_as::<_, Charge>(
+        r#"
+        SELECT *
+        FROM charges
+        WHERE car_id=$1 AND (NOT $2 OR paid_at IS NULL)
+        ORDER BY occurred_at DESC
+    "#,
+    )
+    .bind(car_id)
+    .bind(query.outstanding)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(charges))
+}
+
+pub async fn pay_charge(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, charge_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Charge>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Edit).await?;
+
+    let charge = get_car_charge(&state.pg_pool, &car_id, &charge_id).await?;
+
+    let charge = sqlx::query_as::<_, Charge>(
+        r#"
+        UPDATE charges
+        SET paid_at=current_timestamp
+        WHERE id=$1 AND paid_at IS NULL
+        RETURNING *
+    "#,
+    )
+    .bind(charge.id)
+    .fetch_optional(&state.pg_pool)
+    .await?
+    .ok_or_else(|| Error::Conflict("the charge is already paid".into()))?;
+
+    Ok(Json(charge))
+}
+
+/// Makes another member of the car responsible for the charge.
+pub async fn reassign_charge(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, charge_id)): Path<(Uuid, Uuid)>,
+    Json(driver): Json<ChargeDriver>,
+) -> Result<Json<Charge>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Edit).await?;
+    if let Some(driver_id) = driver.driver_id {
+        check_car_driver(&state.pg_pool, &car_id, &driver_id).await?;
+    }
+
+    let charge = get_car_charge(&state.pg_pool, &car_id, &charge_id).await?;
+
+    let charge = sqlx::query_as::<_, Charge>(
+        r#"
+        UPDATE charges
+        SET driver_id=$2
+        WHERE id=$1
+        RETURNING *
+    "#,
+    )
+    .bind(charge.id)
+    .bind(driver.driver_id)
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    Ok(Json(charge))
+}
+
+/// Unpaid charges of the cars of the user and the ones the user is responsible for, by due date.
+pub async fn get_outstanding_charges(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Charge>>> {
+    let charges = sqlx::query_as::<_, Charge>(
+        r#"
+        SELECT charge.*
+        FROM charges charge
+        JOIN car ON car.id = charge.car_id
+        LEFT JOIN car_members member ON member.car_id=car.id AND member.user_id=$1
+        WHERE charge.paid_at IS NULL
+            AND car.archived_at IS NULL
+            AND (car.user_id=$1 OR member.user_id=$1 OR charge.driver_id=$1)
+        ORDER BY charge.due_on NULLS LAST, charge.occurred_at
+    "#,
+    )
+    .bind(user.id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(charges))
+}
+
+async fn get_car_charge(pg_pool: &PgPool, car_id: &Uuid, charge_id: &Uuid) -> Result<Charge> {
+    sqlx::query_as::<_, Charge>(
+        r#"
+        SELECT *
+        FROM charges
+        WHERE id=$1 AND car_id=$2
+    "#,
+    )
+    .bind(charge_id)
+    .bind(car_id)
+    .fetch_optional(pg_pool)
+    .await?
+    .ok_or_else(|| Error::NotFound("charge not found".into()))
+}
@@ -0,0 +1,230 @@
+use super::{
+    authenticate::User,
+    car::{check_car_permission, Permission},
+    AppState,
+};
+use crate::{
+    encrypt::{decrypt_bytes, encrypt_bytes, spawn_crypto},
+    errors::Error,
+    storage::delete_keys,
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+/// Maximum size of an uploaded document, before encryption.
+pub const MAX_DOCUMENT_SIZE: usize = 10 * 1024 * 1024;
+
+const ALLOWED_CONTENT_TYPES: [&str; 3] = ["application/pdf", "image/jpeg", "image/png"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "document_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentKind {
+    RegistrationCertificate,
+    InsuranceCard,
+    Other,
+}
+
+/// The document content is sent as the request body, with its `Content-Type`.
+#[derive(Deserialize, Debug, Clone, Validate)]
+pub struct NewDocumentQuery {
+    pub kind: DocumentKind,
+    #[validate(length(min = 1, max = 255))]
+    pub file_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct Document {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub kind: DocumentKind,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    #[serde(skip)]
+    pub storage_key: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+pub async fn upload_document(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Query(query): Query<NewDocumentQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<Document>)> {
+    query.validate()?;
+    let content_type = get_content_type(&headers)?;
+    if body.is_empty() {
+        let mut errors = ValidationErrors::new();
+        errors.add("body", ValidationError::new("empty"));
+        return Err(errors.into());
+    }
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Edit).await?;
+
+    let id = Uuid::new_v4();
+    let storage_key = format!("documents/{}/{}", car_id, id);
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    let document = sqlx::query_as::<_, Document>(
+        r#"
+        INSERT INTO documents(id, car_id, kind, file_name, content_type, size_bytes, storage_key)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+    "#,
+    )
+    .bind(id)
+    .bind(car_id)
+    .bind(query.kind)
+    .bind(query.file_name.trim())
+    .bind(&content_type)
+    .bind(body.len() as i64)
+    .bind(&storage_key)
+    .fetch_one(&mut tx)
+    .await?;
+
+    // the row is only committed once the content is stored, the content is removed if the
+    // row is not committed
+    let encrypted = spawn_crypto(move || encrypt_bytes(&body)).await?;
+    let result = async {
+        state.storage.put(&storage_key, encrypted).await?;
+        tx.commit().await?;
+
+        Ok::<_, Error>(())
+    }
+    .await;
+    if let Err(err) = result {
+        delete_keys(state.storage.as_ref(), &[storage_key]).await;
+        return Err(err);
+    }
+
+    Ok((StatusCode::CREATED, Json(document)))
+}
+
+/// Returns the media type of the request without its parameters if it is allowed.
+fn get_content_type(headers: &HeaderMap) -> Result<String> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_lowercase())
+        .unwrap_or_default();
+
+    if !ALLOWED_CONTENT_TYPES.contains(&content_type.as_str()) {
+        return Err(Error::UnsupportedMediaType(format!(
+            "content type must be one of {}",
+            ALLOWED_CONTENT_TYPES.join(", ")
+        )));
+    }
+
+    Ok(content_type)
+}
+
+pub async fn get_documents(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<Document>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let documents = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT *
+        FROM documents
+        WHERE car_id=$1
+        ORDER BY created_at DESC
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(documents))
+}
+
+pub async fn download_document(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, document_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let document = get_car_document(&state, &car_id, &document_id).await?;
+
+    let encrypted = state.storage.get(&document.storage_key).await?;
+    let content = spawn_crypto(move || decrypt_bytes(&encrypted)).await?;
+
+    let file_name: String = document
+        .file_name
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+        .collect();
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, document.content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        content,
+    )
+        .into_response())
+}
+
+pub async fn delete_document(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, document_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Delete).await?;
+
+    let document = get_car_document(&state, &car_id, &document_id).await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM documents
+        WHERE id=$1
+    "#,
+    )
+    .bind(document.id)
+    .execute(&state.pg_pool)
+    .await?;
+
+    // the file is deleted once the row is, a file left behind is never read
+    delete_keys(state.storage.as_ref(), &[document.storage_key]).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_car_document(state: &AppState, car_id: &Uuid, document_id: &Uuid) -> Result<Document> {
+    sqlx::query_as::<_, Document>(
+        r#"
+        SELECT *
+        FROM documents
+        WHERE id=$1 AND car_id=$2
+    "#,
+    )
+    .bind(document_id)
+    .bind(car_id)
+    .fetch_optional(&state.pg_pool)
+    .await?
+    .ok_or_else(|| Error::NotFound("document not found".into()))
+}
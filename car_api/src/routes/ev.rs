@@ -0,0 +1,382 @@
+use super::{
+    authenticate::User,
+    car::{check_car_permission, Permission},
+    AppState,
+};
+use crate::errors::Error;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgHasArrayType, PgTypeInfo};
+use sqlx::types::uuid::Uuid;
+use sqlx::PgPool;
+use validator::Validate;
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+/// Readings below this state of charge are too imprecise to estimate the battery capacity.
+const MIN_CAPACITY_SOC_PERCENT: f64 = 20.0;
+
+/// Readings of the last days before the latest one used to estimate the current capacity.
+const CAPACITY_WINDOW_DAYS: i32 = 30;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "connector_type", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectorType {
+    Type1,
+    Type2,
+    Ccs1,
+    Ccs2,
+    Chademo,
+    Nacs,
+    GbT,
+}
+
+impl PgHasArrayType for ConnectorType {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_connector_type")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewEvSpecs {
+    /// Usable capacity of the battery when new.
+    #[validate(range(min = 1.0, max = 500.0))]
+    pub battery_capacity_kwh: f64,
+    #[validate(length(min = 1))]
+    pub connector_types: Vec<ConnectorType>,
+    #[validate(range(min = 1.0, max = 50.0))]
+    pub max_ac_power_kw: Option<f64>,
+    #[validate(range(min = 1.0, max = 1000.0))]
+    pub max_dc_power_kw: Option<f64>,
+    #[validate(range(min = 1, max = 2000))]
+    pub wltp_range_km: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct EvSpecs {
+    pub car_id: Uuid,
+    pub battery_capacity_kwh: f64,
+    pub connector_types: Vec<ConnectorType>,
+    pub max_ac_power_kw: Option<f64>,
+    pub max_dc_power_kw: Option<f64>,
+    pub wltp_range_km: Option<i32>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Makes the car an electric car or updates its specifications.
+pub async fn set_ev_specs(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(mut new_specs): Json<NewEvSpecs>,
+) -> Result<Json<EvSpecs>> {
+    new_specs.validate()?;
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Edit).await?;
+
+    new_specs
+        .connector_types
+        .sort_by_key(|connector| *connector as u8);
+    new_specs.connector_types.dedup();
+
+    let specs = sqlx::query_as::<_, EvSpecs>(
+        r#"
+        INSERT INTO car_ev_specs(car_id, battery_capacity_kwh, connector_types, max_ac_power_kw, max_dc_power_kw, wltp_range_km)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (car_id) DO UPDATE
+        SET battery_capacity_kwh=EXCLUDED.battery_capacity_kwh, connector_types=EXCLUDED.connector_types,
+            max_ac_power_kw=EXCLUDED.max_ac_power_kw, max_dc_power_kw=EXCLUDED.max_dc_power_kw,
+            wltp_range_km=EXCLUDED.wltp_range_km
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(new_specs.battery_capacity_kwh)
+    .bind(&new_specs.connector_types)
+    .bind(new_specs.max_ac_power_kw)
+    .bind(new_specs.max_dc_power_kw)
+    .bind(new_specs.wltp_range_km)
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    Ok(Json(specs))
+}
+
+pub async fn get_ev_specs(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<EvSpecs>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let specs = get_car_ev_specs(&state.pg_pool, &car_id).await?;
+
+    Ok(Json(specs))
+}
+
+async fn get_car_ev_specs(pg_pool: &PgPool, car_id: &Uuid) -> Result<EvSpecs> {
+    sqlx::query_as::<_, EvSpecs>(
+        r#"
+        SELECT *
+        FROM car_ev_specs
+        WHERE car_id=$1
+    "#,
+    )
+    .bind(car_id)
+    .fetch_optional(pg_pool)
+    .await?
+    .ok_or_else(|| Error::NotFound("the car is not an electric car".into()))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewSocReading {
+    pub recorded_at: chrono::NaiveDateTime,
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub soc_percent: f64,
+    /// Energy left in the battery, when reported by the car.
+    #[validate(range(min = 0.0))]
+    pub energy_kwh: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewSocReadings {
+    #[validate(length(min = 1, max = 1000))]
+    #[validate]
+    pub readings: Vec<NewSocReading>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct SocReading {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub recorded_at: chrono::NaiveDateTime,
+    pub soc_percent: f64,
+    pub energy_kwh: Option<f64>,
+    /// WLTP range scaled by the state of charge and the state of health of the battery.
+    #[sqlx(default)]
+    pub estimated_range_km: Option<f64>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SocQuery {
+    pub from: Option<chrono::NaiveDateTime>,
+    pub to: Option<chrono::NaiveDateTime>,
+}
+
+/// Records a batch of readings, a reading sent again for the same time replaces the previous one.
+pub async fn create_soc_readings(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(new_readings): Json<NewSocReadings>,
+) -> Result<(StatusCode, Json<Vec<SocReading>>)> {
+    new_readings.validate()?;
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::LogTrips).await?;
+
+    let (recorded_at, (soc_percent, energy_kwh)): (Vec<_>, (Vec<_>, Vec<_>)) = new_readings
+        .readings
+        .iter()
+        .map(|reading| {
+            (
+                reading.recorded_at,
+                (reading.soc_percent, reading.energy_kwh),
+            )
+        })
+        .unzip();
+
+    let readings = sqlx::query_as::<_, SocReading>(
+        r#"
+        INSERT INTO soc_readings(car_id, recorded_at, soc_percent, energy_kwh)
+        SELECT $1, *
+        FROM UNNEST($2::timestamp[], $3::float8[], $4::float8[])
+        ON CONFLICT (car_id, recorded_at) DO UPDATE
+        SET soc_percent=EXCLUDED.soc_percent, energy_kwh=EXCLUDED.energy_kwh
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(recorded_at)
+    .bind(soc_percent)
+    .bind(energy_kwh)
+    .fetch_all(&state.pg_pool)
+    .await
+    .map_err(|err| match err {
+        // the same time twice in a batch
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("21000") => {
+            Error::Conflict("a reading is recorded twice".into())
+        }
+        err => err.into(),
+    })?;
+
+    Ok((StatusCode::CREATED, Json(readings)))
+}
+
+/// The state of charge history of the car, oldest first.
+pub async fn get_soc_readings(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Query(query): Query<SocQuery>,
+) -> Result<Json<Vec<SocReading>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let specs = get_car_ev_specs(&state.pg_pool, &car_id).await?;
+    let capacity = estimate_current_capacity(&state.pg_pool, &car_id).await?;
+
+    let mut readings = sqlx::query_as::<_, SocReading>(
+        r#"
+        SELECT *
+        FROM soc_readings
+        WHERE car_id=$1
+            AND ($2::timestamp IS NULL OR recorded_at >= $2)
+            AND ($3::timestamp IS NULL OR recorded_at < $3)
+        ORDER BY recorded_at
+    "#,
+    )
+    .bind(car_id)
+    .bind(query.from)
+    .bind(query.to)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    for reading in &mut readings {
+        reading.estimated_range_km = estimate_range(&specs, capacity, reading.soc_percent);
+    }
+
+    Ok(Json(readings))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct MonthlyBatteryCapacity {
+    /// First day of the month.
+    pub month: NaiveDate,
+    /// Average of the capacities estimated from the readings of the month.
+    pub estimated_capacity_kwh: f64,
+    pub readings: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatteryHealth {
+    pub nominal_capacity_kwh: f64,
+    /// Estimated from the energy reported with the state of charge, over the last readings.
+    pub estimated_capacity_kwh: Option<f64>,
+    pub state_of_health_percent: Option<f64>,
+    /// Capacity lost each year in percent of the nominal capacity, by linear regression
+    /// over all the readings. Negative when the estimates increase.
+    pub degradation_percent_per_year: Option<f64>,
+    pub latest_reading: Option<SocReading>,
+    pub monthly: Vec<MonthlyBatteryCapacity>,
+}
+
+pub async fn get_battery_health(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<BatteryHealth>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let specs = get_car_ev_specs(&state.pg_pool, &car_id).await?;
+    let capacity = estimate_current_capacity(&state.pg_pool, &car_id).await?;
+
+    let monthly = sqlx::query_as::<_, MonthlyBatteryCapacity>(
+        r#"
+        SELECT
+            date_trunc('month', recorded_at)::date AS month,
+            AVG(energy_kwh * 100 / soc_percent) AS estimated_capacity_kwh,
+            COUNT(*) AS readings
+        FROM soc_readings
+        WHERE car_id=$1 AND energy_kwh IS NOT NULL AND soc_percent >= $2
+        GROUP BY month
+        ORDER BY month
+    "#,
+    )
+    .bind(car_id)
+    .bind(MIN_CAPACITY_SOC_PERCENT)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    // slope of the capacity estimates in kWh per year
+    let (slope,) = sqlx::query_as::<_, (Option<f64>,)>(
+        r#"
+        SELECT regr_slope(energy_kwh * 100 / soc_percent, extract(epoch FROM recorded_at)::float8 / 31557600)
+        FROM soc_readings
+        WHERE car_id=$1 AND energy_kwh IS NOT NULL AND soc_percent >= $2
+    "#,
+    )
+    .bind(car_id)
+    .bind(MIN_CAPACITY_SOC_PERCENT)
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    let mut latest_reading = sqlx::query_as::<_, SocReading>(
+        r#"
+        SELECT *
+        FROM soc_readings
+        WHERE car_id=$1
+        ORDER BY recorded_at DESC
+        LIMIT 1
+    "#,
+    )
+    .bind(car_id)
+    .fetch_optional(&state.pg_pool)
+    .await?;
+    if let Some(reading) = &mut latest_reading {
+        reading.estimated_range_km = estimate_range(&specs, capacity, reading.soc_percent);
+    }
+
+    Ok(Json(BatteryHealth {
+        nominal_capacity_kwh: specs.battery_capacity_kwh,
+        estimated_capacity_kwh: capacity,
+        state_of_health_percent: capacity
+            .map(|capacity| capacity * 100.0 / specs.battery_capacity_kwh),
+        degradation_percent_per_year: slope
+            .map(|slope| -slope * 100.0 / specs.battery_capacity_kwh),
+        latest_reading,
+        monthly,
+    }))
+}
+
+/// Capacity of the battery estimated from the readings of the month before the latest one
+/// reporting the energy left: the energy divided by the state of charge.
+async fn estimate_current_capacity(pg_pool: &PgPool, car_id: &Uuid) -> Result<Option<f64>> {
+    let (capacity,) = sqlx::query_as::<_, (Option<f64>,)>(
+        r#"
+        WITH estimable AS (
+            SELECT recorded_at, energy_kwh * 100 / soc_percent AS capacity_kwh
+            FROM soc_readings
+            WHERE car_id=$1 AND energy_kwh IS NOT NULL AND soc_percent >= $2
+        )
+        SELECT AVG(capacity_kwh)
+        FROM estimable
+        WHERE recorded_at > (SELECT MAX(recorded_at) FROM estimable) - make_interval(days => $3)
+    "#,
+    )
+    .bind(car_id)
+    .bind(MIN_CAPACITY_SOC_PERCENT)
+    .bind(CAPACITY_WINDOW_DAYS)
+    .fetch_one(pg_pool)
+    .await?;
+
+    Ok(capacity)
+}
+
+/// The WLTP range for the state of charge, reduced by the capacity the battery lost.
+fn estimate_range(specs: &EvSpecs, capacity_kwh: Option<f64>, soc_percent: f64) -> Option<f64> {
+    let state_of_health = capacity_kwh
+        .map(|capacity| (capacity / specs.battery_capacity_kwh).min(1.0))
+        .unwrap_or(1.0);
+
+    specs
+        .wltp_range_km
+        .map(|range| range as f64 * soc_percent / 100.0 * state_of_health)
+}
@@ -0,0 +1,248 @@
+use super::{
+    authenticate::User,
+    car::{check_car_permission, Permission},
+    AppState,
+};
+use crate::errors::Error;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::PgPool;
+use validator::Validate;
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "energy_type", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum EnergyType {
+    /// Quantities in litres.
+    Fuel,
+    /// Quantities in kWh.
+    Electric,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewFuelEntry {
+    pub energy_type: EnergyType,
+    #[validate(range(min = 0.01))]
+    pub quantity: f64,
+    /// Total price paid for the entry.
+    #[validate(range(min = 0))]
+    pub price_cents: i64,
+    #[validate(range(min = 0))]
+    pub odometer: i64,
+    /// The tank was filled up, or the battery charged, to full.
+    #[serde(default = "default_full_tank")]
+    pub full_tank: bool,
+    /// Defaults to now.
+    pub filled_at: Option<chrono::NaiveDateTime>,
+}
+
+fn default_full_tank() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct FuelEntry {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub energy_type: EnergyType,
+    pub quantity: f64,
+    pub price_cents: i64,
+    pub odometer: i64,
+    pub full_tank: bool,
+    pub filled_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+pub async fn create_fuel_entry(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(new_entry): Json<NewFuelEntry>,
+) -> Result<(StatusCode, Json<FuelEntry>)> {
+    new_entry.validate()?;
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::LogTrips).await?;
+
+    let entry = sqlx::query_as::<_, FuelEntry>(
+        r#"
+        INSERT INTO fuel_entries(car_id, energy_type, quantity, price_cents, odometer, full_tank, filled_at)
+        VALUES ($1, $2, $3, $4, $5, $6, COALESCE($7, current_timestamp))
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(new_entry.energy_type)
+    .bind(new_entry.quantity)
+    .bind(new_entry.price_cents)
+    .bind(new_entry.odometer)
+    .bind(new_entry.full_tank)
+    .bind(new_entry.filled_at)
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+pub async fn get_fuel_entries(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<FuelEntry>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let entries = get_car_fuel_entries(&state.pg_pool, &car_id).await?;
+
+    Ok(Json(entries))
+}
+
+/// Entries of the car sorted by odometer.
+pub async fn get_car_fuel_entries(pg_pool: &PgPool, car_id: &Uuid) -> Result<Vec<FuelEntry>> {
+    let entries = sqlx::query_as::<_, FuelEntry>(
+        r#"
+        SELECT *
+        FROM fuel_entries
+        WHERE car_id=$1
+        ORDER BY odometer, filled_at
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(pg_pool)
+    .await?;
+
+    Ok(entries)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsumptionStats {
+    pub energy_type: EnergyType,
+    /// L/100km for fuel, kWh/100km for electric.
+    pub consumption_per_100km: Option<f64>,
+    pub cost_per_km_cents: Option<f64>,
+    /// Distance, quantity and cost of the full-to-full intervals the stats are computed on.
+    pub distance_km: i64,
+    pub quantity: f64,
+    pub cost_cents: i64,
+}
+
+pub async fn get_fuel_consumption(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<ConsumptionStats>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let entries = get_car_fuel_entries(&state.pg_pool, &car_id).await?;
+
+    let stats = [EnergyType::Fuel, EnergyType::Electric]
+        .into_iter()
+        .filter_map(|energy_type| {
+            let entries: Vec<&FuelEntry> = entries
+                .iter()
+                .filter(|entry| entry.energy_type == energy_type)
+                .collect();
+
+            (!entries.is_empty()).then(|| compute_consumption(energy_type, &entries))
+        })
+        .collect();
+
+    Ok(Json(stats))
+}
+
+/// Uses the full-to-full method: the quantity put in the tank between two full fills,
+/// partial fills included, is what was consumed over the distance between them.
+/// Fills before the first full one and after the last full one are not counted.
+fn compute_consumption(energy_type: EnergyType, entries: &[&FuelEntry]) -> ConsumptionStats {
+    let mut last_full_odometer = None;
+    let mut pending_quantity = 0.0;
+    let mut pending_cost = 0;
+
+    let mut distance_km = 0;
+    let mut quantity = 0.0;
+    let mut cost_cents = 0;
+
+    for entry in entries {
+        if last_full_odometer.is_some() {
+            pending_quantity += entry.quantity;
+            pending_cost += entry.price_cents;
+        }
+
+        if entry.full_tank {
+            if let Some(start) = last_full_odometer {
+                distance_km += entry.odometer - start;
+                quantity += pending_quantity;
+                cost_cents += pending_cost;
+            }
+
+            last_full_odometer = Some(entry.odometer);
+            pending_quantity = 0.0;
+            pending_cost = 0;
+        }
+    }
+
+    let (consumption_per_100km, cost_per_km_cents) = if distance_km > 0 {
+        (
+            Some(quantity * 100.0 / distance_km as f64),
+            Some(cost_cents as f64 / distance_km as f64),
+        )
+    } else {
+        (None, None)
+    };
+
+    ConsumptionStats {
+        energy_type,
+        consumption_per_100km,
+        cost_per_km_cents,
+        distance_km,
+        quantity,
+        cost_cents,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct MonthlyFuelTotal {
+    /// First day of the month.
+    pub month: NaiveDate,
+    pub energy_type: EnergyType,
+    pub quantity: f64,
+    pub cost_cents: i64,
+    pub entries: i64,
+}
+
+pub async fn get_fuel_monthly_totals(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<MonthlyFuelTotal>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let totals = sqlx::query_as::<_, MonthlyFuelTotal>(
+        r#"
+        SELECT
+            date_trunc('month', filled_at)::date AS month,
+            energy_type,
+            SUM(quantity) AS quantity,
+            SUM(price_cents)::BIGINT AS cost_cents,
+            COUNT(*) AS entries
+        FROM fuel_entries
+        WHERE car_id=$1
+        GROUP BY month, energy_type
+        ORDER BY month DESC, energy_type
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(totals))
+}
@@ -0,0 +1,435 @@
+use super::{
+    authenticate::User,
+    car::{lock_car, Permission},
+    AppState,
+};
+use crate::{
+    errors::Error,
+    geo::{Area, Coordinates, TrackPoint},
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::{Postgres, Transaction};
+use validator::{Validate, ValidationError};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "geofence_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum GeofenceKind {
+    Circle,
+    Polygon,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "geofence_event_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum GeofenceEventKind {
+    Enter,
+    Exit,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+#[validate(schema(function = "validate_geofence"))]
+pub struct NewGeofence {
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    #[serde(flatten)]
+    pub area: Area,
+}
+
+fn validate_geofence(geofence: &NewGeofence) -> Result<(), ValidationError> {
+    let coordinates = match &geofence.area {
+        Area::Circle { center, radius_m } => {
+            if *radius_m <= 0.0 {
+                return Err(ValidationError::new("radius_not_positive"));
+            }
+            std::slice::from_ref(center)
+        }
+        Area::Polygon { vertices } => {
+            if vertices.len() < 3 {
+                return Err(ValidationError::new("polygon_needs_three_vertices"));
+            }
+            vertices.as_slice()
+        }
+    };
+
+    if coordinates
+        .iter()
+        .any(|coordinates| coordinates.validate().is_err())
+    {
+        return Err(ValidationError::new("coordinates_out_of_range"));
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Geofence {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(flatten)]
+    pub area: Area,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct GeofenceRow {
+    id: Uuid,
+    name: String,
+    kind: GeofenceKind,
+    center_latitude: Option<f64>,
+    center_longitude: Option<f64>,
+    radius_m: Option<f64>,
+    latitudes: Option<Vec<f64>>,
+    longitudes: Option<Vec<f64>>,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl From<GeofenceRow> for Geofence {
+    fn from(row: GeofenceRow) -> Self {
+        // the table constraints ensure the columns of the kind are set
+        let area = match row.kind {
+            GeofenceKind::Circle => Area::Circle {
+                center: Coordinates {
+                    latitude: row.center_latitude.unwrap_or_default(),
+                    longitude: row.center_longitude.unwrap_or_default(),
+                },
+                radius_m: row.radius_m.unwrap_or_default(),
+            },
+            GeofenceKind::Polygon => Area::Polygon {
+                vertices: row
+                    .latitudes
+                    .unwrap_or_default()
+                    .into_iter()
+                    .zip(row.longitudes.unwrap_or_default())
+                    .map(|(latitude, longitude)| Coordinates {
+                        latitude,
+                        longitude,
+                    })
+                    .collect(),
+            },
+        };
+
+        Self {
+            id: row.id,
+            name: row.name,
+            area,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// Locations of a car, they are evaluated against the geofences of the owner of the car.
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewLocations {
+    #[validate(length(min = 1))]
+    #[validate]
+    pub points: Vec<TrackPoint>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct GeofenceEvent {
+    pub id: Uuid,
+    pub geofence_id: Uuid,
+    pub car_id: Uuid,
+    pub kind: GeofenceEventKind,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub occurred_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GeofenceEventsQuery {
+    pub car_id: Option<Uuid>,
+    pub geofence_id: Option<Uuid>,
+    pub kind: Option<GeofenceEventKind>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+pub async fn create_geofence(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Json(new_geofence): Json<NewGeofence>,
+) -> Result<(StatusCode, Json<Geofence>)> {
+    new_geofence.validate()?;
+
+    let (kind, center, radius_m, latitudes, longitudes) = match &new_geofence.area {
+        Area::Circle { center, radius_m } => (
+            GeofenceKind::Circle,
+            Some(*center),
+            Some(*radius_m),
+            None,
+            None,
+        ),
+        Area::Polygon { vertices } => (
+            GeofenceKind::Polygon,
+            None,
+            None,
+            Some(
+                vertices
+                    .iter()
+                    .map(|vertex| vertex.latitude)
+                    .collect::<Vec<_>>(),
+            ),
+            Some(
+                vertices
+                    .iter()
+                    .map(|vertex| vertex.longitude)
+                    .collect::<Vec<_>>(),
+            ),
+        ),
+    };
+
+    let row = sqlx::query_as::<_, GeofenceRow>(
+        r#"
+        INSERT INTO geofences(user_id, name, kind, center_latitude, center_longitude, radius_m, latitudes, longitudes)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING *
+    "#,
+    )
+    .bind(user.id)
+    .bind(new_geofence.name.trim())
+    .bind(kind)
+    .bind(center.map(|center| center.latitude))
+    .bind(center.map(|center| center.longitude))
+    .bind(radius_m)
+    .bind(latitudes)
+    .bind(longitudes)
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(row.into())))
+}
+
+pub async fn get_geofences(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Geofence>>> {
+    let rows = sqlx::query_as::<_, GeofenceRow>(
+        r#"
+        SELECT *
+        FROM geofences
+        WHERE user_id=$1
+        ORDER BY name
+    "#,
+    )
+    .bind(user.id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(rows.into_iter().map(Geofence::from).collect()))
+}
+
+/// The events of the geofence are deleted with it.
+pub async fn delete_geofence(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(geofence_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM geofences
+        WHERE id=$1 AND user_id=$2
+    "#,
+    )
+    .bind(geofence_id)
+    .bind(user.id)
+    .execute(&state.pg_pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound("geofence not found".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Records locations of the car and returns the geofence events they caused.
+pub async fn create_car_locations(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(new_locations): Json<NewLocations>,
+) -> Result<Json<Vec<GeofenceEvent>>> {
+    new_locations.validate()?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_car(&mut tx, &user, &car_id, Permission::LogTrips).await?;
+    let events = detect_geofence_events(&mut tx, &car_id, &new_locations.points).await?;
+
+    tx.commit().await?;
+
+    Ok(Json(events))
+}
+
+/// Compares the points, in time order, with the last known state of the car in each geofence
+/// of its owner and records an event each time the car crosses a border. The first location
+/// of a car in a geofence only sets its state. Points older than the state are ignored.
+/// Points without time are located now. The car has to be locked by the transaction.
+pub async fn detect_geofence_events(
+    tx: &mut Transaction<'_, Postgres>,
+    car_id: &Uuid,
+    points: &[TrackPoint],
+) -> Result<Vec<GeofenceEvent>> {
+    let geofences: Vec<Geofence> = sqlx::query_as::<_, GeofenceRow>(
+        r#"
+        SELECT geofence.*
+        FROM geofences geofence
+        JOIN car ON car.user_id = geofence.user_id
+        WHERE car.id=$1
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(Geofence::from)
+    .collect();
+
+    if geofences.is_empty() || points.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut states: HashMap<Uuid, (bool, NaiveDateTime)> =
+        sqlx::query_as::<_, (Uuid, bool, NaiveDateTime)>(
+            r#"
+            SELECT geofence_id, inside, located_at
+            FROM geofence_states
+            WHERE car_id=$1
+        "#,
+        )
+        .bind(car_id)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|(geofence_id, inside, located_at)| (geofence_id, (inside, located_at)))
+        .collect();
+
+    let now = chrono::Utc::now().naive_utc();
+    let mut points: Vec<(NaiveDateTime, Coordinates)> = points
+        .iter()
+        .map(|point| (point.recorded_at.unwrap_or(now), point.coordinates()))
+        .collect();
+    points.sort_by_key(|(located_at, _)| *located_at);
+
+    let mut events = Vec::new();
+
+    for geofence in &geofences {
+        let mut state = states.get(&geofence.id).copied();
+
+        for (located_at, coordinates) in &points {
+            if matches!(state, Some((_, state_at)) if *located_at < state_at) {
+                continue;
+            }
+
+            let inside = geofence.area.contains(*coordinates);
+            if let Some((was_inside, _)) = state {
+                if was_inside != inside {
+                    let kind = if inside {
+                        GeofenceEventKind::Enter
+                    } else {
+                        GeofenceEventKind::Exit
+                    };
+                    events.push((geofence.id, kind, *coordinates, *located_at));
+                }
+            }
+            state = Some((inside, *located_at));
+        }
+
+        if let Some(state) = state {
+            states.insert(geofence.id, state);
+        }
+    }
+
+    for (geofence_id, (inside, located_at)) in &states {
+        sqlx::query(
+            r#"
+            INSERT INTO geofence_states(geofence_id, car_id, inside, located_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (geofence_id, car_id) DO UPDATE
+            SET inside = EXCLUDED.inside,
+                located_at = EXCLUDED.located_at
+        "#,
+        )
+        .bind(geofence_id)
+        .bind(car_id)
+        .bind(inside)
+        .bind(located_at)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let mut recorded = Vec::with_capacity(events.len());
+    for (geofence_id, kind, coordinates, occurred_at) in events {
+        let event = sqlx::query_as::<_, GeofenceEvent>(
+            r#"
+            INSERT INTO geofence_events(geofence_id, car_id, kind, latitude, longitude, occurred_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+        "#,
+        )
+        .bind(geofence_id)
+        .bind(car_id)
+        .bind(kind)
+        .bind(coordinates.latitude)
+        .bind(coordinates.longitude)
+        .bind(occurred_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        recorded.push(event);
+    }
+    recorded.sort_by_key(|event| event.occurred_at);
+
+    Ok(recorded)
+}
+
+/// Events of the geofences of the user, the filters are optional and `from`/`to` are inclusive.
+pub async fn get_geofence_events(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<GeofenceEventsQuery>,
+) -> Result<Json<Vec<GeofenceEvent>>> {
+    let events = sqlx::query_as::<_, GeofenceEvent>(
+        r#"
+        SELECT event.*
+        FROM geofence_events event
+        JOIN geofences geofence ON geofence.id = event.geofence_id
+        WHERE geofence.user_id=$1
+            AND ($2::uuid IS NULL OR event.car_id=$2)
+            AND ($3::uuid IS NULL OR event.geofence_id=$3)
+            AND ($4::geofence_event_kind IS NULL OR event.kind=$4)
+            AND ($5::timestamp IS NULL OR event.occurred_at >= $5)
+            AND ($6::timestamp IS NULL OR event.occurred_at <= $6)
+        ORDER BY event.occurred_at DESC
+    "#,
+    )
+    .bind(user.id)
+    .bind(query.car_id)
+    .bind(query.geofence_id)
+    .bind(query.kind)
+    .bind(query.from)
+    .bind(query.to)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(events))
+}
@@ -0,0 +1,214 @@
+use super::{
+    authenticate::User,
+    car::{check_car_permission, Permission},
+    AppState,
+};
+use crate::{
+    encrypt::{decrypt_data, encrypt_data, spawn_crypto},
+    errors::Error,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "coverage_type", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CoverageType {
+    ThirdParty,
+    ThirdPartyFireTheft,
+    Comprehensive,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+#[validate(schema(function = "validate_policy_dates"))]
+pub struct NewInsurancePolicy {
+    #[validate(length(min = 1))]
+    pub insurer: String,
+    #[validate(length(min = 1))]
+    pub policy_number: String,
+    pub coverage_type: CoverageType,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    #[validate(range(min = 0))]
+    pub premium_cents: i64,
+}
+
+fn validate_policy_dates(policy: &NewInsurancePolicy) -> Result<(), ValidationError> {
+    if policy.ends_on < policy.starts_on {
+        return Err(ValidationError::new("ends_on_before_starts_on"));
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct InsurancePolicy {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub insurer: String,
+    pub policy_number: String,
+    pub coverage_type: CoverageType,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub premium_cents: i64,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl InsurancePolicy {
+    fn decrypted(mut self) -> Result<Self> {
+        self.policy_number = decrypt_data(self.policy_number)?;
+
+        Ok(self)
+    }
+}
+
+pub async fn create_insurance_policy(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(new_policy): Json<NewInsurancePolicy>,
+) -> Result<(StatusCode, Json<InsurancePolicy>)> {
+    new_policy.validate()?;
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Edit).await?;
+
+    let policy_number = new_policy.policy_number.trim().to_owned();
+    let policy_number = spawn_crypto(move || encrypt_data(policy_number)).await?;
+
+    let policy = sqlx::query_as::<_, InsurancePolicy>(
+        r#"
+        INSERT INTO insurance_policies(car_id, insurer, policy_number, coverage_type, starts_on, ends_on, premium_cents)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(&new_policy.insurer)
+    .bind(policy_number)
+    .bind(new_policy.coverage_type)
+    .bind(new_policy.starts_on)
+    .bind(new_policy.ends_on)
+    .bind(new_policy.premium_cents)
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    let policy = spawn_crypto(move || policy.decrypted()).await?;
+
+    Ok((StatusCode::CREATED, Json(policy)))
+}
+
+pub async fn get_insurance_policies(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<InsurancePolicy>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let policies = sqlx::query_as::<_, InsurancePolicy>(
+        r#"
+        SELECT *
+        FROM insurance_policies
+        WHERE car_id=$1
+        ORDER BY ends_on DESC
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    let policies = spawn_crypto(move || {
+        policies
+            .into_iter()
+            .map(InsurancePolicy::decrypted)
+            .collect::<Result<_>>()
+    })
+    .await?;
+
+    Ok(Json(policies))
+}
+
+/// Ten years, the policies ending later are not expiring.
+const MAX_WITHIN_DAYS: i32 = 3650;
+
+#[derive(Deserialize, Debug, Clone, Validate)]
+pub struct ExpiringPoliciesQuery {
+    #[serde(default = "default_within_days")]
+    #[validate(range(min = 0, max = "MAX_WITHIN_DAYS"))]
+    pub within_days: i32,
+}
+
+fn default_within_days() -> i32 {
+    30
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExpiringPolicy {
+    #[serde(flatten)]
+    pub policy: InsurancePolicy,
+    /// Negative once the policy has expired.
+    pub days_left: i64,
+}
+
+/// Lists the latest policy of each car of the user, owned or shared, when it ends within `within_days` days
+/// or has already ended, the cars are then not insured unless a new policy is added.
+pub async fn get_expiring_insurance_policies(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ExpiringPoliciesQuery>,
+) -> Result<Json<Vec<ExpiringPolicy>>> {
+    query.validate()?;
+
+    let today = chrono::Utc::now().date_naive();
+
+    let policies = sqlx::query_as::<_, InsurancePolicy>(
+        r#"
+        SELECT policy.*
+        FROM insurance_policies policy
+        JOIN car ON car.id = policy.car_id
+        LEFT JOIN car_members member ON member.car_id=car.id AND member.user_id=$1
+        WHERE (car.user_id=$1 OR member.user_id=$1)
+            AND car.archived_at IS NULL
+            AND policy.ends_on <= $2::date + $3::integer
+            AND NOT EXISTS (
+                SELECT 1
+                FROM insurance_policies newer
+                WHERE newer.car_id = policy.car_id AND newer.ends_on > policy.ends_on
+            )
+        ORDER BY policy.ends_on
+    "#,
+    )
+    .bind(user.id)
+    .bind(today)
+    .bind(query.within_days)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    let policies = spawn_crypto(move || {
+        policies
+            .into_iter()
+            .map(|policy| {
+                let days_left = (policy.ends_on - today).num_days();
+
+                Ok(ExpiringPolicy {
+                    policy: policy.decrypted()?,
+                    days_left,
+                })
+            })
+            .collect::<Result<_>>()
+    })
+    .await?;
+
+    Ok(Json(policies))
+}
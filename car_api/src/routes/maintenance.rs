@@ -0,0 +1,401 @@
+use super::{
+    authenticate::User,
+    car::{check_car_permission, Permission},
+    odometer::get_current_mileage,
+    AppState,
+};
+use crate::errors::Error;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::PgPool;
+use validator::{Validate, ValidationError};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Validate)]
+pub struct NewMaintenanceRecord {
+    #[validate(length(min = 1))]
+    pub service_type: String,
+    pub performed_on: NaiveDate,
+    #[validate(range(min = 0))]
+    pub mileage: Option<i64>,
+    #[validate(range(min = 0))]
+    pub cost_cents: Option<i64>,
+    pub garage: Option<String>,
+    pub notes: Option<String>,
+}
+
+impl NewMaintenanceRecord {
+    /// Trims the service type, it is validated and matched with the intervals once trimmed.
+    pub fn normalize(&mut self) {
+        self.service_type = self.service_type.trim().to_owned();
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct MaintenanceRecord {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub service_type: String,
+    pub performed_on: NaiveDate,
+    pub mileage: Option<i64>,
+    pub cost_cents: Option<i64>,
+    pub garage: Option<String>,
+    pub notes: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+pub async fn create_maintenance_record(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(mut new_record): Json<NewMaintenanceRecord>,
+) -> Result<(StatusCode, Json<MaintenanceRecord>)> {
+    new_record.normalize();
+    new_record.validate()?;
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Edit).await?;
+
+    let record = sqlx::query_as::<_, MaintenanceRecord>(
+        r#"
+        INSERT INTO maintenance_records(car_id, service_type, performed_on, mileage, cost_cents, garage, notes)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(&new_record.service_type)
+    .bind(new_record.performed_on)
+    .bind(new_record.mileage)
+    .bind(new_record.cost_cents)
+    .bind(&new_record.garage)
+    .bind(&new_record.notes)
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(record)))
+}
+
+pub async fn get_maintenance_records(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<MaintenanceRecord>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let records = get_car_maintenance_records(&state.pg_pool, &car_id).await?;
+
+    Ok(Json(records))
+}
+
+pub async fn get_car_maintenance_records(
+    pg_pool: &PgPool,
+    car_id: &Uuid,
+) -> Result<Vec<MaintenanceRecord>> {
+    let records = sqlx::query_as::<_, MaintenanceRecord>(
+        r#"
+        SELECT *
+        FROM maintenance_records
+        WHERE car_id=$1
+        ORDER BY performed_on DESC, created_at DESC
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(pg_pool)
+    .await?;
+
+    Ok(records)
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Validate)]
+#[validate(schema(function = "validate_service_interval"))]
+pub struct NewServiceInterval {
+    #[validate(length(min = 1))]
+    pub service_type: String,
+    #[validate(range(min = 1))]
+    pub interval_months: Option<i32>,
+    #[validate(range(min = 1))]
+    pub interval_km: Option<i64>,
+}
+
+impl NewServiceInterval {
+    /// Trims the service type, it is validated and matched with the records once trimmed.
+    pub fn normalize(&mut self) {
+        self.service_type = self.service_type.trim().to_owned();
+    }
+}
+
+fn validate_service_interval(interval: &NewServiceInterval) -> Result<(), ValidationError> {
+    if interval.interval_months.is_none() && interval.interval_km.is_none() {
+        return Err(ValidationError::new(
+            "interval_months_or_interval_km_required",
+        ));
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct ServiceInterval {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub service_type: String,
+    pub interval_months: Option<i32>,
+    pub interval_km: Option<i64>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Creates the interval of a service type, or replaces it if it already exists.
+pub async fn set_service_interval(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(mut new_interval): Json<NewServiceInterval>,
+) -> Result<Json<ServiceInterval>> {
+    new_interval.normalize();
+    new_interval.validate()?;
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Edit).await?;
+
+    let interval = sqlx::query_as::<_, ServiceInterval>(
+        r#"
+        INSERT INTO service_intervals(car_id, service_type, interval_months, interval_km)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (car_id, service_type)
+        DO UPDATE SET interval_months = EXCLUDED.interval_months, interval_km = EXCLUDED.interval_km
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(&new_interval.service_type)
+    .bind(new_interval.interval_months)
+    .bind(new_interval.interval_km)
+    .fetch_one(&state.pg_pool)
+    .await?;
+
+    Ok(Json(interval))
+}
+
+pub async fn get_service_intervals(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<ServiceInterval>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let intervals = get_car_service_intervals(&state.pg_pool, &car_id).await?;
+
+    Ok(Json(intervals))
+}
+
+pub async fn delete_service_interval(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, interval_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Delete).await?;
+
+    let result = sqlx::query(
+        r#"
+        DELETE FROM service_intervals
+        WHERE id=$1 AND car_id=$2
+    "#,
+    )
+    .bind(interval_id)
+    .bind(car_id)
+    .execute(&state.pg_pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound("service interval not found".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_car_service_intervals(
+    pg_pool: &PgPool,
+    car_id: &Uuid,
+) -> Result<Vec<ServiceInterval>> {
+    let intervals = sqlx::query_as::<_, ServiceInterval>(
+        r#"
+        SELECT *
+        FROM service_intervals
+        WHERE car_id=$1
+        ORDER BY service_type
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(pg_pool)
+    .await?;
+
+    Ok(intervals)
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DueServicesQuery {
+    /// A service due in less than `within_days` days is upcoming.
+    #[serde(default = "default_within_days")]
+    pub within_days: i64,
+    /// A service due in less than `within_km` km is upcoming.
+    #[serde(default = "default_within_km")]
+    pub within_km: i64,
+}
+
+fn default_within_days() -> i64 {
+    30
+}
+
+fn default_within_km() -> i64 {
+    1000
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceStatus {
+    Overdue,
+    Upcoming,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ServiceDue {
+    pub service_type: String,
+    pub status: ServiceStatus,
+    pub last_performed_on: Option<NaiveDate>,
+    pub last_mileage: Option<i64>,
+    pub due_on: Option<NaiveDate>,
+    pub due_mileage: Option<i64>,
+    pub current_mileage: Option<i64>,
+}
+
+/// Lists the services of the car that are overdue or upcoming, overdue ones first.
+pub async fn get_due_services(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Query(query): Query<DueServicesQuery>,
+) -> Result<Json<Vec<ServiceDue>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let intervals = get_car_service_intervals(&state.pg_pool, &car_id);
+    let records = get_car_maintenance_records(&state.pg_pool, &car_id);
+    let current_mileage = get_current_mileage(&state.pg_pool, &car_id);
+    let car_created_at = get_car_created_at(&state.pg_pool, &car_id);
+
+    let (intervals, records, current_mileage, car_created_at) =
+        tokio::try_join!(intervals, records, current_mileage, car_created_at)?;
+
+    let today = chrono::Utc::now().date_naive();
+
+    let mut due_services: Vec<ServiceDue> = intervals
+        .iter()
+        .filter_map(|interval| {
+            // records are sorted by most recent first
+            let mut service_records = records
+                .iter()
+                .filter(|record| record.service_type == interval.service_type);
+            let last = service_records.next();
+            // a record without mileage still counts for the time interval only
+            let last_with_mileage = last
+                .filter(|record| record.mileage.is_some())
+                .or_else(|| service_records.find(|record| record.mileage.is_some()));
+
+            compute_service_due(
+                interval,
+                last,
+                last_with_mileage,
+                car_created_at.date(),
+                current_mileage,
+                today,
+                &query,
+            )
+        })
+        .collect();
+
+    due_services.sort_by_key(|service| (service.status, service.due_on));
+
+    Ok(Json(due_services))
+}
+
+/// Without any record of the service, the interval is counted from the car registration
+/// and from a mileage of 0. The km interval is counted from the latest record with a mileage.
+fn compute_service_due(
+    interval: &ServiceInterval,
+    last: Option<&MaintenanceRecord>,
+    last_with_mileage: Option<&MaintenanceRecord>,
+    car_created_on: NaiveDate,
+    current_mileage: Option<i64>,
+    today: NaiveDate,
+    query: &DueServicesQuery,
+) -> Option<ServiceDue> {
+    let last_performed_on = last.map(|record| record.performed_on);
+    let last_mileage = match last_with_mileage {
+        Some(record) => record.mileage,
+        None => Some(0),
+    };
+
+    let due_on = interval.interval_months.and_then(|months| {
+        last_performed_on
+            .unwrap_or(car_created_on)
+            .checked_add_months(Months::new(months as u32))
+    });
+    let due_mileage = interval
+        .interval_km
+        .zip(last_mileage)
+        .map(|(interval_km, last_mileage)| last_mileage + interval_km);
+
+    let days_left = due_on.map(|due_on| (due_on - today).num_days());
+    let km_left = due_mileage
+        .zip(current_mileage)
+        .map(|(due_mileage, current_mileage)| due_mileage - current_mileage);
+
+    let status =
+        if matches!(days_left, Some(days) if days <= 0) || matches!(km_left, Some(km) if km <= 0) {
+            ServiceStatus::Overdue
+        } else if matches!(days_left, Some(days) if days <= query.within_days)
+            || matches!(km_left, Some(km) if km <= query.within_km)
+        {
+            ServiceStatus::Upcoming
+        } else {
+            return None;
+        };
+
+    Some(ServiceDue {
+        service_type: interval.service_type.clone(),
+        status,
+        last_performed_on,
+        last_mileage: last_with_mileage.and_then(|record| record.mileage),
+        due_on,
+        due_mileage,
+        current_mileage,
+    })
+}
+
+#[derive(sqlx::FromRow)]
+struct CarCreatedAt {
+    created_at: chrono::NaiveDateTime,
+}
+
+async fn get_car_created_at(pg_pool: &PgPool, car_id: &Uuid) -> Result<chrono::NaiveDateTime> {
+    let car = sqlx::query_as::<_, CarCreatedAt>(
+        r#"
+        SELECT created_at
+        FROM car
+        WHERE id=$1
+    "#,
+    )
+    .bind(car_id)
+    .fetch_one(pg_pool)
+    .await?;
+
+    Ok(car.created_at)
+}
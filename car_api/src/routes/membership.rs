@@ -0,0 +1,403 @@
+use super::{
+    authenticate::User,
+    car::{check_car_owner, check_car_permission, CarRole, Permission, Permissions},
+    AppState,
+};
+use crate::errors::Error;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use tracing::debug;
+use validator::{Validate, ValidationError};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "invitation_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum InvitationStatus {
+    Pending,
+    Accepted,
+    Declined,
+    Revoked,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewInvitation {
+    #[validate(email)]
+    pub email: String,
+    #[validate(custom = "validate_member_role")]
+    pub role: CarRole,
+    /// Defaults to the permissions of the role.
+    pub permissions: Option<Vec<Permission>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct UpdateMember {
+    #[validate(custom = "validate_member_role")]
+    pub role: CarRole,
+    /// Defaults to the permissions of the role.
+    pub permissions: Option<Vec<Permission>>,
+}
+
+fn validate_member_role(role: &CarRole) -> Result<(), ValidationError> {
+    if *role == CarRole::Owner {
+        return Err(ValidationError::new("owner_role_not_allowed"));
+    }
+
+    Ok(())
+}
+
+fn member_permissions(role: CarRole, permissions: &Option<Vec<Permission>>) -> Permissions {
+    match permissions {
+        Some(permissions) => Permissions::from_list(permissions),
+        None => Permissions::for_role(role),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct Invitation {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub invited_by: Uuid,
+    pub email: String,
+    pub role: CarRole,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub permissions: Permissions,
+    pub status: InvitationStatus,
+    pub responded_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct CarMember {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub email: String,
+    pub role: CarRole,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub permissions: Permissions,
+}
+
+/// Invites an account by email to share the car, only the owner can invite.
+pub async fn invite_car_member(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(new_invitation): Json<NewInvitation>,
+) -> Result<(StatusCode, Json<Invitation>)> {
+    new_invitation.validate()?;
+    check_car_owner(&state.pg_pool, &user, &car_id).await?;
+
+    let email = new_invitation.email.trim().to_lowercase();
+    if email == user.email.to_lowercase() {
+        return Err(Error::Conflict("cannot invite yourself".into()));
+    }
+
+    let already_member = sqlx::query_as::<_, (Uuid,)>(
+        r#"
+        SELECT member.user_id
+        FROM car_members member
+        JOIN users ON users.id = member.user_id
+        WHERE member.car_id=$1 AND lower(users.email)=$2
+    "#,
+    )
+    .bind(car_id)
+    .bind(&email)
+    .fetch_optional(&state.pg_pool)
+    .await?;
+
+    if already_member.is_some() {
+        return Err(Error::Conflict("already a member of this car".into()));
+    }
+
+    let permissions = member_permissions(new_invitation.role, &new_invitation.permissions);
+
+    let invitation = sqlx::query_as::<_, Invitation>(
+        r#"
+        INSERT INTO car_invitations(car_id, invited_by, email, role, can_log_trips, can_edit, can_delete)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(user.id)
+    .bind(&email)
+    .bind(new_invitation.role)
+    .bind(permissions.can_log_trips)
+    .bind(permissions.can_edit)
+    .bind(permissions.can_delete)
+    .fetch_one(&state.pg_pool)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(db_err)
+            if db_err.code().as_deref() == Some("23505")
+                && db_err.constraint() == Some("car_invitations_pending_idx") =>
+        {
+            debug!("{}", db_err.message());
+            Error::Conflict("an invitation is already pending for this email".into())
+        }
+        err => err.into(),
+    })?;
+
+    Ok((StatusCode::CREATED, Json(invitation)))
+}
+
+pub async fn get_car_invitations(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<Invitation>>> {
+    check_car_owner(&state.pg_pool, &user, &car_id).await?;
+
+    let invitations = sqlx::query_as::<_, Invitation>(
+        r#"
+        SELECT *
+        FROM car_invitations
+        WHERE car_id=$1
+        ORDER BY created_at DESC
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(invitations))
+}
+
+pub async fn revoke_car_invitation(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, invitation_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    check_car_owner(&state.pg_pool, &user, &car_id).await?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE car_invitations
+        SET status='revoked', responded_at=current_timestamp
+        WHERE id=$1 AND car_id=$2 AND status='pending'
+    "#,
+    )
+    .bind(invitation_id)
+    .bind(car_id)
+    .execute(&state.pg_pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound("invitation not found".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Pending invitations sent to the email of the user.
+pub async fn get_my_invitations(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Invitation>>> {
+    let invitations = sqlx::query_as::<_, Invitation>(
+        r#"
+        SELECT *
+        FROM car_invitations
+        WHERE lower(email)=lower($1) AND status='pending'
+        ORDER BY created_at DESC
+    "#,
+    )
+    .bind(&user.email)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(invitations))
+}
+
+pub async fn accept_invitation(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(invitation_id): Path<Uuid>,
+) -> Result<Json<Invitation>> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let invitation = sqlx::query_as::<_, Invitation>(
+        r#"
+        UPDATE car_invitations
+        SET status='accepted', responded_at=current_timestamp
+        WHERE id=$1 AND lower(email)=lower($2) AND status='pending'
+        RETURNING *
+    "#,
+    )
+    .bind(invitation_id)
+    .bind(&user.email)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| Error::NotFound("invitation not found".into()))?;
+
+    let is_owner = sqlx::query_as::<_, (Uuid,)>(
+        r#"
+        SELECT id
+        FROM car
+        WHERE id=$1 AND user_id=$2
+    "#,
+    )
+    .bind(invitation.car_id)
+    .bind(user.id)
+    .fetch_optional(&mut tx)
+    .await?;
+
+    if is_owner.is_some() {
+        return Err(Error::Conflict("already the owner of this car".into()));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO car_members(car_id, user_id, role, can_log_trips, can_edit, can_delete)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (car_id, user_id) DO UPDATE
+        SET role = EXCLUDED.role,
+            can_log_trips = EXCLUDED.can_log_trips,
+            can_edit = EXCLUDED.can_edit,
+            can_delete = EXCLUDED.can_delete
+    "#,
+    )
+    .bind(invitation.car_id)
+    .bind(user.id)
+    .bind(invitation.role)
+    .bind(invitation.permissions.can_log_trips)
+    .bind(invitation.permissions.can_edit)
+    .bind(invitation.permissions.can_delete)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(invitation))
+}
+
+pub async fn decline_invitation(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(invitation_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    let result = sqlx::query(
+        r#"
+        UPDATE car_invitations
+        SET status='declined', responded_at=current_timestamp
+        WHERE id=$1 AND lower(email)=lower($2) AND status='pending'
+    "#,
+    )
+    .bind(invitation_id)
+    .bind(&user.email)
+    .execute(&state.pg_pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound("invitation not found".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists the owner and the members of the car.
+pub async fn get_car_members(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<CarMember>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let members = sqlx::query_as::<_, CarMember>(
+        r#"
+        SELECT users.id AS user_id, users.user_name, users.email, 'owner'::car_role AS role,
+            true AS can_log_trips, true AS can_edit, true AS can_delete
+        FROM car
+        JOIN users ON users.id = car.user_id
+        WHERE car.id=$1
+        UNION ALL
+        SELECT users.id AS user_id, users.user_name, users.email, member.role,
+            member.can_log_trips, member.can_edit, member.can_delete
+        FROM car_members member
+        JOIN users ON users.id = member.user_id
+        WHERE member.car_id=$1
+        ORDER BY role, email
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(members))
+}
+
+pub async fn update_car_member(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, member_id)): Path<(Uuid, Uuid)>,
+    Json(update): Json<UpdateMember>,
+) -> Result<StatusCode> {
+    update.validate()?;
+    check_car_owner(&state.pg_pool, &user, &car_id).await?;
+
+    let permissions = member_permissions(update.role, &update.permissions);
+
+    let result = sqlx::query(
+        r#"
+        UPDATE car_members
+        SET role=$3, can_log_trips=$4, can_edit=$5, can_delete=$6
+        WHERE car_id=$1 AND user_id=$2
+    "#,
+    )
+    .bind(car_id)
+    .bind(member_id)
+    .bind(update.role)
+    .bind(permissions.can_log_trips)
+    .bind(permissions.can_edit)
+    .bind(permissions.can_delete)
+    .execute(&state.pg_pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound("member not found".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The owner can remove any member, a member can leave the car.
+pub async fn remove_car_member(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, member_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    if member_id == user.id {
+        check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+    } else {
+        check_car_owner(&state.pg_pool, &user, &car_id).await?;
+    }
+
+    let result = sqlx::query(
+        r#"
+        DELETE FROM car_members
+        WHERE car_id=$1 AND user_id=$2
+    "#,
+    )
+    .bind(car_id)
+    .bind(member_id)
+    .execute(&state.pg_pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound("member not found".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,11 +1,32 @@
 pub mod account;
 pub mod authenticate;
+pub mod bank_details;
+pub mod car;
+pub mod car_csv;
+pub mod charge;
+pub mod document;
+pub mod ev;
+pub mod fuel;
+pub mod geofence;
 pub mod health_check;
+pub mod insurance;
+pub mod maintenance;
+pub mod membership;
+pub mod odometer;
+pub mod parking;
+pub mod photo;
+pub mod recall;
+pub mod sepa;
+pub mod transfer;
+pub mod trip;
 pub mod user;
 
+use crate::storage::Storage;
+
 use sqlx::postgres::PgPool;
 
 /// The data that is shared across the processes.
 pub struct AppState {
     pub pg_pool: PgPool,
+    pub storage: Box<dyn Storage>,
 }
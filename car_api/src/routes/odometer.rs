@@ -0,0 +1,185 @@
+use super::{
+    authenticate::User,
+    car::{check_car_permission, lock_car, Permission},
+    AppState,
+};
+use crate::errors::Error;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::PgPool;
+use validator::Validate;
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Validate)]
+pub struct NewOdometerReading {
+    #[validate(range(min = 0))]
+    pub mileage: i64,
+    /// The odometer was replaced, the mileage is allowed to be lower than the previous one.
+    #[serde(default)]
+    pub meter_replacement: bool,
+    /// Defaults to now.
+    pub recorded_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct OdometerReading {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub mileage: i64,
+    pub meter_replacement: bool,
+    pub recorded_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+pub async fn create_odometer_reading(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(new_reading): Json<NewOdometerReading>,
+) -> Result<(StatusCode, Json<OdometerReading>)> {
+    new_reading.validate()?;
+
+    let recorded_at = new_reading
+        .recorded_at
+        .unwrap_or_else(|| chrono::Utc::now().naive_utc());
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    // lock the car so concurrent readings are checked against each other
+    lock_car(&mut tx, &user, &car_id, Permission::LogTrips).await?;
+
+    let previous = sqlx::query_as::<_, OdometerReading>(
+        r#"
+        SELECT *
+        FROM odometer_readings
+        WHERE car_id=$1 AND recorded_at <= $2
+        ORDER BY recorded_at DESC, created_at DESC
+        LIMIT 1
+    "#,
+    )
+    .bind(car_id)
+    .bind(recorded_at)
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let next = sqlx::query_as::<_, OdometerReading>(
+        r#"
+        SELECT *
+        FROM odometer_readings
+        WHERE car_id=$1 AND recorded_at > $2
+        ORDER BY recorded_at ASC, created_at ASC
+        LIMIT 1
+    "#,
+    )
+    .bind(car_id)
+    .bind(recorded_at)
+    .fetch_optional(&mut tx)
+    .await?;
+
+    check_monotonic(&new_reading, previous.as_ref(), next.as_ref())?;
+
+    let reading = sqlx::query_as::<_, OdometerReading>(
+        r#"
+        INSERT INTO odometer_readings(car_id, mileage, meter_replacement, recorded_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(new_reading.mileage)
+    .bind(new_reading.meter_replacement)
+    .bind(recorded_at)
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(reading)))
+}
+
+/// A reading can't be lower than the one before it, nor higher than the one after it,
+/// unless the later of the two is a meter replacement.
+fn check_monotonic(
+    new_reading: &NewOdometerReading,
+    previous: Option<&OdometerReading>,
+    next: Option<&OdometerReading>,
+) -> Result<()> {
+    if let Some(previous) = previous {
+        if !new_reading.meter_replacement && new_reading.mileage < previous.mileage {
+            return Err(Error::Conflict(format!(
+                "mileage {} is lower than the previous reading of {}",
+                new_reading.mileage, previous.mileage
+            )));
+        }
+    }
+
+    if let Some(next) = next {
+        if !next.meter_replacement && next.mileage < new_reading.mileage {
+            return Err(Error::Conflict(format!(
+                "mileage {} is higher than the following reading of {}",
+                new_reading.mileage, next.mileage
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn get_odometer_readings(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<OdometerReading>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let readings = get_car_odometer_readings(&state.pg_pool, &car_id).await?;
+
+    Ok(Json(readings))
+}
+
+pub async fn get_car_odometer_readings(
+    pg_pool: &PgPool,
+    car_id: &Uuid,
+) -> Result<Vec<OdometerReading>> {
+    let readings = sqlx::query_as::<_, OdometerReading>(
+        r#"
+        SELECT *
+        FROM odometer_readings
+        WHERE car_id=$1
+        ORDER BY recorded_at DESC, created_at DESC
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(pg_pool)
+    .await?;
+
+    Ok(readings)
+}
+
+/// Returns the mileage of the latest reading of the car.
+pub async fn get_current_mileage(pg_pool: &PgPool, car_id: &Uuid) -> Result<Option<i64>> {
+    let reading = sqlx::query_as::<_, OdometerReading>(
+        r#"
+        SELECT *
+        FROM odometer_readings
+        WHERE car_id=$1
+        ORDER BY recorded_at DESC, created_at DESC
+        LIMIT 1
+    "#,
+    )
+    .bind(car_id)
+    .fetch_optional(pg_pool)
+    .await?;
+
+    Ok(reading.map(|reading| reading.mileage))
+}
@@ -0,0 +1,218 @@
+use super::{
+    authenticate::User,
+    car::{check_car_permission, lock_car, Permission},
+    geofence::detect_geofence_events,
+    AppState,
+};
+use crate::{errors::Error, geo::TrackPoint};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::PgPool;
+use tracing::{debug, error};
+use validator::{Validate, ValidationError, ValidationErrors};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_LOCATION_RETENTION_DAYS: i32 = 30;
+
+const PURGE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewParkedLocation {
+    #[validate(range(min = -90.0, max = 90.0))]
+    pub latitude: f64,
+    #[validate(range(min = -180.0, max = 180.0))]
+    pub longitude: f64,
+    #[validate(length(max = 255))]
+    pub level: Option<String>,
+    #[validate(length(max = 1000))]
+    pub spot_note: Option<String>,
+    /// A photo of the spot uploaded as a document of the car.
+    pub photo_document_id: Option<Uuid>,
+    pub meter_expires_at: Option<chrono::NaiveDateTime>,
+    /// Defaults to now.
+    pub parked_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ParkedLocation {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub level: Option<String>,
+    pub spot_note: Option<String>,
+    pub photo_document_id: Option<Uuid>,
+    pub meter_expires_at: Option<chrono::NaiveDateTime>,
+    pub parked_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Records where the car is parked, the location is also checked against the geofences.
+pub async fn park_car(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(new_location): Json<NewParkedLocation>,
+) -> Result<(StatusCode, Json<ParkedLocation>)> {
+    new_location.validate()?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_car(&mut tx, &user, &car_id, Permission::LogTrips).await?;
+
+    if let Some(document_id) = new_location.photo_document_id {
+        let document = sqlx::query_as::<_, (Uuid,)>(
+            r#"
+            SELECT id
+            FROM documents
+            WHERE id=$1 AND car_id=$2
+        "#,
+        )
+        .bind(document_id)
+        .bind(car_id)
+        .fetch_optional(&mut tx)
+        .await?;
+
+        if document.is_none() {
+            let mut errors = ValidationErrors::new();
+            errors.add(
+                "photo_document_id",
+                ValidationError::new("unknown_document"),
+            );
+            return Err(errors.into());
+        }
+    }
+
+    let location = sqlx::query_as::<_, ParkedLocation>(
+        r#"
+        INSERT INTO car_locations(car_id, latitude, longitude, level, spot_note, photo_document_id, meter_expires_at, parked_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, COALESCE($8, current_timestamp))
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(new_location.latitude)
+    .bind(new_location.longitude)
+    .bind(new_location.level.as_deref().map(str::trim))
+    .bind(new_location.spot_note.as_deref().map(str::trim))
+    .bind(new_location.photo_document_id)
+    .bind(new_location.meter_expires_at)
+    .bind(new_location.parked_at)
+    .fetch_one(&mut tx)
+    .await?;
+
+    let point = TrackPoint {
+        latitude: location.latitude,
+        longitude: location.longitude,
+        elevation: None,
+        recorded_at: Some(location.parked_at),
+    };
+    detect_geofence_events(&mut tx, &car_id, &[point]).await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(location)))
+}
+
+/// Where the car was parked last.
+pub async fn get_parked_location(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<ParkedLocation>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let location = sqlx::query_as::<_, ParkedLocation>(
+        r#"
+        SELECT *
+        FROM car_locations
+        WHERE car_id=$1
+        ORDER BY parked_at DESC, created_at DESC
+        LIMIT 1
+    "#,
+    )
+    .bind(car_id)
+    .fetch_optional(&state.pg_pool)
+    .await?
+    .ok_or_else(|| Error::NotFound("no known location for this car".into()))?;
+
+    Ok(Json(location))
+}
+
+/// The locations of the car kept by the retention period, latest first.
+pub async fn get_parking_history(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<ParkedLocation>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let locations = sqlx::query_as::<_, ParkedLocation>(
+        r#"
+        SELECT *
+        FROM car_locations
+        WHERE car_id=$1
+        ORDER BY parked_at DESC, created_at DESC
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(locations))
+}
+
+/// Number of days the locations are kept, from `LOCATION_RETENTION_DAYS`, 30 by default.
+pub fn location_retention_days() -> i32 {
+    std::env::var("LOCATION_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(DEFAULT_LOCATION_RETENTION_DAYS)
+}
+
+/// Deletes the locations parked before the retention period, the latest location
+/// of each car is kept so the car can still be found. Returns the number of deleted rows.
+pub async fn purge_location_history(pg_pool: &PgPool, retention_days: i32) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM car_locations location
+        WHERE parked_at < current_timestamp - make_interval(days => $1)
+            AND EXISTS (
+                SELECT 1
+                FROM car_locations newer
+                WHERE newer.car_id = location.car_id
+                    AND (newer.parked_at, newer.created_at) > (location.parked_at, location.created_at)
+            )
+    "#,
+    )
+    .bind(retention_days)
+    .execute(pg_pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Purges the location history every hour, runs until the server stops.
+pub async fn run_location_purge(pg_pool: PgPool) {
+    let retention_days = location_retention_days();
+    let mut interval = tokio::time::interval(PURGE_PERIOD);
+
+    loop {
+        interval.tick().await;
+
+        match purge_location_history(&pg_pool, retention_days).await {
+            Ok(deleted) => debug!("purged {} car locations", deleted),
+            Err(err) => error!("failed to purge car locations: {:?}", err),
+        }
+    }
+}
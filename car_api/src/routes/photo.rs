@@ -0,0 +1,293 @@
+use super::{
+    authenticate::User,
+    car::{check_car_permission, lock_car, Permission},
+    AppState,
+};
+use crate::{
+    errors::Error,
+    photo::{process_photo, PhotoVariant},
+    storage::delete_keys,
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::PgExecutor;
+use validator::{ValidationError, ValidationErrors};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+/// Maximum size of an uploaded photo.
+pub const MAX_PHOTO_SIZE: usize = 10 * 1024 * 1024;
+
+const ALLOWED_CONTENT_TYPES: [&str; 2] = ["image/jpeg", "image/png"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Photo {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub content_type: String,
+    /// Dimensions of the original variant.
+    pub width: i32,
+    pub height: i32,
+    pub size_bytes: i64,
+    /// The photo shown with the car, the first photo of a car is the primary one.
+    pub is_primary: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+pub fn photo_storage_key(car_id: &Uuid, photo_id: &Uuid, variant: PhotoVariant) -> String {
+    format!("photos/{}/{}/{}", car_id, photo_id, variant.as_str())
+}
+
+/// The photo is sent as the request body, its thumbnail and medium variants are generated
+/// and the metadata of the image is removed before storing it.
+pub async fn upload_car_photo(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<Photo>)> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_lowercase())
+        .unwrap_or_default();
+    if !ALLOWED_CONTENT_TYPES.contains(&content_type.as_str()) {
+        return Err(Error::UnsupportedMediaType(format!(
+            "content type must be one of {}",
+            ALLOWED_CONTENT_TYPES.join(", ")
+        )));
+    }
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Edit).await?;
+
+    // decoding and resizing are cpu bound, they are kept off the async workers
+    let processed = tokio::task::spawn_blocking(move || process_photo(&body))
+        .await
+        .map_err(std::io::Error::other)?;
+    let processed = match processed {
+        Ok(processed) if processed.content_type == content_type => processed,
+        Ok(_) => return Err(invalid_body(ValidationError::new("content_type_mismatch"))),
+        Err(err) => return Err(invalid_body(err)),
+    };
+
+    let id = Uuid::new_v4();
+    let original_size = processed
+        .variants
+        .iter()
+        .find(|(variant, _)| *variant == PhotoVariant::Original)
+        .map(|(_, content)| content.len())
+        .unwrap_or_default();
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_car(&mut tx, &user, &car_id, Permission::Edit).await?;
+
+    let photo = sqlx::query_as::<_, Photo>(
+        r#"
+        INSERT INTO car_photos(id, car_id, content_type, width, height, size_bytes, is_primary)
+        VALUES ($1, $2, $3, $4, $5, $6, NOT EXISTS (SELECT 1 FROM car_photos WHERE car_id=$2))
+        RETURNING *
+    "#,
+    )
+    .bind(id)
+    .bind(car_id)
+    .bind(processed.content_type)
+    .bind(processed.width as i32)
+    .bind(processed.height as i32)
+    .bind(original_size as i64)
+    .fetch_one(&mut tx)
+    .await?;
+
+    // the row is only committed once all the variants are stored, the stored ones are
+    // removed if the photo is not committed
+    let mut written_keys = Vec::new();
+    let result = async {
+        for (variant, content) in processed.variants {
+            let storage_key = photo_storage_key(&car_id, &id, variant);
+            written_keys.push(storage_key.clone());
+            state.storage.put(&storage_key, content).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok::<_, Error>(())
+    }
+    .await;
+    if let Err(err) = result {
+        delete_keys(state.storage.as_ref(), &written_keys).await;
+        return Err(err);
+    }
+
+    Ok((StatusCode::CREATED, Json(photo)))
+}
+
+fn invalid_body(error: ValidationError) -> Error {
+    let mut errors = ValidationErrors::new();
+    errors.add("body", error);
+    errors.into()
+}
+
+/// The photos of the car, the primary one first.
+pub async fn get_car_photos(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<Photo>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let photos = sqlx::query_as::<_, Photo>(
+        r#"
+        SELECT *
+        FROM car_photos
+        WHERE car_id=$1
+        ORDER BY is_primary DESC, created_at DESC
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(photos))
+}
+
+pub async fn download_car_photo(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, photo_id, variant)): Path<(Uuid, Uuid, PhotoVariant)>,
+) -> Result<Response> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let photo = get_car_photo(&state.pg_pool, &car_id, &photo_id).await?;
+
+    let content = state
+        .storage
+        .get(&photo_storage_key(&car_id, &photo.id, variant))
+        .await?;
+
+    Ok(([(header::CONTENT_TYPE, photo.content_type)], content).into_response())
+}
+
+/// Makes the photo the one shown with the car.
+pub async fn set_primary_car_photo(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, photo_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Photo>> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_car(&mut tx, &user, &car_id, Permission::Edit).await?;
+
+    let photo = get_car_photo(&mut tx, &car_id, &photo_id).await?;
+
+    // a single photo of the car can be primary, the previous one is unset first
+    sqlx::query(
+        r#"
+        UPDATE car_photos
+        SET is_primary=false
+        WHERE car_id=$1 AND is_primary AND id<>$2
+    "#,
+    )
+    .bind(car_id)
+    .bind(photo.id)
+    .execute(&mut tx)
+    .await?;
+
+    let photo = sqlx::query_as::<_, Photo>(
+        r#"
+        UPDATE car_photos
+        SET is_primary=true
+        WHERE id=$1
+        RETURNING *
+    "#,
+    )
+    .bind(photo.id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(photo))
+}
+
+/// Deletes the photo and its variants, the latest photo left becomes primary if needed.
+pub async fn delete_car_photo(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, photo_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_car(&mut tx, &user, &car_id, Permission::Delete).await?;
+
+    let photo = get_car_photo(&mut tx, &car_id, &photo_id).await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM car_photos
+        WHERE id=$1
+    "#,
+    )
+    .bind(photo.id)
+    .execute(&mut tx)
+    .await?;
+
+    if photo.is_primary {
+        sqlx::query(
+            r#"
+            UPDATE car_photos
+            SET is_primary=true
+            WHERE id=(
+                SELECT id
+                FROM car_photos
+                WHERE car_id=$1
+                ORDER BY created_at DESC
+                LIMIT 1
+            )
+        "#,
+        )
+        .bind(car_id)
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    for variant in PhotoVariant::ALL {
+        state
+            .storage
+            .delete(&photo_storage_key(&car_id, &photo.id, variant))
+            .await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_car_photo<'c, E>(executor: E, car_id: &Uuid, photo_id: &Uuid) -> Result<Photo>
+where
+    E: PgExecutor<'c>,
+{
+    sqlx::query_as::<_, Photo>(
+        r#"
+        SELECT *
+        FROM car_photos
+        WHERE id=$1 AND car_id=$2
+    "#,
+    )
+    .bind(photo_id)
+    .bind(car_id)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| Error::NotFound("photo not found".into()))
+}
@@ -0,0 +1,355 @@
+use super::{
+    authenticate::{check_bearer_token, User},
+    car::{check_car_permission, Permission},
+    AppState,
+};
+use crate::errors::Error;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap},
+    Extension, Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::PgPool;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+/// Maximum size of an imported recall feed.
+pub const MAX_RECALL_FEED_SIZE: usize = 10 * 1024 * 1024;
+
+/// A recall campaign of a feed, it applies to the cars inside its VIN range and its year range.
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+#[validate(schema(function = "validate_recall_ranges"))]
+pub struct NewRecall {
+    /// Identifier of the campaign given by the manufacturer, a campaign imported again is updated.
+    #[validate(length(min = 1, max = 255))]
+    pub campaign: String,
+    #[validate(length(min = 1, max = 255))]
+    pub manufacturer: String,
+    /// All the models of the manufacturer when missing.
+    #[validate(length(min = 1, max = 255))]
+    pub model: Option<String>,
+    #[validate(custom = "validate_vin")]
+    pub vin_from: Option<String>,
+    #[validate(custom = "validate_vin")]
+    pub vin_to: Option<String>,
+    #[validate(range(min = 1886, max = 2100))]
+    pub year_from: Option<i32>,
+    #[validate(range(min = 1886, max = 2100))]
+    pub year_to: Option<i32>,
+    #[validate(length(min = 1, max = 10000))]
+    pub description: String,
+    pub published_on: Option<NaiveDate>,
+}
+
+/// A 17 characters VIN, the letters I, O and Q are not used.
+pub fn validate_vin(vin: &str) -> Result<(), ValidationError> {
+    let valid = vin.len() == 17
+        && vin.chars().all(|c| {
+            c.is_ascii_alphanumeric() && !matches!(c.to_ascii_uppercase(), 'I' | 'O' | 'Q')
+        });
+
+    if !valid {
+        return Err(ValidationError::new("invalid_vin"));
+    }
+
+    Ok(())
+}
+
+/// A VIN range is a range of VIS (characters 10 to 17) for a WMI and VDS (characters 1 to 8),
+/// the check digit in between is not part of the range.
+fn validate_recall_ranges(recall: &NewRecall) -> Result<(), ValidationError> {
+    match (&recall.vin_from, &recall.vin_to) {
+        (Some(from), Some(to)) => {
+            let (from, to) = (from.to_uppercase(), to.to_uppercase());
+            if from.get(..8) != to.get(..8) {
+                return Err(ValidationError::new("vin_range_across_models"));
+            }
+            if from.get(9..) > to.get(9..) {
+                return Err(ValidationError::new("inverted_vin_range"));
+            }
+        }
+        (Some(_), None) | (None, Some(_)) => {
+            return Err(ValidationError::new("incomplete_vin_range"))
+        }
+        _ => {}
+    }
+
+    match (recall.year_from, recall.year_to) {
+        (Some(from), Some(to)) if from > to => {
+            return Err(ValidationError::new("inverted_year_range"))
+        }
+        (Some(_), None) | (None, Some(_)) => {
+            return Err(ValidationError::new("incomplete_year_range"))
+        }
+        _ => {}
+    }
+
+    if recall.vin_from.is_none() && recall.year_from.is_none() {
+        return Err(ValidationError::new("missing_range"));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecallFormat {
+    /// With a header line naming the fields of `NewRecall`.
+    Csv,
+    /// An array of `NewRecall`.
+    Json,
+}
+
+impl RecallFormat {
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type {
+            "text/csv" => Some(RecallFormat::Csv),
+            "application/json" => Some(RecallFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "csv" => Some(RecallFormat::Csv),
+            "json" => Some(RecallFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Reads and validates the recalls of a feed, the errors of all the records are returned together.
+pub fn parse_recalls(
+    format: RecallFormat,
+    content: &[u8],
+) -> Result<Vec<NewRecall>, ValidationErrors> {
+    let mut errors = ValidationErrors::new();
+
+    let records: Vec<std::result::Result<NewRecall, String>> = match format {
+        RecallFormat::Csv => csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(content)
+            .deserialize::<NewRecall>()
+            .map(|record| record.map_err(|err| err.to_string()))
+            .collect(),
+        RecallFormat::Json => match serde_json::from_slice::<Vec<NewRecall>>(content) {
+            Ok(recalls) => recalls.into_iter().map(Ok).collect(),
+            Err(err) => {
+                errors.add("records", invalid_recall(0, err.to_string()));
+                return Err(errors);
+            }
+        },
+    };
+
+    let mut recalls = Vec::with_capacity(records.len());
+    for (index, record) in records.into_iter().enumerate() {
+        match record.and_then(|recall| {
+            recall
+                .validate()
+                .map(|_| recall)
+                .map_err(|err| err.to_string())
+        }) {
+            Ok(recall) => recalls.push(recall),
+            Err(message) => errors.add("records", invalid_recall(index + 1, message)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    if recalls.is_empty() {
+        errors.add("records", ValidationError::new("empty"));
+        return Err(errors);
+    }
+
+    Ok(recalls)
+}
+
+/// `record` is the position of the record in the feed starting at 1, 0 when the whole feed is invalid.
+fn invalid_recall(record: usize, message: String) -> ValidationError {
+    let mut error = ValidationError::new("invalid_recall");
+    error.add_param("record".into(), &record);
+    error.message = Some(message.into());
+    error
+}
+
+/// Inserts the recalls or updates the campaigns already known, returns the number of saved recalls.
+pub async fn save_recalls(pg_pool: &PgPool, recalls: &[NewRecall]) -> Result<u64> {
+    let mut tx = pg_pool.begin().await?;
+
+    for recall in recalls {
+        sqlx::query(
+            r#"
+            INSERT INTO recalls(campaign, manufacturer, model, vin_from, vin_to, year_from, year_to, description, published_on)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (lower(manufacturer), campaign) DO UPDATE
+            SET manufacturer=EXCLUDED.manufacturer, model=EXCLUDED.model, vin_from=EXCLUDED.vin_from,
+                vin_to=EXCLUDED.vin_to, year_from=EXCLUDED.year_from, year_to=EXCLUDED.year_to,
+                description=EXCLUDED.description, published_on=EXCLUDED.published_on
+        "#,
+        )
+        .bind(recall.campaign.trim())
+        .bind(recall.manufacturer.trim())
+        .bind(recall.model.as_deref().map(str::trim))
+        .bind(recall.vin_from.as_deref().map(str::to_uppercase))
+        .bind(recall.vin_to.as_deref().map(str::to_uppercase))
+        .bind(recall.year_from)
+        .bind(recall.year_to)
+        .bind(recall.description.trim())
+        .bind(recall.published_on)
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(recalls.len() as u64)
+}
+
+/// Imports a CSV or JSON recall feed from the filesystem, the format is given by the extension.
+pub async fn import_recalls_file(pg_pool: &PgPool, path: &std::path::Path) -> Result<u64> {
+    let format = RecallFormat::from_path(path).ok_or_else(|| {
+        Error::UnsupportedMediaType("recall feeds must be .csv or .json files".into())
+    })?;
+    let content = tokio::fs::read(path).await?;
+
+    let recalls = parse_recalls(format, &content)?;
+
+    save_recalls(pg_pool, &recalls).await
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecallImport {
+    pub imported: u64,
+}
+
+/// Imports a recall feed sent as `text/csv` or `application/json`, the feed is authenticated
+/// by the `RECALL_IMPORT_TOKEN` bearer token and the import is refused when it is not set.
+pub async fn import_recalls(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<RecallImport>> {
+    check_bearer_token(&headers, "RECALL_IMPORT_TOKEN")?;
+
+    let format = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .and_then(|value| RecallFormat::from_content_type(&value.trim().to_lowercase()))
+        .ok_or_else(|| {
+            Error::UnsupportedMediaType("content type must be text/csv or application/json".into())
+        })?;
+
+    let recalls = parse_recalls(format, &body)?;
+    let imported = save_recalls(&state.pg_pool, &recalls).await?;
+
+    Ok(Json(RecallImport { imported }))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Recall {
+    pub id: Uuid,
+    pub campaign: String,
+    pub manufacturer: String,
+    pub model: Option<String>,
+    pub vin_from: Option<String>,
+    pub vin_to: Option<String>,
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+    pub description: String,
+    pub published_on: Option<NaiveDate>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct CarRecall {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub recall: Recall,
+    /// The recall is open until the repair is recorded.
+    pub completed_on: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RecallCompletion {
+    /// Defaults to today.
+    pub completed_on: Option<NaiveDate>,
+}
+
+/// The recalls matching the car, open ones first.
+pub async fn get_car_recalls(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<CarRecall>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let recalls = sqlx::query_as::<_, CarRecall>(
+        r#"
+        SELECT recall.*, completion.completed_on
+        FROM car
+        JOIN recalls recall ON recall_matches_car(recall, car)
+        LEFT JOIN recall_completions completion ON completion.car_id=car.id AND completion.recall_id=recall.id
+        WHERE car.id=$1
+        ORDER BY completion.completed_on DESC NULLS FIRST, recall.published_on DESC NULLS LAST
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(recalls))
+}
+
+/// Records that the recall repair was done on the car, which closes the recall.
+pub async fn complete_car_recall(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, recall_id)): Path<(Uuid, Uuid)>,
+    Json(completion): Json<RecallCompletion>,
+) -> Result<Json<CarRecall>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::Edit).await?;
+
+    let recall = sqlx::query_as::<_, Recall>(
+        r#"
+        SELECT recall.*
+        FROM car
+        JOIN recalls recall ON recall_matches_car(recall, car)
+        WHERE car.id=$1 AND recall.id=$2
+    "#,
+    )
+    .bind(car_id)
+    .bind(recall_id)
+    .fetch_optional(&state.pg_pool)
+    .await?
+    .ok_or_else(|| Error::NotFound("recall not found for this car".into()))?;
+
+    let (completed_on,) = sqlx::query_as::<_, (NaiveDate,)>(
+        r#"
+        INSERT INTO recall_completions(car_id, recall_id, completed_on)
+        VALUES ($1, $2, COALESCE($3, current_date))
+        ON CONFLICT DO NOTHING
+        RETURNING completed_on
+    "#,
+    )
+    .bind(car_id)
+    .bind(recall.id)
+    .bind(completion.completed_on)
+    .fetch_optional(&state.pg_pool)
+    .await?
+    .ok_or_else(|| Error::Conflict("the recall is already completed".into()))?;
+
+    Ok(Json(CarRecall {
+        recall,
+        completed_on: Some(completed_on),
+    }))
+}
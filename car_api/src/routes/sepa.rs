@@ -0,0 +1,567 @@
+use super::{
+    authenticate::{check_bearer_token, User},
+    bank_details::{get_user_bank_details, lock_user_bank_details},
+    AppState,
+};
+use crate::{
+    encrypt::decrypt_all,
+    errors::Error,
+    sepa::{
+        pain001_document, pain008_document, Company, CreditTransfer, DirectDebit, SequenceType,
+        MAX_REMITTANCE_INFORMATION_LENGTH,
+    },
+};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::PgExecutor;
+use validator::{Validate, ValidationError};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewMandate {
+    #[validate(custom = "validate_signed_on")]
+    pub signed_on: NaiveDate,
+}
+
+fn validate_signed_on(signed_on: &NaiveDate) -> Result<(), ValidationError> {
+    if *signed_on > chrono::Utc::now().date_naive() {
+        return Err(ValidationError::new("signed_in_the_future"));
+    }
+
+    Ok(())
+}
+
+/// Authorization given by the user to collect payments from a bank account.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Mandate {
+    pub id: Uuid,
+    pub bank_details_id: Uuid,
+    /// Unique mandate reference sent with every collection.
+    pub reference: String,
+    pub signed_on: NaiveDate,
+    /// The sequence type of the next collection.
+    pub sequence_type: SequenceType,
+    /// No payment is collected once the mandate is revoked.
+    pub revoked_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Records a mandate signed by the user for one of their bank accounts.
+pub async fn create_mandate(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(bank_details_id): Path<Uuid>,
+    Json(new_mandate): Json<NewMandate>,
+) -> Result<(StatusCode, Json<Mandate>)> {
+    new_mandate.validate()?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    // the bank account cannot be removed while the mandate is created
+    lock_user_bank_details(&mut tx, user.id).await?;
+
+    let bank_details = get_user_bank_details(&mut tx, &user, &bank_details_id).await?;
+
+    // 33 characters, the references are limited to 35
+    let reference = format!("M{}", Uuid::new_v4().simple()).to_uppercase();
+
+    let mandate = sqlx::query_as::<_, Mandate>(
+        r#"
+        INSERT INTO sepa_mandates(bank_details_id, reference, signed_on)
+        VALUES ($1, $2, $3)
+        RETURNING *
+    "#,
+    )
+    .bind(bank_details.id)
+    .bind(reference)
+    .bind(new_mandate.signed_on)
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(mandate)))
+}
+
+/// The mandates of all the bank accounts of the user, the latest first.
+pub async fn get_mandates(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Mandate>>> {
+    let mandates = sqlx::query_as::<_, Mandate>(
+        r#"
+        SELECT mandate.*
+        FROM sepa_mandates mandate
+        JOIN bank_details bank ON bank.id=mandate.bank_details_id
+        WHERE bank.user_id=$1
+        ORDER BY mandate.created_at DESC
+    "#,
+    )
+    .bind(user.id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(mandates))
+}
+
+/// Revokes the mandate, its pending collections are not exported anymore.
+pub async fn revoke_mandate(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(mandate_id): Path<Uuid>,
+) -> Result<Json<Mandate>> {
+    let mandate = get_user_mandate(&state.pg_pool, &user, &mandate_id).await?;
+    if mandate.revoked_at.is_some() {
+        return Err(Error::Conflict("the mandate is already revoked".into()));
+    }
+
+    let mandate = sqlx::query_as::<_, Mandate>(
+        r#"
+        UPDATE sepa_mandates
+        SET revoked_at=current_timestamp
+        WHERE id=$1 AND revoked_at IS NULL
+        RETURNING *
+    "#,
+    )
+    .bind(mandate.id)
+    .fetch_optional(&state.pg_pool)
+    .await?
+    .ok_or_else(|| Error::Conflict("the mandate is already revoked".into()))?;
+
+    Ok(Json(mandate))
+}
+
+async fn get_user_mandate<'c, E>(executor: E, user: &User, mandate_id: &Uuid) -> Result<Mandate>
+where
+    E: PgExecutor<'c>,
+{
+    sqlx::query_as::<_, Mandate>(
+        r#"
+        SELECT mandate.*
+        FROM sepa_mandates mandate
+        JOIN bank_details bank ON bank.id=mandate.bank_details_id
+        WHERE mandate.id=$1 AND bank.user_id=$2
+    "#,
+    )
+    .bind(mandate_id)
+    .bind(user.id)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| Error::NotFound("mandate not found".into()))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewCollection {
+    pub mandate_id: Uuid,
+    #[validate(range(min = 1))]
+    pub amount_cents: i64,
+    /// Shown to the user on their bank statement.
+    #[validate(length(min = 1, max = "MAX_REMITTANCE_INFORMATION_LENGTH"))]
+    pub remittance_information: String,
+    /// Requested collection date.
+    #[validate(custom = "validate_due_on")]
+    pub due_on: NaiveDate,
+}
+
+fn validate_due_on(due_on: &NaiveDate) -> Result<(), ValidationError> {
+    if *due_on < chrono::Utc::now().date_naive() {
+        return Err(ValidationError::new("due_in_the_past"));
+    }
+
+    Ok(())
+}
+
+/// A payment to collect under a mandate, in euro cents.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Collection {
+    pub id: Uuid,
+    pub mandate_id: Uuid,
+    pub amount_cents: i64,
+    pub remittance_information: String,
+    pub due_on: NaiveDate,
+    /// Set once the collection is exported.
+    pub batch_id: Option<Uuid>,
+    pub sequence_type: Option<SequenceType>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Schedules a collection, the endpoint is authenticated by the `SEPA_EXPORT_TOKEN` bearer token.
+pub async fn create_collection(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(new_collection): Json<NewCollection>,
+) -> Result<(StatusCode, Json<Collection>)> {
+    check_bearer_token(&headers, "SEPA_EXPORT_TOKEN")?;
+    new_collection.validate()?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    let (revoked,): (bool,) = sqlx::query_as(
+        r#"
+        SELECT revoked_at IS NOT NULL
+        FROM sepa_mandates
+        WHERE id=$1
+        FOR UPDATE
+    "#,
+    )
+    .bind(new_collection.mandate_id)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| Error::NotFound("mandate not found".into()))?;
+    if revoked {
+        return Err(Error::Conflict("the mandate is revoked".into()));
+    }
+
+    let collection = sqlx::query_as::<_, Collection>(
+        r#"
+        INSERT INTO sepa_collections(mandate_id, amount_cents, remittance_information, due_on)
+        VALUES ($1, $2, $3, $4)
+        RETURNING *
+    "#,
+    )
+    .bind(new_collection.mandate_id)
+    .bind(new_collection.amount_cents)
+    .bind(&new_collection.remittance_information)
+    .bind(new_collection.due_on)
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(collection)))
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct PendingCollection {
+    id: Uuid,
+    amount_cents: i64,
+    remittance_information: String,
+    due_on: NaiveDate,
+    mandate_id: Uuid,
+    mandate_reference: String,
+    mandate_signed_on: NaiveDate,
+    mandate_sequence_type: SequenceType,
+    account_holder: String,
+    iban: String,
+    bic: Option<String>,
+}
+
+/// Exports the pending collections of the active mandates as a pain.008.001.02 message to send
+/// to the bank. The first collection of a mandate is sent as `FRST`, the following ones as `RCUR`.
+pub async fn create_direct_debit_batch(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    check_bearer_token(&headers, "SEPA_EXPORT_TOKEN")?;
+    let creditor = Company::from_env()
+        .ok_or_else(|| Error::MissingConfiguration("the SEPA company is not configured".into()))?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    let collections = sqlx::query_as::<_, PendingCollection>(
+        r#"
+        SELECT collection.id, collection.amount_cents, collection.remittance_information, collection.due_on,
+            mandate.id AS mandate_id, mandate.reference AS mandate_reference,
+            mandate.signed_on AS mandate_signed_on, mandate.sequence_type AS mandate_sequence_type,
+            bank.account_holder, bank.iban, bank.bic
+        FROM sepa_collections collection
+        JOIN sepa_mandates mandate ON mandate.id=collection.mandate_id
+        JOIN bank_details bank ON bank.id=mandate.bank_details_id
+        WHERE collection.batch_id IS NULL AND mandate.revoked_at IS NULL
+        ORDER BY mandate.id, collection.due_on, collection.created_at
+        FOR UPDATE OF collection, mandate
+    "#,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+    if collections.is_empty() {
+        return Err(Error::NotFound("no pending collection".into()));
+    }
+
+    // the IBANs are only decrypted to write the message
+    let ibans = decrypt_all(
+        collections
+            .iter()
+            .map(|collection| collection.iban.clone())
+            .collect(),
+    )
+    .await?;
+
+    let mut debits = Vec::with_capacity(collections.len());
+    let mut first_collections = Vec::new();
+    for ((index, collection), debtor_iban) in collections.iter().enumerate().zip(ibans) {
+        let first_of_mandate =
+            index == 0 || collections[index - 1].mandate_id != collection.mandate_id;
+        let sequence_type = if first_of_mandate {
+            collection.mandate_sequence_type
+        } else {
+            SequenceType::Rcur
+        };
+        if sequence_type == SequenceType::Frst {
+            first_collections.push(collection.id);
+        }
+
+        debits.push(DirectDebit {
+            end_to_end_id: collection.id.simple().to_string(),
+            amount_cents: collection.amount_cents,
+            collection_date: collection.due_on,
+            sequence_type,
+            mandate_reference: collection.mandate_reference.clone(),
+            mandate_signed_on: collection.mandate_signed_on,
+            debtor_name: collection.account_holder.clone(),
+            debtor_iban,
+            debtor_bic: collection.bic.clone(),
+            remittance_information: collection.remittance_information.clone(),
+        });
+    }
+
+    let (message_id, batch_id, created_at) = create_batch(&mut tx).await?;
+
+    let collection_ids: Vec<_> = collections.iter().map(|collection| collection.id).collect();
+    sqlx::query(
+        r#"
+        UPDATE sepa_collections
+        SET batch_id=$1,
+            sequence_type=CASE WHEN id = ANY($3) THEN 'frst' ELSE 'rcur' END::sepa_sequence_type
+        WHERE id = ANY($2)
+    "#,
+    )
+    .bind(batch_id)
+    .bind(&collection_ids)
+    .bind(&first_collections)
+    .execute(&mut tx)
+    .await?;
+
+    let mandate_ids: Vec<_> = collections
+        .iter()
+        .map(|collection| collection.mandate_id)
+        .collect();
+    sqlx::query(
+        r#"
+        UPDATE sepa_mandates
+        SET sequence_type='rcur'
+        WHERE id = ANY($1)
+    "#,
+    )
+    .bind(&mandate_ids)
+    .execute(&mut tx)
+    .await?;
+
+    let document = pain008_document(&message_id, created_at, &creditor, &debits);
+
+    tx.commit().await?;
+
+    Ok(xml_attachment(&message_id, document))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewPayout {
+    pub user_id: Uuid,
+    /// The primary account of the user when missing.
+    pub bank_details_id: Option<Uuid>,
+    #[validate(range(min = 1))]
+    pub amount_cents: i64,
+    /// Shown to the user on their bank statement.
+    #[validate(length(min = 1, max = "MAX_REMITTANCE_INFORMATION_LENGTH"))]
+    pub remittance_information: String,
+    /// Requested execution date.
+    #[validate(custom = "validate_due_on")]
+    pub execution_date: NaiveDate,
+}
+
+/// Expenses reimbursed to a bank account of the user, in euro cents.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Payout {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub bank_details_id: Uuid,
+    pub amount_cents: i64,
+    pub remittance_information: String,
+    pub execution_date: NaiveDate,
+    /// Set once the payout is exported.
+    pub batch_id: Option<Uuid>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Schedules a payout, the endpoint is authenticated by the `SEPA_EXPORT_TOKEN` bearer token.
+pub async fn create_payout(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(new_payout): Json<NewPayout>,
+) -> Result<(StatusCode, Json<Payout>)> {
+    check_bearer_token(&headers, "SEPA_EXPORT_TOKEN")?;
+    new_payout.validate()?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    // the bank account cannot be removed while the payout is created
+    lock_user_bank_details(&mut tx, new_payout.user_id).await?;
+
+    let payout = sqlx::query_as::<_, Payout>(
+        r#"
+        INSERT INTO sepa_payouts(user_id, bank_details_id, amount_cents, remittance_information, execution_date)
+        SELECT $1, id, $3, $4, $5
+        FROM bank_details
+        WHERE user_id=$1 AND deleted_at IS NULL AND (id=$2 OR ($2 IS NULL AND is_primary))
+        RETURNING *
+    "#,
+    )
+    .bind(new_payout.user_id)
+    .bind(new_payout.bank_details_id)
+    .bind(new_payout.amount_cents)
+    .bind(&new_payout.remittance_information)
+    .bind(new_payout.execution_date)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| Error::NotFound("bank details not found".into()))?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(payout)))
+}
+
+/// The payouts of the user, the latest first.
+pub async fn get_payouts(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Payout>>> {
+    let payouts = sqlx::query_as::<_, Payout>(
+        r#"
+        SELECT *
+        FROM sepa_payouts
+        WHERE user_id=$1
+        ORDER BY execution_date DESC, created_at DESC
+    "#,
+    )
+    .bind(user.id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(payouts))
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct PendingPayout {
+    id: Uuid,
+    amount_cents: i64,
+    remittance_information: String,
+    execution_date: NaiveDate,
+    account_holder: String,
+    iban: String,
+    bic: Option<String>,
+}
+
+/// Exports the pending payouts as a pain.001.001.03 message to send to the bank.
+pub async fn create_credit_transfer_batch(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    check_bearer_token(&headers, "SEPA_EXPORT_TOKEN")?;
+    let debtor = Company::from_env()
+        .ok_or_else(|| Error::MissingConfiguration("the SEPA company is not configured".into()))?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    let payouts = sqlx::query_as::<_, PendingPayout>(
+        r#"
+        SELECT payout.id, payout.amount_cents, payout.remittance_information, payout.execution_date,
+            bank.account_holder, bank.iban, bank.bic
+        FROM sepa_payouts payout
+        JOIN bank_details bank ON bank.id=payout.bank_details_id
+        WHERE payout.batch_id IS NULL
+        ORDER BY payout.execution_date, payout.created_at
+        FOR UPDATE OF payout, bank
+    "#,
+    )
+    .fetch_all(&mut tx)
+    .await?;
+    if payouts.is_empty() {
+        return Err(Error::NotFound("no pending payout".into()));
+    }
+
+    // the IBANs are only decrypted to write the message
+    let ibans = decrypt_all(payouts.iter().map(|payout| payout.iban.clone()).collect()).await?;
+
+    let transfers: Vec<_> = payouts
+        .iter()
+        .zip(ibans)
+        .map(|(payout, creditor_iban)| CreditTransfer {
+            end_to_end_id: payout.id.simple().to_string(),
+            amount_cents: payout.amount_cents,
+            execution_date: payout.execution_date,
+            creditor_name: payout.account_holder.clone(),
+            creditor_iban,
+            creditor_bic: payout.bic.clone(),
+            remittance_information: payout.remittance_information.clone(),
+        })
+        .collect();
+
+    let (message_id, batch_id, created_at) = create_batch(&mut tx).await?;
+
+    let payout_ids: Vec<_> = payouts.iter().map(|payout| payout.id).collect();
+    sqlx::query(
+        r#"
+        UPDATE sepa_payouts
+        SET batch_id=$1
+        WHERE id = ANY($2)
+    "#,
+    )
+    .bind(batch_id)
+    .bind(&payout_ids)
+    .execute(&mut tx)
+    .await?;
+
+    let document = pain001_document(&message_id, created_at, &debtor, &transfers);
+
+    tx.commit().await?;
+
+    Ok(xml_attachment(&message_id, document))
+}
+
+/// Records a new message, returns its identifier with the id and creation time of the batch.
+async fn create_batch<'c, E>(executor: E) -> Result<(String, Uuid, chrono::NaiveDateTime)>
+where
+    E: PgExecutor<'c>,
+{
+    let message_id = Uuid::new_v4().simple().to_string();
+    let (batch_id, created_at): (Uuid, chrono::NaiveDateTime) = sqlx::query_as(
+        r#"
+        INSERT INTO sepa_batches(message_id)
+        VALUES ($1)
+        RETURNING id, created_at
+    "#,
+    )
+    .bind(&message_id)
+    .fetch_one(executor)
+    .await?;
+
+    Ok((message_id, batch_id, created_at))
+}
+
+fn xml_attachment(message_id: &str, document: String) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "application/xml".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.xml\"", message_id),
+            ),
+        ],
+        document,
+    )
+        .into_response()
+}
@@ -0,0 +1,522 @@
+use super::{
+    authenticate::User,
+    car::check_car_owner,
+    document::Document,
+    photo::{photo_storage_key, Photo},
+    AppState,
+};
+use crate::{errors::Error, photo::PhotoVariant, storage::delete_keys};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::{Postgres, Transaction};
+use tracing::debug;
+use validator::Validate;
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "transfer_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum TransferStatus {
+    Pending,
+    Accepted,
+    Declined,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewTransfer {
+    /// Email of the account the car is transferred to.
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct Transfer {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub from_user_id: Uuid,
+    pub to_email: String,
+    pub to_user_id: Option<Uuid>,
+    /// Read-only copy of the car kept by the previous owner, set once accepted.
+    pub snapshot_car_id: Option<Uuid>,
+    pub status: TransferStatus,
+    pub responded_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Offers the car to another account, only the owner can transfer it and a single transfer can be pending.
+pub async fn create_car_transfer(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(new_transfer): Json<NewTransfer>,
+) -> Result<(StatusCode, Json<Transfer>)> {
+    new_transfer.validate()?;
+    check_car_owner(&state.pg_pool, &user, &car_id).await?;
+
+    let email = new_transfer.email.trim().to_lowercase();
+    if email == user.email.to_lowercase() {
+        return Err(Error::Conflict("cannot transfer a car to yourself".into()));
+    }
+
+    let transfer = sqlx::query_as::<_, Transfer>(
+        r#"
+        INSERT INTO car_transfers(car_id, from_user_id, to_email)
+        VALUES ($1, $2, $3)
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(user.id)
+    .bind(&email)
+    .fetch_one(&state.pg_pool)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(db_err)
+            if db_err.code().as_deref() == Some("23505")
+                && db_err.constraint() == Some("car_transfers_pending_idx") =>
+        {
+            debug!("{}", db_err.message());
+            Error::Conflict("a transfer is already pending for this car".into())
+        }
+        err => err.into(),
+    })?;
+
+    Ok((StatusCode::CREATED, Json(transfer)))
+}
+
+pub async fn get_car_transfers(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<Transfer>>> {
+    check_car_owner(&state.pg_pool, &user, &car_id).await?;
+
+    let transfers = sqlx::query_as::<_, Transfer>(
+        r#"
+        SELECT *
+        FROM car_transfers
+        WHERE car_id=$1
+        ORDER BY created_at DESC
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(transfers))
+}
+
+pub async fn cancel_car_transfer(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, transfer_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    check_car_owner(&state.pg_pool, &user, &car_id).await?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE car_transfers
+        SET status='cancelled', responded_at=current_timestamp
+        WHERE id=$1 AND car_id=$2 AND status='pending'
+    "#,
+    )
+    .bind(transfer_id)
+    .bind(car_id)
+    .execute(&state.pg_pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound("transfer not found".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Pending transfers sent to the email of the user.
+pub async fn get_my_transfers(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Transfer>>> {
+    let transfers = sqlx::query_as::<_, Transfer>(
+        r#"
+        SELECT *
+        FROM car_transfers
+        WHERE lower(to_email)=lower($1) AND status='pending'
+        ORDER BY created_at DESC
+    "#,
+    )
+    .bind(&user.email)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(transfers))
+}
+
+/// Makes the user the owner of the car with all its history, the previous owner keeps
+/// a read-only copy of the car and of its history up to now. The car members and the
+/// parking history are removed from the car.
+pub async fn accept_transfer(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(transfer_id): Path<Uuid>,
+) -> Result<Json<Transfer>> {
+    let mut tx = state.pg_pool.begin().await?;
+
+    let transfer = sqlx::query_as::<_, Transfer>(
+        r#"
+        SELECT *
+        FROM car_transfers
+        WHERE id=$1 AND lower(to_email)=lower($2) AND status='pending'
+        FOR UPDATE
+    "#,
+    )
+    .bind(transfer_id)
+    .bind(&user.email)
+    .fetch_optional(&mut tx)
+    .await?
+    .ok_or_else(|| Error::NotFound("transfer not found".into()))?;
+
+    // the car is locked until the transfer is done
+    let still_owned = sqlx::query_as::<_, (Uuid,)>(
+        r#"
+        SELECT id
+        FROM car
+        WHERE id=$1 AND user_id=$2 AND archived_at IS NULL
+        FOR UPDATE
+    "#,
+    )
+    .bind(transfer.car_id)
+    .bind(transfer.from_user_id)
+    .fetch_optional(&mut tx)
+    .await?;
+
+    if still_owned.is_none() {
+        return Err(Error::Conflict(
+            "the car is no longer owned by the sender of the transfer".into(),
+        ));
+    }
+
+    // the copies of the stored files are removed if the transfer is not committed
+    let mut copied_keys = Vec::new();
+    let result = complete_transfer(&state, tx, &transfer, &user, &mut copied_keys).await;
+    if result.is_err() {
+        delete_keys(state.storage.as_ref(), &copied_keys).await;
+    }
+
+    result.map(Json)
+}
+
+async fn complete_transfer(
+    state: &AppState,
+    mut tx: Transaction<'_, Postgres>,
+    transfer: &Transfer,
+    user: &User,
+    copied_keys: &mut Vec<String>,
+) -> Result<Transfer> {
+    let snapshot_car_id = create_snapshot(state, &mut tx, transfer, copied_keys).await?;
+
+    sqlx::query(
+        r#"
+        UPDATE car
+        SET user_id=$2
+        WHERE id=$1
+    "#,
+    )
+    .bind(transfer.car_id)
+    .bind(user.id)
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM car_members
+        WHERE car_id=$1
+    "#,
+    )
+    .bind(transfer.car_id)
+    .execute(&mut tx)
+    .await?;
+
+    // where the car was parked is only kept in the snapshot of the previous owner
+    sqlx::query(
+        r#"
+        DELETE FROM car_locations
+        WHERE car_id=$1
+    "#,
+    )
+    .bind(transfer.car_id)
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE car_invitations
+        SET status='revoked', responded_at=current_timestamp
+        WHERE car_id=$1 AND status='pending'
+    "#,
+    )
+    .bind(transfer.car_id)
+    .execute(&mut tx)
+    .await?;
+
+    let transfer = sqlx::query_as::<_, Transfer>(
+        r#"
+        UPDATE car_transfers
+        SET status='accepted', responded_at=current_timestamp, to_user_id=$2, snapshot_car_id=$3
+        WHERE id=$1
+        RETURNING *
+    "#,
+    )
+    .bind(transfer.id)
+    .bind(user.id)
+    .bind(snapshot_car_id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(transfer)
+}
+
+/// Copies the car and its history to an archived car owned by the previous owner, the keys
+/// of the copied files are added to `copied_keys`.
+async fn create_snapshot(
+    state: &AppState,
+    tx: &mut Transaction<'_, Postgres>,
+    transfer: &Transfer,
+    copied_keys: &mut Vec<String>,
+) -> Result<Uuid> {
+    let (snapshot_car_id,) = sqlx::query_as::<_, (Uuid,)>(
+        r#"
+        INSERT INTO car(user_id, plate, model, manufacturer, vin, model_year, created_at, archived_at)
+        SELECT user_id, plate, model, manufacturer, vin, model_year, created_at, current_timestamp
+        FROM car
+        WHERE id=$1
+        RETURNING id
+    "#,
+    )
+    .bind(transfer.car_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let history = [
+        r#"
+        INSERT INTO odometer_readings(car_id, mileage, meter_replacement, recorded_at, created_at)
+        SELECT $2, mileage, meter_replacement, recorded_at, created_at
+        FROM odometer_readings
+        WHERE car_id=$1
+    "#,
+        r#"
+        INSERT INTO maintenance_records(car_id, service_type, performed_on, mileage, cost_cents, garage, notes, created_at)
+        SELECT $2, service_type, performed_on, mileage, cost_cents, garage, notes, created_at
+        FROM maintenance_records
+        WHERE car_id=$1
+    "#,
+        r#"
+        INSERT INTO service_intervals(car_id, service_type, interval_months, interval_km, created_at)
+        SELECT $2, service_type, interval_months, interval_km, created_at
+        FROM service_intervals
+        WHERE car_id=$1
+    "#,
+        r#"
+        INSERT INTO fuel_entries(car_id, energy_type, quantity, price_cents, odometer, full_tank, filled_at, created_at)
+        SELECT $2, energy_type, quantity, price_cents, odometer, full_tank, filled_at, created_at
+        FROM fuel_entries
+        WHERE car_id=$1
+    "#,
+        r#"
+        INSERT INTO insurance_policies(car_id, insurer, policy_number, coverage_type, starts_on, ends_on, premium_cents, created_at)
+        SELECT $2, insurer, policy_number, coverage_type, starts_on, ends_on, premium_cents, created_at
+        FROM insurance_policies
+        WHERE car_id=$1
+    "#,
+        r#"
+        INSERT INTO charges(car_id, kind, occurred_at, location, amount_cents, currency, reference, due_on, paid_at, driver_id, created_at)
+        SELECT $2, kind, occurred_at, location, amount_cents, currency, reference, due_on, paid_at, driver_id, created_at
+        FROM charges
+        WHERE car_id=$1
+    "#,
+        r#"
+        INSERT INTO car_ev_specs(car_id, battery_capacity_kwh, connector_types, max_ac_power_kw, max_dc_power_kw, wltp_range_km, created_at)
+        SELECT $2, battery_capacity_kwh, connector_types, max_ac_power_kw, max_dc_power_kw, wltp_range_km, created_at
+        FROM car_ev_specs
+        WHERE car_id=$1
+    "#,
+        r#"
+        INSERT INTO soc_readings(car_id, recorded_at, soc_percent, energy_kwh, created_at)
+        SELECT $2, recorded_at, soc_percent, energy_kwh, created_at
+        FROM soc_readings
+        WHERE car_id=$1
+    "#,
+        r#"
+        INSERT INTO recall_completions(car_id, recall_id, completed_on, created_at)
+        SELECT $2, recall_id, completed_on, created_at
+        FROM recall_completions
+        WHERE car_id=$1
+    "#,
+        r#"
+        WITH copies AS (
+            SELECT id AS trip_id, gen_random_uuid() AS copy_id
+            FROM trips
+            WHERE car_id=$1
+        ), copied_trips AS (
+            INSERT INTO trips(id, car_id, name, started_at, ended_at, start_odometer, end_odometer, distance_m, created_at)
+            SELECT copies.copy_id, $2, name, started_at, ended_at, start_odometer, end_odometer, distance_m, created_at
+            FROM trips
+            JOIN copies ON copies.trip_id = trips.id
+        )
+        INSERT INTO trip_points(trip_id, seq, latitude, longitude, elevation, recorded_at)
+        SELECT copies.copy_id, seq, latitude, longitude, elevation, recorded_at
+        FROM trip_points
+        JOIN copies ON copies.trip_id = trip_points.trip_id
+    "#,
+    ];
+
+    for query in history {
+        sqlx::query(query)
+            .bind(transfer.car_id)
+            .bind(snapshot_car_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let documents = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT *
+        FROM documents
+        WHERE car_id=$1
+    "#,
+    )
+    .bind(transfer.car_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut document_ids = Vec::with_capacity(documents.len());
+    let mut copy_ids = Vec::with_capacity(documents.len());
+
+    // the stored content is already encrypted, it is copied as is
+    for document in documents {
+        let id = Uuid::new_v4();
+        document_ids.push(document.id);
+        copy_ids.push(id);
+        let storage_key = format!("documents/{}/{}", snapshot_car_id, id);
+
+        sqlx::query(
+            r#"
+            INSERT INTO documents(id, car_id, kind, file_name, content_type, size_bytes, storage_key, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        )
+        .bind(id)
+        .bind(snapshot_car_id)
+        .bind(document.kind)
+        .bind(&document.file_name)
+        .bind(&document.content_type)
+        .bind(document.size_bytes)
+        .bind(&storage_key)
+        .bind(document.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        let content = state.storage.get(&document.storage_key).await?;
+        copied_keys.push(storage_key.clone());
+        state.storage.put(&storage_key, content).await?;
+    }
+
+    // the photos of the parking spots point to the copies of the documents
+    sqlx::query(
+        r#"
+        INSERT INTO car_locations(car_id, latitude, longitude, level, spot_note, photo_document_id, meter_expires_at, parked_at, created_at)
+        SELECT $2, latitude, longitude, level, spot_note, copies.copy_id, meter_expires_at, parked_at, created_at
+        FROM car_locations
+        LEFT JOIN UNNEST($3::uuid[], $4::uuid[]) AS copies(document_id, copy_id)
+            ON copies.document_id = car_locations.photo_document_id
+        WHERE car_id=$1
+    "#,
+    )
+    .bind(transfer.car_id)
+    .bind(snapshot_car_id)
+    .bind(&document_ids)
+    .bind(&copy_ids)
+    .execute(&mut *tx)
+    .await?;
+
+    let photos = sqlx::query_as::<_, Photo>(
+        r#"
+        SELECT *
+        FROM car_photos
+        WHERE car_id=$1
+    "#,
+    )
+    .bind(transfer.car_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for photo in photos {
+        let id = Uuid::new_v4();
+
+        sqlx::query(
+            r#"
+            INSERT INTO car_photos(id, car_id, content_type, width, height, size_bytes, is_primary, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        )
+        .bind(id)
+        .bind(snapshot_car_id)
+        .bind(&photo.content_type)
+        .bind(photo.width)
+        .bind(photo.height)
+        .bind(photo.size_bytes)
+        .bind(photo.is_primary)
+        .bind(photo.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        for variant in PhotoVariant::ALL {
+            let content = state
+                .storage
+                .get(&photo_storage_key(&photo.car_id, &photo.id, variant))
+                .await?;
+            let storage_key = photo_storage_key(&snapshot_car_id, &id, variant);
+            copied_keys.push(storage_key.clone());
+            state.storage.put(&storage_key, content).await?;
+        }
+    }
+
+    Ok(snapshot_car_id)
+}
+
+pub async fn decline_transfer(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(transfer_id): Path<Uuid>,
+) -> Result<StatusCode> {
+    let result = sqlx::query(
+        r#"
+        UPDATE car_transfers
+        SET status='declined', responded_at=current_timestamp
+        WHERE id=$1 AND lower(to_email)=lower($2) AND status='pending'
+    "#,
+    )
+    .bind(transfer_id)
+    .bind(&user.email)
+    .execute(&state.pg_pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::NotFound("transfer not found".into()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,398 @@
+use super::{
+    authenticate::User,
+    car::{check_car_permission, lock_car, Permission},
+    geofence::detect_geofence_events,
+    AppState,
+};
+use crate::{
+    errors::Error,
+    geo::{parse_gpx, track_distance, write_gpx, write_kml, Track, TrackPoint},
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::uuid::Uuid;
+use sqlx::{PgExecutor, Postgres, QueryBuilder, Transaction};
+use validator::{Validate, ValidationError, ValidationErrors};
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+use std::sync::Arc;
+
+/// Maximum size of an uploaded GPX file.
+pub const MAX_GPX_SIZE: usize = 20 * 1024 * 1024;
+
+const GPX_CONTENT_TYPES: [&str; 3] = ["application/gpx+xml", "application/xml", "text/xml"];
+
+/// Points inserted by a single statement, keeps the bind parameters under the postgres limit.
+const POINTS_PER_INSERT: usize = 1000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+#[validate(schema(function = "validate_trip"))]
+pub struct NewTrip {
+    #[validate(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    pub started_at: chrono::NaiveDateTime,
+    pub ended_at: Option<chrono::NaiveDateTime>,
+    #[validate(range(min = 0))]
+    pub start_odometer: Option<i64>,
+    #[validate(range(min = 0))]
+    pub end_odometer: Option<i64>,
+    /// More points can be sent later in batches.
+    #[serde(default)]
+    #[validate]
+    pub points: Vec<TrackPoint>,
+}
+
+fn validate_trip(trip: &NewTrip) -> Result<(), ValidationError> {
+    if matches!(trip.ended_at, Some(ended_at) if ended_at < trip.started_at) {
+        return Err(ValidationError::new("ended_at_before_started_at"));
+    }
+
+    if let (Some(start), Some(end)) = (trip.start_odometer, trip.end_odometer) {
+        if end < start {
+            return Err(ValidationError::new("end_odometer_below_start_odometer"));
+        }
+    }
+
+    Ok(())
+}
+
+/// A batch of points appended to the track of a trip.
+#[derive(Serialize, Deserialize, Debug, Clone, Validate)]
+pub struct NewTripPoints {
+    #[validate(length(min = 1))]
+    #[validate]
+    pub points: Vec<TrackPoint>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct Trip {
+    pub id: Uuid,
+    pub car_id: Uuid,
+    pub name: Option<String>,
+    pub started_at: chrono::NaiveDateTime,
+    pub ended_at: Option<chrono::NaiveDateTime>,
+    pub start_odometer: Option<i64>,
+    pub end_odometer: Option<i64>,
+    /// Length of the GPS track in metres.
+    pub distance_m: f64,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TripDetails {
+    #[serde(flatten)]
+    pub trip: Trip,
+    pub points: Vec<TrackPoint>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackFormat {
+    Gpx,
+    Kml,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExportQuery {
+    pub format: TrackFormat,
+}
+
+pub async fn create_trip(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    Json(new_trip): Json<NewTrip>,
+) -> Result<(StatusCode, Json<Trip>)> {
+    new_trip.validate()?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_car(&mut tx, &user, &car_id, Permission::LogTrips).await?;
+
+    let trip = sqlx::query_as::<_, Trip>(
+        r#"
+        INSERT INTO trips(car_id, name, started_at, ended_at, start_odometer, end_odometer)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(new_trip.name.as_deref().map(str::trim))
+    .bind(new_trip.started_at)
+    .bind(new_trip.ended_at)
+    .bind(new_trip.start_odometer)
+    .bind(new_trip.end_odometer)
+    .fetch_one(&mut tx)
+    .await?;
+
+    let trip = add_points(&mut tx, trip, &new_trip.points).await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(trip)))
+}
+
+/// Creates a trip from the tracks of a GPX file sent as the request body, the trip
+/// starts and ends at the earliest and latest times of its points.
+pub async fn import_gpx_trip(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<Trip>)> {
+    check_gpx_content_type(&headers)?;
+    let track = std::str::from_utf8(&body)
+        .map_err(|_| ValidationError::new("invalid_utf8"))
+        .and_then(parse_gpx)
+        .map_err(|error| {
+            let mut errors = ValidationErrors::new();
+            errors.add("body", error);
+            errors
+        })?;
+
+    // the points are not always in the order of their times
+    let times = track.points.iter().filter_map(|point| point.recorded_at);
+    let started_at = times.clone().min();
+    let ended_at = times.max();
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_car(&mut tx, &user, &car_id, Permission::LogTrips).await?;
+
+    let trip = sqlx::query_as::<_, Trip>(
+        r#"
+        INSERT INTO trips(car_id, name, started_at, ended_at)
+        VALUES ($1, $2, COALESCE($3, current_timestamp), $4)
+        RETURNING *
+    "#,
+    )
+    .bind(car_id)
+    .bind(&track.name)
+    .bind(started_at)
+    .bind(ended_at)
+    .fetch_one(&mut tx)
+    .await?;
+
+    let trip = add_points(&mut tx, trip, &track.points).await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(trip)))
+}
+
+fn check_gpx_content_type(headers: &HeaderMap) -> Result<()> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_lowercase())
+        .unwrap_or_default();
+
+    if !GPX_CONTENT_TYPES.contains(&content_type.as_str()) {
+        return Err(Error::UnsupportedMediaType(format!(
+            "content type must be one of {}",
+            GPX_CONTENT_TYPES.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+/// Appends a batch of points to the end of the track of the trip.
+pub async fn add_trip_points(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, trip_id)): Path<(Uuid, Uuid)>,
+    Json(new_points): Json<NewTripPoints>,
+) -> Result<Json<Trip>> {
+    new_points.validate()?;
+
+    let mut tx = state.pg_pool.begin().await?;
+
+    lock_car(&mut tx, &user, &car_id, Permission::LogTrips).await?;
+    let trip = get_car_trip(&mut tx, &car_id, &trip_id).await?;
+
+    let trip = add_points(&mut tx, trip, &new_points.points).await?;
+
+    tx.commit().await?;
+
+    Ok(Json(trip))
+}
+
+/// Inserts the points after the existing ones and updates the distance of the trip,
+/// the points are also checked against the geofences. The car has to be locked.
+async fn add_points(
+    tx: &mut Transaction<'_, Postgres>,
+    trip: Trip,
+    points: &[TrackPoint],
+) -> Result<Trip> {
+    if points.is_empty() {
+        return Ok(trip);
+    }
+
+    let (next_seq,) = sqlx::query_as::<_, (i32,)>(
+        r#"
+        SELECT COALESCE(MAX(seq) + 1, 0)
+        FROM trip_points
+        WHERE trip_id=$1
+    "#,
+    )
+    .bind(trip.id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    for (chunk_index, chunk) in points.chunks(POINTS_PER_INSERT).enumerate() {
+        let first_seq = next_seq + (chunk_index * POINTS_PER_INSERT) as i32;
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            "INSERT INTO trip_points(trip_id, seq, latitude, longitude, elevation, recorded_at) ",
+        );
+        query.push_values(chunk.iter().enumerate(), |mut row, (index, point)| {
+            row.push_bind(trip.id)
+                .push_bind(first_seq + index as i32)
+                .push_bind(point.latitude)
+                .push_bind(point.longitude)
+                .push_bind(point.elevation)
+                .push_bind(point.recorded_at);
+        });
+        query.build().execute(&mut *tx).await?;
+    }
+
+    detect_geofence_events(tx, &trip.car_id, points).await?;
+
+    let track = get_trip_points(&mut *tx, &trip.id).await?;
+
+    let trip = sqlx::query_as::<_, Trip>(
+        r#"
+        UPDATE trips
+        SET distance_m=$2
+        WHERE id=$1
+        RETURNING *
+    "#,
+    )
+    .bind(trip.id)
+    .bind(track_distance(&track))
+    .fetch_one(&mut *tx)
+    .await?;
+
+    Ok(trip)
+}
+
+pub async fn get_trips(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path(car_id): Path<Uuid>,
+) -> Result<Json<Vec<Trip>>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let trips = sqlx::query_as::<_, Trip>(
+        r#"
+        SELECT *
+        FROM trips
+        WHERE car_id=$1
+        ORDER BY started_at DESC
+    "#,
+    )
+    .bind(car_id)
+    .fetch_all(&state.pg_pool)
+    .await?;
+
+    Ok(Json(trips))
+}
+
+pub async fn get_trip(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, trip_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<TripDetails>> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let trip = get_car_trip(&state.pg_pool, &car_id, &trip_id).await?;
+    let points = get_trip_points(&state.pg_pool, &trip.id).await?;
+
+    Ok(Json(TripDetails { trip, points }))
+}
+
+/// Downloads the track of the trip as a GPX or KML file.
+pub async fn export_trip(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+    Path((car_id, trip_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response> {
+    check_car_permission(&state.pg_pool, &user, &car_id, Permission::View).await?;
+
+    let trip = get_car_trip(&state.pg_pool, &car_id, &trip_id).await?;
+    let track = Track {
+        name: trip.name,
+        points: get_trip_points(&state.pg_pool, &trip.id).await?,
+    };
+
+    let (content_type, extension, content) = match query.format {
+        TrackFormat::Gpx => ("application/gpx+xml", "gpx", write_gpx(&track)),
+        TrackFormat::Kml => (
+            "application/vnd.google-earth.kml+xml",
+            "kml",
+            write_kml(&track),
+        ),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"trip-{}.{}\"", trip.id, extension),
+            ),
+        ],
+        content,
+    )
+        .into_response())
+}
+
+async fn get_car_trip<'c, E>(executor: E, car_id: &Uuid, trip_id: &Uuid) -> Result<Trip>
+where
+    E: PgExecutor<'c>,
+{
+    sqlx::query_as::<_, Trip>(
+        r#"
+        SELECT *
+        FROM trips
+        WHERE id=$1 AND car_id=$2
+    "#,
+    )
+    .bind(trip_id)
+    .bind(car_id)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| Error::NotFound("trip not found".into()))
+}
+
+async fn get_trip_points<'c, E>(executor: E, trip_id: &Uuid) -> Result<Vec<TrackPoint>>
+where
+    E: PgExecutor<'c>,
+{
+    let points = sqlx::query_as::<_, TrackPoint>(
+        r#"
+        SELECT latitude, longitude, elevation, recorded_at
+        FROM trip_points
+        WHERE trip_id=$1
+        ORDER BY seq
+    "#,
+    )
+    .bind(trip_id)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(points)
+}
@@ -0,0 +1,305 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+const PAIN_001_NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:pain.001.001.03";
+
+const PAIN_008_NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:pain.008.001.02";
+
+/// The payment information identifiers are the message identifier followed by a counter,
+/// they are limited to 35 characters like the message identifier.
+const MAX_PAYMENT_PREFIX_LENGTH: usize = 28;
+
+/// Longest name of a party accepted by the schema.
+const MAX_NAME_LENGTH: usize = 70;
+
+/// Longest unstructured remittance information accepted by the schema.
+pub const MAX_REMITTANCE_INFORMATION_LENGTH: usize = 140;
+
+/// Place of a collection in the life of its mandate, the first collection of a mandate
+/// is announced as such to the debtor bank.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type,
+)]
+#[sqlx(type_name = "sepa_sequence_type", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum SequenceType {
+    Frst,
+    Rcur,
+}
+
+impl SequenceType {
+    pub fn as_code(&self) -> &'static str {
+        match self {
+            SequenceType::Frst => "FRST",
+            SequenceType::Rcur => "RCUR",
+        }
+    }
+}
+
+/// The company collecting the direct debits and paying the payouts, it is configured with the
+/// `SEPA_COMPANY_NAME`, `SEPA_COMPANY_IBAN`, `SEPA_COMPANY_BIC` and `SEPA_CREDITOR_ID` variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Company {
+    pub name: String,
+    pub iban: String,
+    pub bic: String,
+    /// SEPA creditor identifier given by the national bank.
+    pub scheme_id: String,
+}
+
+impl Company {
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        Some(Self {
+            name: var("SEPA_COMPANY_NAME")?,
+            iban: var("SEPA_COMPANY_IBAN")?,
+            bic: var("SEPA_COMPANY_BIC")?,
+            scheme_id: var("SEPA_CREDITOR_ID")?,
+        })
+    }
+}
+
+/// A collection of the batch, in euros.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectDebit {
+    /// Returned by the banks with the payment, at most 35 characters.
+    pub end_to_end_id: String,
+    pub amount_cents: i64,
+    pub collection_date: NaiveDate,
+    pub sequence_type: SequenceType,
+    pub mandate_reference: String,
+    pub mandate_signed_on: NaiveDate,
+    pub debtor_name: String,
+    pub debtor_iban: String,
+    pub debtor_bic: Option<String>,
+    pub remittance_information: String,
+}
+
+/// Writes a pain.008.001.02 customer direct debit initiation message. The debits are grouped
+/// in a payment information block per collection date and sequence type.
+pub fn pain008_document(
+    message_id: &str,
+    created_at: NaiveDateTime,
+    creditor: &Company,
+    debits: &[DirectDebit],
+) -> String {
+    let mut payments = BTreeMap::<(NaiveDate, SequenceType), Vec<&DirectDebit>>::new();
+    for debit in debits {
+        payments
+            .entry((debit.collection_date, debit.sequence_type))
+            .or_default()
+            .push(debit);
+    }
+
+    let mut xml = String::new();
+    // writing to a string cannot fail
+    let _ = write!(
+        xml,
+        r#"<?xml version="1.0" encoding="UTF-8"?><Document xmlns="{}"><CstmrDrctDbtInitn>"#,
+        PAIN_008_NAMESPACE
+    );
+    let _ = write!(
+        xml,
+        "<GrpHdr><MsgId>{}</MsgId><CreDtTm>{}</CreDtTm><NbOfTxs>{}</NbOfTxs><CtrlSum>{}</CtrlSum>\
+        <InitgPty><Nm>{}</Nm></InitgPty></GrpHdr>",
+        escape(message_id),
+        created_at.format("%Y-%m-%dT%H:%M:%S"),
+        debits.len(),
+        format_amount(debits.iter().map(|debit| debit.amount_cents).sum()),
+        escape(truncate(&creditor.name, MAX_NAME_LENGTH)),
+    );
+
+    for (index, ((collection_date, sequence_type), debits)) in payments.iter().enumerate() {
+        let _ = write!(
+            xml,
+            "<PmtInf><PmtInfId>{}-{}</PmtInfId><PmtMtd>DD</PmtMtd><NbOfTxs>{}</NbOfTxs>\
+            <CtrlSum>{}</CtrlSum><PmtTpInf><SvcLvl><Cd>SEPA</Cd></SvcLvl>\
+            <LclInstrm><Cd>CORE</Cd></LclInstrm><SeqTp>{}</SeqTp></PmtTpInf>\
+            <ReqdColltnDt>{}</ReqdColltnDt><Cdtr><Nm>{}</Nm></Cdtr>\
+            <CdtrAcct><Id><IBAN>{}</IBAN></Id></CdtrAcct>\
+            <CdtrAgt><FinInstnId><BIC>{}</BIC></FinInstnId></CdtrAgt><ChrgBr>SLEV</ChrgBr>\
+            <CdtrSchmeId><Id><PrvtId><Othr><Id>{}</Id><SchmeNm><Prtry>SEPA</Prtry></SchmeNm>\
+            </Othr></PrvtId></Id></CdtrSchmeId>",
+            escape(truncate(message_id, MAX_PAYMENT_PREFIX_LENGTH)),
+            index + 1,
+            debits.len(),
+            format_amount(debits.iter().map(|debit| debit.amount_cents).sum()),
+            sequence_type.as_code(),
+            collection_date.format("%Y-%m-%d"),
+            escape(truncate(&creditor.name, MAX_NAME_LENGTH)),
+            escape(&creditor.iban),
+            escape(&creditor.bic),
+            escape(&creditor.scheme_id),
+        );
+
+        for debit in debits {
+            // the debtor bank is found from the IBAN when its BIC is not known
+            let debtor_agent = match &debit.debtor_bic {
+                Some(bic) => format!("<BIC>{}</BIC>", escape(bic)),
+                None => "<Othr><Id>NOTPROVIDED</Id></Othr>".into(),
+            };
+
+            let _ = write!(
+                xml,
+                "<DrctDbtTxInf><PmtId><EndToEndId>{}</EndToEndId></PmtId>\
+                <InstdAmt Ccy=\"EUR\">{}</InstdAmt><DrctDbtTx><MndtRltdInf><MndtId>{}</MndtId>\
+                <DtOfSgntr>{}</DtOfSgntr></MndtRltdInf></DrctDbtTx>\
+                <DbtrAgt><FinInstnId>{}</FinInstnId></DbtrAgt><Dbtr><Nm>{}</Nm></Dbtr>\
+                <DbtrAcct><Id><IBAN>{}</IBAN></Id></DbtrAcct><RmtInf><Ustrd>{}</Ustrd></RmtInf>\
+                </DrctDbtTxInf>",
+                escape(&debit.end_to_end_id),
+                format_amount(debit.amount_cents),
+                escape(&debit.mandate_reference),
+                debit.mandate_signed_on.format("%Y-%m-%d"),
+                debtor_agent,
+                escape(truncate(&debit.debtor_name, MAX_NAME_LENGTH)),
+                escape(&debit.debtor_iban),
+                escape(truncate(
+                    &debit.remittance_information,
+                    MAX_REMITTANCE_INFORMATION_LENGTH
+                )),
+            );
+        }
+
+        xml.push_str("</PmtInf>");
+    }
+
+    xml.push_str("</CstmrDrctDbtInitn></Document>");
+    xml
+}
+
+/// A payout of the batch, in euros.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreditTransfer {
+    /// Returned by the banks with the payment, at most 35 characters.
+    pub end_to_end_id: String,
+    pub amount_cents: i64,
+    pub execution_date: NaiveDate,
+    pub creditor_name: String,
+    pub creditor_iban: String,
+    pub creditor_bic: Option<String>,
+    pub remittance_information: String,
+}
+
+/// Writes a pain.001.001.03 customer credit transfer initiation message paid from the
+/// company account. The transfers are grouped in a payment information block per execution date.
+pub fn pain001_document(
+    message_id: &str,
+    created_at: NaiveDateTime,
+    debtor: &Company,
+    transfers: &[CreditTransfer],
+) -> String {
+    let mut payments = BTreeMap::<NaiveDate, Vec<&CreditTransfer>>::new();
+    for transfer in transfers {
+        payments
+            .entry(transfer.execution_date)
+            .or_default()
+            .push(transfer);
+    }
+
+    let mut xml = String::new();
+    // writing to a string cannot fail
+    let _ = write!(
+        xml,
+        r#"<?xml version="1.0" encoding="UTF-8"?><Document xmlns="{}"><CstmrCdtTrfInitn>"#,
+        PAIN_001_NAMESPACE
+    );
+    let _ = write!(
+        xml,
+        "<GrpHdr><MsgId>{}</MsgId><CreDtTm>{}</CreDtTm><NbOfTxs>{}</NbOfTxs><CtrlSum>{}</CtrlSum>\
+        <InitgPty><Nm>{}</Nm></InitgPty></GrpHdr>",
+        escape(message_id),
+        created_at.format("%Y-%m-%dT%H:%M:%S"),
+        transfers.len(),
+        format_amount(transfers.iter().map(|transfer| transfer.amount_cents).sum()),
+        escape(truncate(&debtor.name, MAX_NAME_LENGTH)),
+    );
+
+    for (index, (execution_date, transfers)) in payments.iter().enumerate() {
+        let _ = write!(
+            xml,
+            "<PmtInf><PmtInfId>{}-{}</PmtInfId><PmtMtd>TRF</PmtMtd><NbOfTxs>{}</NbOfTxs>\
+            <CtrlSum>{}</CtrlSum><PmtTpInf><SvcLvl><Cd>SEPA</Cd></SvcLvl></PmtTpInf>\
+            <ReqdExctnDt>{}</ReqdExctnDt><Dbtr><Nm>{}</Nm></Dbtr>\
+            <DbtrAcct><Id><IBAN>{}</IBAN></Id></DbtrAcct>\
+            <DbtrAgt><FinInstnId><BIC>{}</BIC></FinInstnId></DbtrAgt><ChrgBr>SLEV</ChrgBr>",
+            escape(truncate(message_id, MAX_PAYMENT_PREFIX_LENGTH)),
+            index + 1,
+            transfers.len(),
+            format_amount(transfers.iter().map(|transfer| transfer.amount_cents).sum()),
+            execution_date.format("%Y-%m-%d"),
+            escape(truncate(&debtor.name, MAX_NAME_LENGTH)),
+            escape(&debtor.iban),
+            escape(&debtor.bic),
+        );
+
+        for transfer in transfers {
+            // the creditor bank is found from the IBAN when its BIC is not known
+            let creditor_agent = match &transfer.creditor_bic {
+                Some(bic) => format!(
+                    "<CdtrAgt><FinInstnId><BIC>{}</BIC></FinInstnId></CdtrAgt>",
+                    escape(bic)
+                ),
+                None => String::new(),
+            };
+
+            let _ = write!(
+                xml,
+                "<CdtTrfTxInf><PmtId><EndToEndId>{}</EndToEndId></PmtId>\
+                <Amt><InstdAmt Ccy=\"EUR\">{}</InstdAmt></Amt>{}<Cdtr><Nm>{}</Nm></Cdtr>\
+                <CdtrAcct><Id><IBAN>{}</IBAN></Id></CdtrAcct><RmtInf><Ustrd>{}</Ustrd></RmtInf>\
+                </CdtTrfTxInf>",
+                escape(&transfer.end_to_end_id),
+                format_amount(transfer.amount_cents),
+                creditor_agent,
+                escape(truncate(&transfer.creditor_name, MAX_NAME_LENGTH)),
+                escape(&transfer.creditor_iban),
+                escape(truncate(
+                    &transfer.remittance_information,
+                    MAX_REMITTANCE_INFORMATION_LENGTH
+                )),
+            );
+        }
+
+        xml.push_str("</PmtInf>");
+    }
+
+    xml.push_str("</CstmrCdtTrfInitn></Document>");
+    xml
+}
+
+/// Euros with two decimals, `1234` cents is `12.34`.
+fn format_amount(cents: i64) -> String {
+    format!("{}.{:02}", cents / 100, cents % 100)
+}
+
+fn truncate(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
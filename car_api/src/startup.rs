@@ -1,12 +1,18 @@
-use crate::routes::{account::*, authenticate::*, health_check::*, AppState};
+use crate::routes::{
+    account::*, authenticate::*, bank_details::*, car_csv::*, charge::*, document::*, ev::*,
+    fuel::*, geofence::*, health_check::*, insurance::*, maintenance::*, membership::*,
+    odometer::*, parking::*, photo::*, recall::*, sepa::*, transfer::*, trip::*, AppState,
+};
+use crate::storage::LocalStorage;
 
 use std::net::TcpListener;
 use std::sync::Arc;
 
 use axum::{
+    extract::DefaultBodyLimit,
     http::{StatusCode, Uri},
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 use axum_login::{
@@ -26,12 +32,203 @@ pub async fn run(listener: TcpListener, pg_pool: PgPool) -> std::io::Result<()>
         .with_query("SELECT * FROM users WHERE id::text = $1");
     let auth_layer = AuthLayer::new(user_store, &secret);
 
-    let shared_state = Arc::new(AppState { pg_pool });
+    tokio::spawn(run_location_purge(pg_pool.clone()));
+
+    let shared_state = Arc::new(AppState {
+        pg_pool,
+        storage: Box::new(LocalStorage::from_env()),
+    });
 
     let app = Router::new()
         .route("/api/account", get(get_account_details))
+        .route(
+            "/api/account/bank_details",
+            get(get_bank_details).post(add_bank_details),
+        )
+        .route(
+            "/api/account/bank_details/:bank_details_id",
+            delete(delete_bank_details),
+        )
+        .route(
+            "/api/account/bank_details/:bank_details_id/primary",
+            put(set_primary_bank_details),
+        )
+        .route(
+            "/api/account/bank_details/:bank_details_id/reveal",
+            post(reveal_iban),
+        )
+        .route(
+            "/api/account/bank_details/:bank_details_id/mandates",
+            post(create_mandate),
+        )
+        .route("/api/account/mandates", get(get_mandates))
+        .route(
+            "/api/account/mandates/:mandate_id/revoke",
+            post(revoke_mandate),
+        )
+        .route("/api/account/payouts", get(get_payouts))
+        .route("/api/account/reauthenticate", post(reauthenticate))
+        .route("/api/cars", get(get_cars))
+        .route(
+            "/api/cars/import",
+            post(import_cars).layer(DefaultBodyLimit::max(MAX_CAR_IMPORT_SIZE)),
+        )
+        .route("/api/cars/export", get(export_cars))
+        .route(
+            "/api/cars/:car_id/odometer",
+            get(get_odometer_readings).post(create_odometer_reading),
+        )
+        .route(
+            "/api/cars/:car_id/maintenance",
+            get(get_maintenance_records).post(create_maintenance_record),
+        )
+        .route(
+            "/api/cars/:car_id/service_intervals",
+            get(get_service_intervals).put(set_service_interval),
+        )
+        .route(
+            "/api/cars/:car_id/service_intervals/:interval_id",
+            delete(delete_service_interval),
+        )
+        .route("/api/cars/:car_id/services/due", get(get_due_services))
+        .route(
+            "/api/cars/:car_id/fuel",
+            get(get_fuel_entries).post(create_fuel_entry),
+        )
+        .route(
+            "/api/cars/:car_id/fuel/consumption",
+            get(get_fuel_consumption),
+        )
+        .route(
+            "/api/cars/:car_id/fuel/monthly",
+            get(get_fuel_monthly_totals),
+        )
+        .route(
+            "/api/cars/:car_id/documents",
+            get(get_documents)
+                .post(upload_document)
+                .layer(DefaultBodyLimit::max(MAX_DOCUMENT_SIZE)),
+        )
+        .route(
+            "/api/cars/:car_id/documents/:document_id",
+            get(download_document).delete(delete_document),
+        )
+        .route(
+            "/api/cars/:car_id/photos",
+            get(get_car_photos)
+                .post(upload_car_photo)
+                .layer(DefaultBodyLimit::max(MAX_PHOTO_SIZE)),
+        )
+        .route(
+            "/api/cars/:car_id/photos/:photo_id",
+            delete(delete_car_photo),
+        )
+        .route(
+            "/api/cars/:car_id/photos/:photo_id/primary",
+            put(set_primary_car_photo),
+        )
+        .route(
+            "/api/cars/:car_id/photos/:photo_id/:variant",
+            get(download_car_photo),
+        )
+        .route(
+            "/api/cars/:car_id/insurance",
+            get(get_insurance_policies).post(create_insurance_policy),
+        )
+        .route("/api/cars/:car_id/ev", get(get_ev_specs).put(set_ev_specs))
+        .route(
+            "/api/cars/:car_id/soc",
+            get(get_soc_readings).post(create_soc_readings),
+        )
+        .route("/api/cars/:car_id/battery", get(get_battery_health))
+        .route("/api/cars/:car_id/trips", get(get_trips).post(create_trip))
+        .route(
+            "/api/cars/:car_id/trips/gpx",
+            post(import_gpx_trip).layer(DefaultBodyLimit::max(MAX_GPX_SIZE)),
+        )
+        .route("/api/cars/:car_id/trips/:trip_id", get(get_trip))
+        .route(
+            "/api/cars/:car_id/trips/:trip_id/points",
+            post(add_trip_points),
+        )
+        .route("/api/cars/:car_id/trips/:trip_id/export", get(export_trip))
+        .route("/api/cars/:car_id/locations", post(create_car_locations))
+        .route(
+            "/api/cars/:car_id/parking",
+            get(get_parked_location).post(park_car),
+        )
+        .route(
+            "/api/cars/:car_id/parking/history",
+            get(get_parking_history),
+        )
+        .route(
+            "/api/cars/:car_id/charges",
+            get(get_charges).post(create_charge),
+        )
+        .route("/api/cars/:car_id/charges/:charge_id/pay", post(pay_charge))
+        .route(
+            "/api/cars/:car_id/charges/:charge_id/driver",
+            put(reassign_charge),
+        )
+        .route("/api/cars/:car_id/recalls", get(get_car_recalls))
+        .route(
+            "/api/cars/:car_id/recalls/:recall_id/complete",
+            post(complete_car_recall),
+        )
+        .route(
+            "/api/cars/:car_id/invitations",
+            get(get_car_invitations).post(invite_car_member),
+        )
+        .route(
+            "/api/cars/:car_id/invitations/:invitation_id",
+            delete(revoke_car_invitation),
+        )
+        .route("/api/cars/:car_id/members", get(get_car_members))
+        .route(
+            "/api/cars/:car_id/members/:user_id",
+            put(update_car_member).delete(remove_car_member),
+        )
+        .route("/api/invitations", get(get_my_invitations))
+        .route(
+            "/api/invitations/:invitation_id/accept",
+            post(accept_invitation),
+        )
+        .route(
+            "/api/invitations/:invitation_id/decline",
+            post(decline_invitation),
+        )
+        .route(
+            "/api/cars/:car_id/transfers",
+            get(get_car_transfers).post(create_car_transfer),
+        )
+        .route(
+            "/api/cars/:car_id/transfers/:transfer_id",
+            delete(cancel_car_transfer),
+        )
+        .route("/api/transfers", get(get_my_transfers))
+        .route("/api/transfers/:transfer_id/accept", post(accept_transfer))
+        .route(
+            "/api/transfers/:transfer_id/decline",
+            post(decline_transfer),
+        )
+        .route("/api/charges/outstanding", get(get_outstanding_charges))
+        .route("/api/geofences", get(get_geofences).post(create_geofence))
+        .route("/api/geofences/events", get(get_geofence_events))
+        .route("/api/geofences/:geofence_id", delete(delete_geofence))
+        .route(
+            "/api/insurance/expiring",
+            get(get_expiring_insurance_policies),
+        )
         .route_layer(RequireAuthorizationLayer::<User>::login())
         .route("/api/account", post(create_account))
+        .route(
+            "/api/recalls/import",
+            post(import_recalls).layer(DefaultBodyLimit::max(MAX_RECALL_FEED_SIZE)),
+        )
+        .route("/api/sepa/collections", post(create_collection))
+        .route("/api/sepa/batches", post(create_direct_debit_batch))
+        .route("/api/sepa/payouts", post(create_payout))
+        .route("/api/sepa/transfers", post(create_credit_transfer_batch))
         .route("/login", post(login_handler))
         .route("/logout", get(logout_handler))
         .route("/health_check", get(health_check))
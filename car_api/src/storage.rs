@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use std::path::PathBuf;
+use tracing::error;
+
+use crate::errors::Error;
+
+/// Where uploaded files are stored, keys are `/` separated paths chosen by the api.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), Error>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Error>;
+
+    /// Deleting a missing key is not an error.
+    async fn delete(&self, key: &str) -> Result<(), Error>;
+}
+
+/// Deletes the keys written for a request that failed, the failures are only logged since
+/// the request already returns an error.
+pub async fn delete_keys(storage: &dyn Storage, keys: &[String]) {
+    for key in keys {
+        if let Err(err) = storage.delete(key).await {
+            error!("failed to delete the storage key {}: {:?}", key, err);
+        }
+    }
+}
+
+/// Stores files under a directory of the local filesystem.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns a storage rooted at `STORAGE_PATH`, `./storage` by default.
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("STORAGE_PATH").unwrap_or_else(|_| "storage".to_owned()))
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, Error> {
+        if key.is_empty() || key.split('/').any(|part| part.is_empty() || part == "..") {
+            // keys are chosen by the api, an invalid one is a bug rather than a client error
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid storage key {}", key),
+            )));
+        }
+
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), Error> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, data).await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Error> {
+        let path = self.path(key)?;
+
+        Ok(tokio::fs::read(path).await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        let path = self.path(key)?;
+
+        match tokio::fs::remove_file(path).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
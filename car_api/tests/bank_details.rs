@@ -0,0 +1,226 @@
+mod setup;
+
+use car_api::routes::bank_details::BankDetailsInfo;
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+fn account_body(email: &str, bank_details: serde_json::Value) -> serde_json::Value {
+    serde_json::json!(
+        {
+            "user": {
+                "email": email,
+                "password": "my super password",
+                "user_name": "toto"
+            },
+            "car_info": {
+                "car_model": "tesla",
+                "car_plate": "42"
+            },
+            "bank_details": bank_details
+        }
+    )
+}
+
+#[tokio::test]
+async fn bank_details_are_normalized() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    let body = account_body(
+        "email@email.com",
+        serde_json::json!({
+            "account_holder": "toto",
+            "bank_country": "France",
+            "iban": " fr76 3000 6000 0112 3456 7890 189 ",
+            "bic": "bnpa fr pp xxx"
+        }),
+    );
+
+    // Act
+    let response = client
+        .post(&format!("{}/api/account", &app.address))
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    client
+        .post(&format!("{}/login", &app.address))
+        .json(&serde_json::json!({ "email": "email@email.com", "password_hash": "my super password" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(201, response.status().as_u16());
+    let bank_details = &get_account_details(&app, &client).await.bank_details[0];
+    assert_eq!("FR", bank_details.country);
+    assert_eq!("FR76 **** **** 0189", bank_details.iban);
+    assert_eq!(Some("BNPAFRPPXXX"), bank_details.bic.as_deref());
+}
+
+#[tokio::test]
+async fn invalid_bank_details_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::new();
+    let cases = [
+        // wrong check digits
+        ("FR", "FR7730006000011234567890189", None),
+        // too short for a french IBAN
+        ("FR", "FR763000600001123456789018", None),
+        // letters where the german structure expects digits
+        ("DE", "DE89370400440532O13000", None),
+        ("XX", "XX7630006000011234567890189", None),
+        // valid IBAN of another country
+        ("spain", "DE89370400440532013000", None),
+        ("germany", "DE89370400440532013000", Some("DEUT")),
+        ("germany", "12345", None),
+    ];
+
+    for (index, (country, iban, bic)) in cases.into_iter().enumerate() {
+        let body = account_body(
+            &format!("email{}@email.com", index),
+            serde_json::json!({
+                "account_holder": "toto",
+                "bank_country": country,
+                "iban": iban,
+                "bic": bic
+            }),
+        );
+
+        // Act
+        let response = client
+            .post(&format!("{}/api/account", &app.address))
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+
+        // Assert
+        assert_eq!(422, response.status().as_u16(), "{} {}", country, iban);
+    }
+}
+
+#[tokio::test]
+async fn the_primary_bank_account_can_be_changed() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let first = get_account_details(&app, &client).await.bank_details[0].clone();
+
+    // Act
+    let second = client
+        .post(&format!("{}/api/account/bank_details", &app.address))
+        .json(&serde_json::json!({
+            "account_holder": "toto",
+            "bank_country": "DE",
+            "iban": "DE89 3704 0044 0532 0130 00"
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<BankDetailsInfo>()
+        .await
+        .unwrap();
+    let primary = client
+        .put(&format!(
+            "{}/api/account/bank_details/{}/primary",
+            &app.address, second.id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<BankDetailsInfo>()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(first.is_primary);
+    assert!(!second.is_primary);
+    assert_eq!("DE89 **** **** 3000", second.iban);
+    assert!(primary.is_primary);
+    let bank_details = get_account_details(&app, &client).await.bank_details;
+    assert_eq!(
+        vec![(second.id, true), (first.id, false)],
+        bank_details
+            .iter()
+            .map(|bank_details| (bank_details.id, bank_details.is_primary))
+            .collect::<Vec<_>>()
+    );
+
+    // Act
+    let response = client
+        .delete(&format!(
+            "{}/api/account/bank_details/{}",
+            &app.address, second.id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(204, response.status().as_u16());
+    let bank_details = get_account_details(&app, &client).await.bank_details;
+    assert_eq!(1, bank_details.len());
+    assert_eq!(first.id, bank_details[0].id);
+    assert!(bank_details[0].is_primary);
+}
+
+#[tokio::test]
+async fn bank_accounts_with_active_mandates_are_kept() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let bank_details_id = get_account_details(&app, &client).await.bank_details[0].id;
+    sqlx::query(
+        "INSERT INTO sepa_mandates(bank_details_id, reference, signed_on) VALUES ($1, 'MANDATE-1', '2023-01-15')",
+    )
+    .bind(bank_details_id)
+    .execute(&app.pg_pool)
+    .await
+    .unwrap();
+    let url = format!(
+        "{}/api/account/bank_details/{}",
+        &app.address, bank_details_id
+    );
+
+    // Act
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(409, response.status().as_u16());
+
+    // Arrange
+    sqlx::query("UPDATE sepa_mandates SET revoked_at=now()")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = client
+        .delete(&url)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(204, response.status().as_u16());
+    assert!(get_account_details(&app, &client)
+        .await
+        .bank_details
+        .is_empty());
+    // the revoked mandate is still on record
+    let (mandates,) = sqlx::query_as::<_, (i64,)>("SELECT count(*) FROM sepa_mandates")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(1, mandates);
+}
@@ -0,0 +1,110 @@
+mod setup;
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn cars_are_imported_after_a_dry_run_and_exported() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let import_url = format!("{}/api/cars/import", &app.address);
+    let file = "\
+plate,model,manufacturer,vin,model_year
+AA-100,zoe,Renault,VF1AG000000000001,2020
+AB-200,clio,Renault,not a vin,2019
+,megane,,,
+AC-300,captur,,,
+";
+
+    // Act
+    let dry_run = client
+        .post(&format!("{}?dry_run=true", import_url))
+        .header("Content-Type", "text/csv")
+        .body(file)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(2, dry_run["valid"]);
+    assert_eq!(0, dry_run["imported"]);
+    assert_eq!(3, dry_run["rows"][0]["line"]);
+    assert!(dry_run["rows"][0]["errors"]["vin"].is_array());
+    assert_eq!(4, dry_run["rows"][1]["line"]);
+    assert!(dry_run["rows"][1]["errors"]["plate"].is_array());
+    assert_eq!(1, get_account_details(&app, &client).await.cars_info.len());
+
+    // Act
+    let import = client
+        .post(&import_url)
+        .header("Content-Type", "text/csv")
+        .body(file)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let export = client
+        .get(&format!("{}/api/cars/export", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(2, import["imported"]);
+    assert_eq!("text/csv", export.headers()["content-type"]);
+    let export = export.text().await.unwrap();
+    let lines: Vec<&str> = export.lines().collect();
+    assert_eq!(4, lines.len());
+    assert_eq!(
+        "id,plate,model,manufacturer,vin,model_year,role,archived_at,created_at",
+        lines[0]
+    );
+    // the imported cars share the creation time of the transaction
+    assert!(lines[2..]
+        .iter()
+        .any(|line| line.contains(",AA-100,zoe,Renault,VF1AG000000000001,2020,owner,,")));
+    assert!(lines[2..]
+        .iter()
+        .any(|line| line.contains(",AC-300,captur,,,,owner,,")));
+}
+
+#[tokio::test]
+async fn car_import_requires_a_csv_file() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let import_url = format!("{}/api/cars/import", &app.address);
+
+    // Act
+    let response = client
+        .post(&import_url)
+        .json(&serde_json::json!([{ "plate": "AA-100", "model": "zoe" }]))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(415, response.status().as_u16());
+
+    // Act
+    let import = client
+        .post(&import_url)
+        .header("Content-Type", "text/csv")
+        .body("plate,model\nAA-100,zoe,extra\nAB-200\n")
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(0, import["imported"]);
+    assert_eq!(2, import["rows"].as_array().unwrap().len());
+    assert!(import["rows"][0]["errors"]["row"].is_array());
+}
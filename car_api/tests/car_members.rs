@@ -0,0 +1,179 @@
+mod setup;
+
+use car_api::routes::car::CarRole;
+use car_api::routes::membership::{CarMember, Invitation};
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn invited_driver_can_log_but_not_edit() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &owner, "owner@email.com").await;
+    let car_id = get_account_details(&app, &owner).await.cars_info[0].id;
+    let car_url = format!("{}/api/cars/{}", &app.address, car_id);
+
+    let driver = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &driver, "driver@email.com").await;
+
+    // Act
+    let response = owner
+        .post(&format!("{}/invitations", car_url))
+        .json(&serde_json::json!({ "email": "Driver@email.com", "role": "driver" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(201, response.status().as_u16());
+
+    // Act
+    let response = owner
+        .post(&format!("{}/invitations", car_url))
+        .json(&serde_json::json!({ "email": "driver@email.com", "role": "driver" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(409, response.status().as_u16());
+
+    // Act
+    let response = driver
+        .get(&format!("{}/odometer", car_url))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(404, response.status().as_u16());
+
+    // Act
+    let invitations = driver
+        .get(&format!("{}/api/invitations", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<Invitation>>()
+        .await
+        .unwrap();
+    let response = driver
+        .post(&format!(
+            "{}/api/invitations/{}/accept",
+            &app.address, invitations[0].id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(1, invitations.len());
+    assert_eq!(200, response.status().as_u16());
+
+    // Act
+    let response = driver
+        .post(&format!("{}/odometer", car_url))
+        .json(&serde_json::json!({ "mileage": 1000 }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(201, response.status().as_u16());
+
+    // Act
+    let response = driver
+        .post(&format!("{}/maintenance", car_url))
+        .json(&serde_json::json!({ "service_type": "oil_change", "performed_on": "2023-01-10" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(403, response.status().as_u16());
+
+    // Act
+    let response = driver
+        .post(&format!("{}/invitations", car_url))
+        .json(&serde_json::json!({ "email": "friend@email.com", "role": "driver" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(403, response.status().as_u16());
+
+    // Act
+    let details = get_account_details(&app, &driver).await;
+    // Assert
+    assert_eq!(2, details.cars_info.len());
+    let shared = details
+        .cars_info
+        .iter()
+        .find(|car| car.id == car_id)
+        .unwrap();
+    assert_eq!(CarRole::Driver, shared.role);
+
+    // Act
+    let members = owner
+        .get(&format!("{}/members", car_url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<CarMember>>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(2, members.len());
+    assert_eq!(CarRole::Owner, members[0].role);
+    assert!(!members[1].permissions.can_edit);
+}
+
+#[tokio::test]
+async fn declined_invitation_gives_no_access() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &owner, "owner@email.com").await;
+    let car_id = get_account_details(&app, &owner).await.cars_info[0].id;
+
+    let other = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &other, "other@email.com").await;
+
+    let invitation = owner
+        .post(&format!("{}/api/cars/{}/invitations", &app.address, car_id))
+        .json(&serde_json::json!({
+            "email": "other@email.com",
+            "role": "co_owner",
+            "permissions": ["view", "edit"]
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Invitation>()
+        .await
+        .unwrap();
+    assert!(invitation.permissions.can_edit);
+    assert!(!invitation.permissions.can_log_trips);
+
+    // Act
+    let response = other
+        .post(&format!(
+            "{}/api/invitations/{}/decline",
+            &app.address, invitation.id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(204, response.status().as_u16());
+
+    // Act
+    let response = other
+        .post(&format!(
+            "{}/api/invitations/{}/accept",
+            &app.address, invitation.id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(404, response.status().as_u16());
+    assert_eq!(1, get_account_details(&app, &other).await.cars_info.len());
+}
@@ -0,0 +1,176 @@
+mod setup;
+
+use car_api::routes::odometer::OdometerReading;
+use car_api::routes::transfer::{Transfer, TransferStatus};
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn accepted_transfer_moves_the_car_and_keeps_a_snapshot() {
+    // Arrange
+    let app = spawn_app().await;
+    let seller = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &seller, "seller@email.com").await;
+    let car_id = get_account_details(&app, &seller).await.cars_info[0].id;
+    let car_url = format!("{}/api/cars/{}", &app.address, car_id);
+
+    let buyer = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &buyer, "buyer@email.com").await;
+
+    seller
+        .post(&format!("{}/odometer", car_url))
+        .json(&serde_json::json!({ "mileage": 1000, "recorded_at": "2023-01-10T10:00:00" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    seller
+        .post(&format!("{}/parking", car_url))
+        .json(&serde_json::json!({ "latitude": 48.8566, "longitude": 2.3522, "spot_note": "B2" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Act
+    let response = seller
+        .post(&format!("{}/transfers", car_url))
+        .json(&serde_json::json!({ "email": "Buyer@email.com" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(201, response.status().as_u16());
+
+    // Act
+    let transfers = buyer
+        .get(&format!("{}/api/transfers", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<Transfer>>()
+        .await
+        .unwrap();
+    let transfer = buyer
+        .post(&format!(
+            "{}/api/transfers/{}/accept",
+            &app.address, transfers[0].id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Transfer>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(1, transfers.len());
+    assert_eq!(TransferStatus::Accepted, transfer.status);
+
+    // Act
+    let readings = buyer
+        .get(&format!("{}/odometer", car_url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<OdometerReading>>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(1, readings.len());
+    let details = get_account_details(&app, &buyer).await;
+    assert_eq!(2, details.cars_info.len());
+    let car = details.cars_info.iter().find(|car| car.id == car_id);
+    assert!(car.unwrap().location.is_none());
+
+    // Act
+    let response = seller
+        .get(&format!("{}/odometer", car_url))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(404, response.status().as_u16());
+
+    // Act
+    let snapshot_id = transfer.snapshot_car_id.unwrap();
+    let snapshot_url = format!("{}/api/cars/{}", &app.address, snapshot_id);
+    let readings = seller
+        .get(&format!("{}/odometer", snapshot_url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<OdometerReading>>()
+        .await
+        .unwrap();
+    let response = seller
+        .post(&format!("{}/odometer", snapshot_url))
+        .json(&serde_json::json!({ "mileage": 2000 }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(1, readings.len());
+    assert_eq!(1000, readings[0].mileage);
+    assert_eq!(403, response.status().as_u16());
+    let details = get_account_details(&app, &seller).await;
+    assert_eq!(1, details.cars_info.len());
+    assert!(details.cars_info[0].archived_at.is_some());
+    assert!(details.cars_info[0].location.is_some());
+}
+
+#[tokio::test]
+async fn cancelled_transfer_cannot_be_accepted() {
+    // Arrange
+    let app = spawn_app().await;
+    let seller = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &seller, "seller@email.com").await;
+    let car_id = get_account_details(&app, &seller).await.cars_info[0].id;
+    let car_url = format!("{}/api/cars/{}", &app.address, car_id);
+
+    let buyer = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &buyer, "buyer@email.com").await;
+
+    let transfer = seller
+        .post(&format!("{}/transfers", car_url))
+        .json(&serde_json::json!({ "email": "buyer@email.com" }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Transfer>()
+        .await
+        .unwrap();
+
+    // Act
+    let response = seller
+        .post(&format!("{}/transfers", car_url))
+        .json(&serde_json::json!({ "email": "other@email.com" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(409, response.status().as_u16());
+
+    // Act
+    let response = seller
+        .delete(&format!("{}/transfers/{}", car_url, transfer.id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(204, response.status().as_u16());
+
+    // Act
+    let response = buyer
+        .post(&format!(
+            "{}/api/transfers/{}/accept",
+            &app.address, transfer.id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(404, response.status().as_u16());
+    assert_eq!(1, get_account_details(&app, &buyer).await.cars_info.len());
+    let details = get_account_details(&app, &seller).await;
+    assert!(details.cars_info[0].archived_at.is_none());
+}
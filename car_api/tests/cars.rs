@@ -0,0 +1,95 @@
+mod setup;
+
+use car_api::routes::account::CarsPage;
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+async fn get_cars_page(app: &TestApp, client: &Client, path: &str) -> CarsPage {
+    client
+        .get(&format!("{}{}", &app.address, path))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<CarsPage>()
+        .await
+        .expect("Failed to parse cars page.")
+}
+
+fn plates(page: &CarsPage) -> Vec<&str> {
+    page.cars.iter().map(|car| car.plate.as_str()).collect()
+}
+
+#[tokio::test]
+async fn cars_are_filtered_sorted_and_paginated() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let user_id = get_account_details(&app, &client).await.user.id;
+    for (plate, model, created_at) in [
+        ("AA-100", "zoe", "2023-01-01T00:00:00"),
+        ("AB-200", "clio", "2023-02-01T00:00:00"),
+        ("AC-300", "zoe", "2023-03-01T00:00:00"),
+        ("B%-400", "megane", "2023-04-01T00:00:00"),
+    ] {
+        sqlx::query(
+            "INSERT INTO car(user_id, plate, model, created_at) VALUES ($1, $2, $3, $4::timestamp)",
+        )
+        .bind(user_id)
+        .bind(plate)
+        .bind(model)
+        .bind(created_at)
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+    }
+
+    // Act
+    let first = get_cars_page(&app, &client, "/api/cars?sort=plate&order=desc&limit=2").await;
+    let second = get_cars_page(&app, &client, first.next.as_ref().unwrap()).await;
+    let third = get_cars_page(&app, &client, second.next.as_ref().unwrap()).await;
+    let back = get_cars_page(&app, &client, third.prev.as_ref().unwrap()).await;
+    // Assert
+    assert_eq!(vec!["B%-400", "AC-300"], plates(&first));
+    assert_eq!(None, first.prev);
+    assert_eq!(vec!["AB-200", "AA-100"], plates(&second));
+    assert_eq!(vec!["42"], plates(&third));
+    assert_eq!(None, third.next);
+    assert_eq!(plates(&second), plates(&back));
+
+    // Act
+    let models = get_cars_page(&app, &client, "/api/cars?model=zoe&model=CLIO").await;
+    let plate = get_cars_page(&app, &client, "/api/cars?plate=b%25").await;
+    let created = get_cars_page(
+        &app,
+        &client,
+        "/api/cars?created_from=2023-02-01T00:00:00&created_to=2023-04-01T00:00:00",
+    )
+    .await;
+    // Assert
+    assert_eq!(vec!["AA-100", "AB-200", "AC-300"], plates(&models));
+    assert_eq!(vec!["B%-400"], plates(&plate));
+    assert_eq!(vec!["AB-200", "AC-300"], plates(&created));
+    assert_eq!(None, created.next);
+}
+
+#[tokio::test]
+async fn invalid_car_listing_parameters_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+
+    for query in ["limit=0", "limit=1000", "cursor=not-a-cursor"] {
+        // Act
+        let response = client
+            .get(&format!("{}/api/cars?{}", &app.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        // Assert
+        assert_eq!(422, response.status().as_u16());
+    }
+}
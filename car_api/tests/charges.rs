@@ -0,0 +1,165 @@
+mod setup;
+
+use car_api::routes::charge::Charge;
+use car_api::routes::membership::{CarMember, Invitation};
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn fines_are_reassigned_to_the_driver_and_paid() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &owner, "owner@email.com").await;
+    let car_id = get_account_details(&app, &owner).await.cars_info[0].id;
+    let car_url = format!("{}/api/cars/{}", &app.address, car_id);
+
+    let driver = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &driver, "driver@email.com").await;
+    let invitation = owner
+        .post(&format!("{}/invitations", car_url))
+        .json(&serde_json::json!({ "email": "driver@email.com", "role": "driver" }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Invitation>()
+        .await
+        .unwrap();
+    driver
+        .post(&format!(
+            "{}/api/invitations/{}/accept",
+            &app.address, invitation.id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let members = owner
+        .get(&format!("{}/members", car_url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<CarMember>>()
+        .await
+        .unwrap();
+    let driver_id = members[1].user_id;
+
+    // Act
+    let fine = owner
+        .post(&format!("{}/charges", car_url))
+        .json(&serde_json::json!({
+            "kind": "fine",
+            "occurred_at": "2023-01-10T08:00:00",
+            "location": "A6 Paris",
+            "amount_cents": 13500,
+            "currency": "eur",
+            "reference": "FR-123",
+            "due_on": "2023-02-10"
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Charge>()
+        .await
+        .unwrap();
+    let fine = owner
+        .put(&format!("{}/charges/{}/driver", car_url, fine.id))
+        .json(&serde_json::json!({ "driver_id": driver_id }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Charge>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!("EUR", fine.currency);
+    assert_eq!(Some(driver_id), fine.driver_id);
+
+    // Act
+    let outstanding = driver
+        .get(&format!("{}/api/charges/outstanding", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<Charge>>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(1, outstanding.len());
+
+    // Act
+    let response = driver
+        .post(&format!("{}/charges/{}/pay", car_url, fine.id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(403, response.status().as_u16());
+
+    // Act
+    let response = owner
+        .post(&format!("{}/charges/{}/pay", car_url, fine.id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+
+    // Act
+    let response = owner
+        .post(&format!("{}/charges/{}/pay", car_url, fine.id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(409, response.status().as_u16());
+
+    // Act
+    let outstanding = owner
+        .get(&format!("{}/charges?outstanding=true", car_url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<Charge>>()
+        .await
+        .unwrap();
+    // Assert
+    assert!(outstanding.is_empty());
+}
+
+#[tokio::test]
+async fn charges_are_validated() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let charges_url = format!("{}/api/cars/{}/charges", &app.address, car_id);
+
+    for body in [
+        serde_json::json!({
+            "kind": "toll",
+            "occurred_at": "2023-01-10T08:00:00",
+            "amount_cents": 850,
+            "currency": "euro"
+        }),
+        serde_json::json!({
+            "kind": "toll",
+            "occurred_at": "2023-01-10T08:00:00",
+            "amount_cents": 850,
+            "currency": "EUR",
+            "driver_id": uuid::Uuid::new_v4()
+        }),
+    ] {
+        // Act
+        let response = client
+            .post(&charges_url)
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        // Assert
+        assert_eq!(422, response.status().as_u16());
+    }
+}
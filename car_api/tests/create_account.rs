@@ -21,7 +21,7 @@ async fn create_returns_201_for_valid_form_data() {
             "bank_details": {
                 "account_holder": "toto",
                 "bank_country": "france",
-                "iban": "12345"
+                "iban": "FR76 3000 6000 0112 3456 7890 189"
             }
         }
     );
@@ -56,7 +56,7 @@ async fn create_returns_422_for_missing_data() {
             "bank_details": {
                 "account_holder": "toto",
                 "bank_country": "france",
-                "iban": "12345"
+                "iban": "FR76 3000 6000 0112 3456 7890 189"
             }
         }
     );
@@ -92,6 +92,6 @@ async fn create_returns_422_for_missing_data() {
 // 	"bank_details": {
 // 		"account_holder": "toto",
 // 		"bank_country": "france",
-// 		"iban": "12345"
+// 		"iban": "FR76 3000 6000 0112 3456 7890 189"
 // 	}
 // }'
@@ -0,0 +1,111 @@
+mod setup;
+
+use car_api::routes::document::Document;
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn documents_are_stored_encrypted() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "toto@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let url = format!("{}/api/cars/{}/documents", &app.address, car_id);
+    let content = b"%PDF-1.4 registration certificate".to_vec();
+
+    // Act
+    let response = client
+        .post(&format!(
+            "{}?kind=registration_certificate&file_name=carte_grise.pdf",
+            url
+        ))
+        .header("Content-Type", "application/pdf")
+        .body(content.clone())
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(201, response.status().as_u16());
+    let document = response.json::<Document>().await.unwrap();
+    assert_eq!(content.len() as i64, document.size_bytes);
+
+    let stored = std::fs::read(
+        storage_path()
+            .join("documents")
+            .join(car_id.to_string())
+            .join(document.id.to_string()),
+    )
+    .expect("Failed to read stored document.");
+    assert!(!stored.windows(4).any(|window| window == b"%PDF"));
+
+    // Act
+    let documents = client
+        .get(&url)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<Document>>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(1, documents.len());
+
+    // Act
+    let response = client
+        .get(&format!("{}/{}", url, document.id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!("application/pdf", response.headers()["content-type"]);
+    assert_eq!(content, response.bytes().await.unwrap().to_vec());
+
+    // Act
+    let response = client
+        .delete(&format!("{}/{}", url, document.id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(204, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn documents_reject_unsupported_content() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "toto@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let url = format!(
+        "{}/api/cars/{}/documents?kind=other&file_name=notes.txt",
+        &app.address, car_id
+    );
+
+    // Act
+    let response = client
+        .post(&url)
+        .header("Content-Type", "text/plain")
+        .body("some notes")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(415, response.status().as_u16());
+
+    // Act
+    let response = client
+        .post(&url)
+        .header("Content-Type", "image/png")
+        .body(vec![0u8; 10 * 1024 * 1024 + 1])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(413, response.status().as_u16());
+}
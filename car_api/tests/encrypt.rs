@@ -0,0 +1,127 @@
+mod setup;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use car_api::encrypt::{decrypt_data_with, encrypt_data_with, needs_reencryption};
+use car_api::errors::Error;
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn previous_formats_are_still_decrypted() {
+    // Arrange
+    let keyring = keyring(
+        "2026",
+        &[("2025", "previous secret"), ("2026", "rotated secret")],
+    )
+    .with_legacy_secret("legacy secret");
+
+    // Act
+    let pbkdf2 = decrypt_data_with(&keyring, PBKDF2_CIPHERTEXT).unwrap();
+    let legacy = decrypt_data_with(&keyring, LEGACY_CIPHERTEXT).unwrap();
+    let reencrypted = encrypt_data_with(&keyring, &pbkdf2).unwrap();
+
+    // Assert
+    assert_eq!("FR7630006000011234567890189", pbkdf2);
+    assert_eq!("FR7630006000011234567890189", legacy);
+    assert!(needs_reencryption(&keyring, PBKDF2_CIPHERTEXT));
+    assert!(needs_reencryption(&keyring, LEGACY_CIPHERTEXT));
+    assert!(!needs_reencryption(&keyring, &reencrypted));
+    assert!(reencrypted.len() < PBKDF2_CIPHERTEXT.len());
+}
+
+#[tokio::test]
+async fn records_get_their_own_key_and_are_authenticated() {
+    // Arrange
+    let keyring = keyring("aaaa", &[("aaaa", "same secret"), ("bbbb", "same secret")]);
+
+    // Act
+    let first = encrypt_data_with(&keyring, "FR7630006000011234567890189").unwrap();
+    let second = encrypt_data_with(&keyring, "FR7630006000011234567890189").unwrap();
+
+    // Assert
+    assert_ne!(first, second);
+    assert_eq!(
+        "FR7630006000011234567890189",
+        decrypt_data_with(&keyring, &second).unwrap()
+    );
+    let mut tampered = STANDARD.decode(&first).unwrap();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        decrypt_data_with(&keyring, &STANDARD.encode(&tampered)),
+        Err(Error::CiphertextAuthentication)
+    ));
+    // the key id is authenticated, even between keys with the same secret
+    let mut other_key = STANDARD.decode(&first).unwrap();
+    other_key[4..8].copy_from_slice(b"bbbb");
+    assert!(matches!(
+        decrypt_data_with(&keyring, &STANDARD.encode(&other_key)),
+        Err(Error::CiphertextAuthentication)
+    ));
+}
+
+#[tokio::test]
+async fn decryption_fails_closed() {
+    // Arrange
+    let keyring = keyring("2026", &[("2026", "rotated secret")]);
+    let encrypted = encrypt_data_with(&keyring, "FR7630006000011234567890189").unwrap();
+    let truncated = STANDARD.encode(&STANDARD.decode(encrypted).unwrap()[..30]);
+
+    // Act
+    let unknown_key = decrypt_data_with(&keyring, PBKDF2_CIPHERTEXT);
+    let without_legacy_secret = decrypt_data_with(&keyring, LEGACY_CIPHERTEXT);
+    let not_base64 = decrypt_data_with(&keyring, "FR7630006000011234567890189");
+    let truncated = decrypt_data_with(&keyring, &truncated);
+    let empty = decrypt_data_with(&keyring, "");
+    let short = decrypt_data_with(&keyring, "c2hvcnQ=");
+
+    // Assert
+    assert!(matches!(unknown_key, Err(Error::MissingEncryptionKey(_))));
+    assert!(matches!(
+        without_legacy_secret,
+        Err(Error::MissingEncryptionKey(_))
+    ));
+    assert!(matches!(not_base64, Err(Error::MalformedCiphertext(_))));
+    assert!(matches!(truncated, Err(Error::MalformedCiphertext(_))));
+    assert!(matches!(empty, Err(Error::MalformedCiphertext(_))));
+    assert!(matches!(short, Err(Error::MalformedCiphertext(_))));
+}
+
+#[tokio::test]
+async fn decryption_failures_are_not_told_apart_to_clients() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let bank_details_url = format!("{}/api/account/bank_details", &app.address);
+    let mut responses = Vec::new();
+
+    for iban in ["not base64!", "c2hvcnQ=", PBKDF2_CIPHERTEXT] {
+        sqlx::query("UPDATE bank_details SET iban=$1")
+            .bind(iban)
+            .execute(&app.pg_pool)
+            .await
+            .unwrap();
+
+        // Act
+        let response = client
+            .get(&bank_details_url)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        responses.push((response.status().as_u16(), response.text().await.unwrap()));
+    }
+
+    // Assert
+    assert_eq!(
+        vec![
+            (
+                500,
+                r#"{"message":"an internal encryption error occurred"}"#.to_string()
+            );
+            3
+        ],
+        responses
+    );
+}
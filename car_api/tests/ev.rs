@@ -0,0 +1,138 @@
+mod setup;
+
+use car_api::routes::ev::{BatteryHealth, ConnectorType, EvSpecs, SocReading};
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn battery_degradation_and_range_are_estimated() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let car_url = format!("{}/api/cars/{}", &app.address, car_id);
+
+    // Act
+    let specs = client
+        .put(&format!("{}/ev", car_url))
+        .json(&serde_json::json!({
+            "battery_capacity_kwh": 60.0,
+            "connector_types": ["type2", "ccs2", "type2"],
+            "max_ac_power_kw": 11.0,
+            "max_dc_power_kw": 150.0,
+            "wltp_range_km": 400
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<EvSpecs>()
+        .await
+        .unwrap();
+    let response = client
+        .post(&format!("{}/soc", car_url))
+        .json(&serde_json::json!({ "readings": [
+            { "recorded_at": "2021-01-15T10:00:00", "soc_percent": 80.0, "energy_kwh": 48.0 },
+            { "recorded_at": "2022-01-15T10:00:00", "soc_percent": 50.0, "energy_kwh": 28.5 },
+            { "recorded_at": "2023-01-15T10:00:00", "soc_percent": 50.0, "energy_kwh": 27.0 },
+            { "recorded_at": "2023-01-16T10:00:00", "soc_percent": 40.0 }
+        ]}))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(
+        vec![ConnectorType::Type2, ConnectorType::Ccs2],
+        specs.connector_types
+    );
+    assert_eq!(201, response.status().as_u16());
+    let car = &get_account_details(&app, &client).await.cars_info[0];
+    assert_eq!(Some(&specs), car.ev_specs.as_ref());
+
+    // Act
+    let health = client
+        .get(&format!("{}/battery", car_url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<BatteryHealth>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(Some(54.0), health.estimated_capacity_kwh);
+    assert_eq!(Some(90.0), health.state_of_health_percent);
+    let degradation = health.degradation_percent_per_year.unwrap();
+    assert!((degradation - 5.0).abs() < 0.1, "{}", degradation);
+    assert_eq!(3, health.monthly.len());
+    let range = health.latest_reading.unwrap().estimated_range_km.unwrap();
+    assert!((range - 144.0).abs() < 1e-9, "{}", range);
+
+    // Act
+    let readings = client
+        .get(&format!(
+            "{}/soc?from=2022-01-01T00:00:00&to=2023-01-16T00:00:00",
+            car_url
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<SocReading>>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(2, readings.len());
+    assert_eq!(Some(28.5), readings[0].energy_kwh);
+}
+
+#[tokio::test]
+async fn ev_data_is_validated() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let car_url = format!("{}/api/cars/{}", &app.address, car_id);
+
+    // Act
+    let response = client
+        .get(&format!("{}/battery", car_url))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(404, response.status().as_u16());
+
+    for (path, body) in [
+        (
+            "ev",
+            serde_json::json!({ "battery_capacity_kwh": 60.0, "connector_types": [] }),
+        ),
+        (
+            "ev",
+            serde_json::json!({ "battery_capacity_kwh": -1.0, "connector_types": ["ccs2"] }),
+        ),
+        (
+            "soc",
+            serde_json::json!({ "readings": [{ "recorded_at": "2023-01-15T10:00:00", "soc_percent": 120.0 }] }),
+        ),
+    ] {
+        // Act
+        let response = client
+            .request(
+                if path == "ev" {
+                    reqwest::Method::PUT
+                } else {
+                    reqwest::Method::POST
+                },
+                &format!("{}/{}", car_url, path),
+            )
+            .json(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        // Assert
+        assert_eq!(422, response.status().as_u16());
+    }
+}
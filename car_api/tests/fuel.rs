@@ -0,0 +1,88 @@
+mod setup;
+
+use car_api::routes::fuel::{ConsumptionStats, EnergyType, MonthlyFuelTotal};
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn consumption_counts_partial_fills_between_full_fills() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "toto@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let url = format!("{}/api/cars/{}/fuel", &app.address, car_id);
+
+    for entry in [
+        // partial fill before the first full one is ignored
+        serde_json::json!({ "energy_type": "fuel", "quantity": 10.0, "price_cents": 1800, "odometer": 900, "full_tank": false, "filled_at": "2023-01-02T10:00:00" }),
+        serde_json::json!({ "energy_type": "fuel", "quantity": 40.0, "price_cents": 7200, "odometer": 1000, "filled_at": "2023-01-05T10:00:00" }),
+        serde_json::json!({ "energy_type": "fuel", "quantity": 20.0, "price_cents": 3600, "odometer": 1300, "full_tank": false, "filled_at": "2023-01-20T10:00:00" }),
+        serde_json::json!({ "energy_type": "fuel", "quantity": 15.0, "price_cents": 2700, "odometer": 1500, "filled_at": "2023-02-03T10:00:00" }),
+        // partial fill after the last full one is ignored
+        serde_json::json!({ "energy_type": "fuel", "quantity": 30.0, "price_cents": 5400, "odometer": 1800, "full_tank": false, "filled_at": "2023-02-20T10:00:00" }),
+    ] {
+        let response = client
+            .post(&url)
+            .json(&entry)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(201, response.status().as_u16());
+    }
+
+    // Act
+    let stats = client
+        .get(&format!("{}/consumption", url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<ConsumptionStats>>()
+        .await
+        .expect("Failed to parse consumption.");
+
+    // Assert
+    assert_eq!(1, stats.len());
+    assert_eq!(EnergyType::Fuel, stats[0].energy_type);
+    assert_eq!(500, stats[0].distance_km);
+    assert_eq!(Some(7.0), stats[0].consumption_per_100km);
+    assert_eq!(Some(12.6), stats[0].cost_per_km_cents);
+
+    // Act
+    let monthly = client
+        .get(&format!("{}/monthly", url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<MonthlyFuelTotal>>()
+        .await
+        .expect("Failed to parse monthly totals.");
+
+    // Assert
+    assert_eq!(2, monthly.len());
+    assert_eq!("2023-02-01", monthly[0].month.to_string());
+    assert_eq!(8100, monthly[0].cost_cents);
+    assert_eq!(3, monthly[1].entries);
+}
+
+#[tokio::test]
+async fn fuel_entry_rejects_empty_quantity() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "toto@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+
+    // Act
+    let response = client
+        .post(&format!("{}/api/cars/{}/fuel", &app.address, car_id))
+        .json(&serde_json::json!({ "energy_type": "electric", "quantity": 0.0, "price_cents": 0, "odometer": 10 }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(422, response.status().as_u16());
+}
@@ -0,0 +1,180 @@
+mod setup;
+
+use car_api::routes::geofence::{Geofence, GeofenceEvent, GeofenceEventKind};
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn crossing_a_geofence_records_events() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let car_url = format!("{}/api/cars/{}", &app.address, car_id);
+
+    let depot = client
+        .post(&format!("{}/api/geofences", &app.address))
+        .json(&serde_json::json!({
+            "name": "depot",
+            "kind": "circle",
+            "center": { "latitude": 48.8566, "longitude": 2.3522 },
+            "radius_m": 500.0
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Geofence>()
+        .await
+        .unwrap();
+    let response = client
+        .post(&format!("{}/api/geofences", &app.address))
+        .json(&serde_json::json!({
+            "name": "city",
+            "kind": "polygon",
+            "vertices": [
+                { "latitude": 45.7, "longitude": 4.8 },
+                { "latitude": 45.8, "longitude": 4.8 },
+                { "latitude": 45.8, "longitude": 4.9 },
+                { "latitude": 45.7, "longitude": 4.9 }
+            ]
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(201, response.status().as_u16());
+
+    // Act
+    let events = client
+        .post(&format!("{}/locations", car_url))
+        .json(&serde_json::json!({
+            "points": [
+                { "latitude": 48.8566, "longitude": 2.3522, "recorded_at": "2023-01-10T08:00:00" },
+                { "latitude": 48.87, "longitude": 2.3522, "recorded_at": "2023-01-10T08:10:00" }
+            ]
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<GeofenceEvent>>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(1, events.len());
+    assert_eq!(GeofenceEventKind::Exit, events[0].kind);
+    assert_eq!(depot.id, events[0].geofence_id);
+
+    // Act
+    let response = client
+        .post(&format!("{}/trips", car_url))
+        .json(&serde_json::json!({
+            "started_at": "2023-01-10T09:00:00",
+            "points": [
+                { "latitude": 45.75, "longitude": 4.85, "recorded_at": "2023-01-10T12:00:00" },
+                { "latitude": 48.8566, "longitude": 2.3522, "recorded_at": "2023-01-10T17:00:00" }
+            ]
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(201, response.status().as_u16());
+
+    // Act
+    let events = client
+        .get(&format!(
+            "{}/api/geofences/events?car_id={}&geofence_id={}&kind=enter",
+            &app.address, car_id, depot.id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<GeofenceEvent>>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(1, events.len());
+    assert_eq!(depot.id, events[0].geofence_id);
+
+    // Act
+    let events = client
+        .get(&format!(
+            "{}/api/geofences/events?from=2023-01-10T08:30:00",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<GeofenceEvent>>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(3, events.len());
+    assert_eq!(GeofenceEventKind::Enter, events[2].kind);
+    assert_ne!(depot.id, events[2].geofence_id);
+}
+
+#[tokio::test]
+async fn geofences_are_validated_and_private() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+
+    let other = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &other, "other@email.com").await;
+
+    // Act
+    let response = client
+        .post(&format!("{}/api/geofences", &app.address))
+        .json(&serde_json::json!({
+            "name": "line",
+            "kind": "polygon",
+            "vertices": [
+                { "latitude": 45.7, "longitude": 4.8 },
+                { "latitude": 45.8, "longitude": 4.8 }
+            ]
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(422, response.status().as_u16());
+
+    // Act
+    let geofence = client
+        .post(&format!("{}/api/geofences", &app.address))
+        .json(&serde_json::json!({
+            "name": "depot",
+            "kind": "circle",
+            "center": { "latitude": 48.8566, "longitude": 2.3522 },
+            "radius_m": 500.0
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Geofence>()
+        .await
+        .unwrap();
+    let response = other
+        .delete(&format!("{}/api/geofences/{}", &app.address, geofence.id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(404, response.status().as_u16());
+
+    // Act
+    let geofences = other
+        .get(&format!("{}/api/geofences", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<Geofence>>()
+        .await
+        .unwrap();
+    // Assert
+    assert!(geofences.is_empty());
+}
@@ -49,7 +49,7 @@ async fn create_account(app: &TestApp, client: &Client) -> Response {
             "bank_details": {
                 "account_holder": "toto",
                 "bank_country": "france",
-                "iban": "12345"
+                "iban": "FR76 3000 6000 0112 3456 7890 189"
             }
         }
     );
@@ -0,0 +1,121 @@
+mod setup;
+
+use car_api::routes::bank_details::RevealedIban;
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+async fn reauthenticate(app: &TestApp, client: &Client, password: &str) -> u16 {
+    client
+        .post(&format!("{}/api/account/reauthenticate", &app.address))
+        .json(&serde_json::json!({ "password": password }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn iban_is_revealed_after_reauthentication() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let bank_details = get_account_details(&app, &client).await.bank_details[0].clone();
+    let reveal_url = format!(
+        "{}/api/account/bank_details/{}/reveal",
+        &app.address, bank_details.id
+    );
+
+    // Act
+    let without_reauthentication = client
+        .post(&reveal_url)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let wrong_password = reauthenticate(&app, &client, "not my password").await;
+    let right_password = reauthenticate(&app, &client, "my super password").await;
+    let revealed = client
+        .post(&reveal_url)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<RevealedIban>()
+        .await
+        .unwrap();
+    let other_session = Client::builder().cookie_store(true).build().unwrap();
+    login(&app, &other_session, "email@email.com").await;
+    let in_other_session = other_session
+        .post(&reveal_url)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!("FR76 **** **** 0189", bank_details.iban);
+    assert_eq!(403, without_reauthentication.status().as_u16());
+    assert_eq!(403, wrong_password);
+    assert_eq!(204, right_password);
+    assert_eq!("FR7630006000011234567890189", revealed.iban);
+    assert_eq!(403, in_other_session.status().as_u16());
+
+    // Arrange
+    sqlx::query("UPDATE reauthentications SET created_at = created_at - interval '10 minutes'")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = client
+        .post(&reveal_url)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(403, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn reveals_and_reauthentications_are_rate_limited() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let bank_details_id = get_account_details(&app, &client).await.bank_details[0].id;
+    let reveal_url = format!(
+        "{}/api/account/bank_details/{}/reveal",
+        &app.address, bank_details_id
+    );
+    assert_eq!(
+        204,
+        reauthenticate(&app, &client, "my super password").await
+    );
+
+    // Act
+    let mut statuses = Vec::new();
+    for _ in 0..6 {
+        let response = client
+            .post(&reveal_url)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        statuses.push(response.status().as_u16());
+    }
+
+    // Assert
+    assert_eq!(vec![200, 200, 200, 200, 200, 429], statuses);
+
+    // Act
+    let guesses = (0..10).map(|_| reauthenticate(&app, &client, "guess"));
+    let mut statuses = futures_util::future::join_all(guesses).await;
+    let blocked = reauthenticate(&app, &client, "my super password").await;
+
+    // Assert
+    statuses.sort();
+    assert_eq!(vec![403; 5], statuses[..5]);
+    assert_eq!(vec![429; 5], statuses[5..]);
+    assert_eq!(429, blocked);
+}
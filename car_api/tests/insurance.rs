@@ -0,0 +1,142 @@
+mod setup;
+
+use car_api::routes::insurance::{ExpiringPolicy, InsurancePolicy};
+
+use crate::setup::*;
+
+use chrono::{Duration, Utc};
+use reqwest::Client;
+
+#[tokio::test]
+async fn expiring_policies_ignore_renewed_ones() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "toto@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let url = format!("{}/api/cars/{}/insurance", &app.address, car_id);
+    let today = Utc::now().date_naive();
+
+    let policy = |starts_on: chrono::NaiveDate, ends_on: chrono::NaiveDate| {
+        serde_json::json!({
+            "insurer": "assur",
+            "policy_number": "POL-123456",
+            "coverage_type": "comprehensive",
+            "starts_on": starts_on,
+            "ends_on": ends_on,
+            "premium_cents": 60000
+        })
+    };
+
+    // Act
+    let response = client
+        .post(&url)
+        .json(&policy(
+            today - Duration::days(355),
+            today + Duration::days(10),
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(201, response.status().as_u16());
+    let created = response.json::<InsurancePolicy>().await.unwrap();
+    assert_eq!("POL-123456", created.policy_number);
+
+    let stored: (String,) = sqlx::query_as("SELECT policy_number FROM insurance_policies")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap();
+    assert_ne!("POL-123456", stored.0);
+
+    // Act
+    let expiring = client
+        .get(&format!(
+            "{}/api/insurance/expiring?within_days=30",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<ExpiringPolicy>>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(1, expiring.len());
+    assert_eq!(10, expiring[0].days_left);
+
+    // Act
+    let response = client
+        .post(&url)
+        .json(&policy(
+            today + Duration::days(11),
+            today + Duration::days(376),
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(201, response.status().as_u16());
+    let expiring = client
+        .get(&format!(
+            "{}/api/insurance/expiring?within_days=30",
+            &app.address
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<ExpiringPolicy>>()
+        .await
+        .unwrap();
+    // Assert
+    assert!(expiring.is_empty());
+}
+
+#[tokio::test]
+async fn insurance_policy_rejects_inverted_dates() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "toto@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+
+    // Act
+    let response = client
+        .post(&format!("{}/api/cars/{}/insurance", &app.address, car_id))
+        .json(&serde_json::json!({
+            "insurer": "assur",
+            "policy_number": "POL-123456",
+            "coverage_type": "third_party",
+            "starts_on": "2023-06-01",
+            "ends_on": "2023-01-01",
+            "premium_cents": 60000
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(422, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn expiring_policies_window_is_bounded() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "toto@email.com").await;
+
+    for within_days in ["-1", "3651", "2147483647"] {
+        // Act
+        let response = client
+            .get(&format!(
+                "{}/api/insurance/expiring?within_days={}",
+                &app.address, within_days
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+
+        // Assert
+        assert_eq!(422, response.status().as_u16());
+    }
+}
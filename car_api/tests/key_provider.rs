@@ -0,0 +1,203 @@
+mod setup;
+
+use axum::{
+    extract::Path,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use car_api::encrypt::{decrypt_data_with, encrypt_data_with, needs_reencryption, DEFAULT_KEY_ID};
+use car_api::key_provider::{
+    EnvKeyProvider, KeyProvider, SecretFileKeyProvider, VaultTransitKeyProvider,
+};
+
+use crate::setup::*;
+
+use std::net::TcpListener;
+
+const VAULT_TOKEN: &str = "vault token";
+
+/// Answers `/v1/transit/decrypt/car_api` like Vault for the ciphertexts `vault:v1:<plaintext>`.
+async fn transit_decrypt(
+    Path(key_name): Path<String>,
+    headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    if headers
+        .get("X-Vault-Token")
+        .and_then(|token| token.to_str().ok())
+        != Some(VAULT_TOKEN)
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if key_name != "car_api" {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    match body["ciphertext"]
+        .as_str()
+        .and_then(|c| c.strip_prefix("vault:v1:"))
+    {
+        Some(plaintext) => Ok(Json(serde_json::json!({
+            "data": { "plaintext": plaintext }
+        }))),
+        None => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+fn spawn_vault() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
+    let port = listener.local_addr().unwrap().port();
+    let app = Router::new().route("/v1/transit/decrypt/:key_name", post(transit_decrypt));
+    let server = axum::Server::from_tcp(listener)
+        .unwrap()
+        .serve(app.into_make_service());
+    let _ = tokio::spawn(server);
+
+    format!("http://127.0.0.1:{}", port)
+}
+
+fn key_list(keys: &[(&str, &str)]) -> Vec<(String, String)> {
+    keys.iter()
+        .map(|(key_id, ciphertext)| (key_id.to_string(), ciphertext.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn keys_are_read_from_secret_files() {
+    // Arrange
+    let directory = std::env::temp_dir().join(format!("car_api_keys_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(directory.join("..data")).unwrap();
+    std::fs::write(directory.join("2025"), "previous secret").unwrap();
+    std::fs::write(directory.join("2026"), "rotated secret\n").unwrap();
+    std::fs::write(directory.join(".hidden"), "not a key").unwrap();
+
+    // Act
+    let from_files = SecretFileKeyProvider::new(&directory)
+        .with_encryption_key_id("2026")
+        .keyring()
+        .await
+        .unwrap();
+    let without_key_id = SecretFileKeyProvider::new(&directory).keyring().await;
+    let missing = SecretFileKeyProvider::new(directory.join("missing"))
+        .keyring()
+        .await;
+
+    // Assert
+    let encrypted = encrypt_data_with(&from_files, "FR7630006000011234567890189").unwrap();
+    let rotated = keyring("2026", &[("2026", "rotated secret")]);
+    assert!(!needs_reencryption(&rotated, &encrypted));
+    assert_eq!(
+        "FR7630006000011234567890189",
+        decrypt_data_with(&rotated, &encrypted).unwrap()
+    );
+    let previous = keyring("2025", &[("2025", "previous secret")]);
+    let encrypted = encrypt_data_with(&previous, "FR7630006000011234567890189").unwrap();
+    assert!(decrypt_data_with(&from_files, &encrypted).is_ok());
+    assert!(without_key_id.is_err());
+    assert!(missing.is_err());
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test]
+async fn keys_are_unwrapped_by_vault_transit() {
+    // Arrange
+    let address = spawn_vault();
+    let keys = key_list(&[
+        ("2025", "vault:v1:cHJldmlvdXM="),
+        ("2026", "vault:v1:cm90YXRlZA=="),
+    ]);
+
+    // Act
+    let from_vault = VaultTransitKeyProvider::new(&address, VAULT_TOKEN, "car_api", keys.clone())
+        .with_encryption_key_id("2026")
+        .keyring()
+        .await
+        .unwrap();
+    let forbidden = VaultTransitKeyProvider::new(&address, "wrong token", "car_api", keys.clone())
+        .keyring()
+        .await;
+    let unknown_mount = VaultTransitKeyProvider::new(&address, VAULT_TOKEN, "car_api", keys)
+        .with_mount("secret")
+        .keyring()
+        .await;
+
+    // Assert
+    let encrypted = encrypt_data_with(&from_vault, "FR7630006000011234567890189").unwrap();
+    let unwrapped = keyring(
+        "2026",
+        &[("2025", "cHJldmlvdXM="), ("2026", "cm90YXRlZA==")],
+    );
+    assert!(!needs_reencryption(&unwrapped, &encrypted));
+    assert_eq!(
+        "FR7630006000011234567890189",
+        decrypt_data_with(&unwrapped, &encrypted).unwrap()
+    );
+    assert!(forbidden.is_err());
+    assert!(unknown_mount.is_err());
+}
+
+#[tokio::test]
+async fn listed_keys_still_decrypt_the_values_of_the_encryption_secret() {
+    // Arrange
+    let previous = keyring(DEFAULT_KEY_ID, &[(DEFAULT_KEY_ID, "previous secret")]);
+    let encrypted = encrypt_data_with(&previous, "FR7630006000011234567890189").unwrap();
+    std::env::set_var("ENCRYPTION_SECRET", "previous secret");
+    std::env::set_var("ENCRYPTION_KEYS", "2026:rotated secret");
+    std::env::remove_var("ENCRYPTION_KEY_ID");
+
+    // Act
+    let keyring = EnvKeyProvider.keyring().await.unwrap();
+
+    // Assert
+    assert_eq!("2026", keyring.encryption_key_id());
+    assert_eq!(
+        "FR7630006000011234567890189",
+        decrypt_data_with(&keyring, &encrypted).unwrap()
+    );
+    assert!(needs_reencryption(&keyring, &encrypted));
+}
+
+#[tokio::test]
+async fn providers_still_decrypt_the_values_of_the_default_secret() {
+    // Arrange
+    let directory = std::env::temp_dir().join(format!("car_api_keys_{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("2026"), "rotated secret").unwrap();
+    let address = spawn_vault();
+    let previous = keyring(DEFAULT_KEY_ID, &[(DEFAULT_KEY_ID, "legacy secret")]);
+    let encrypted = encrypt_data_with(&previous, "FR7630006000011234567890189").unwrap();
+
+    // Act
+    let from_files = SecretFileKeyProvider::new(&directory)
+        .with_default_secret("legacy secret")
+        .keyring()
+        .await
+        .unwrap();
+    let from_vault = VaultTransitKeyProvider::new(
+        &address,
+        VAULT_TOKEN,
+        "car_api",
+        key_list(&[("2026", "vault:v1:cm90YXRlZA==")]),
+    )
+    .with_default_secret("legacy secret")
+    .keyring()
+    .await
+    .unwrap();
+
+    // Assert
+    for keyring in [from_files, from_vault] {
+        assert_eq!("2026", keyring.encryption_key_id());
+        assert_eq!(
+            "FR7630006000011234567890189",
+            decrypt_data_with(&keyring, &encrypted).unwrap()
+        );
+        assert_eq!(
+            "FR7630006000011234567890189",
+            decrypt_data_with(&keyring, LEGACY_CIPHERTEXT).unwrap()
+        );
+    }
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
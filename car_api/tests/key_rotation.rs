@@ -0,0 +1,92 @@
+mod setup;
+
+use car_api::encrypt::{
+    decrypt_data_with, encrypt_data_with, needs_reencryption, Keyring, DEFAULT_KEY_ID,
+};
+use car_api::errors::Error;
+use car_api::routes::bank_details::reencrypt_bank_details;
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn values_are_decrypted_with_the_key_of_their_header() {
+    // Arrange
+    let previous = keyring("2025", &[("2025", "previous secret")]);
+    let rotated = keyring(
+        "2026",
+        &[("2025", "previous secret"), ("2026", "rotated secret")],
+    )
+    .with_legacy_secret("legacy secret");
+    let encrypted = encrypt_data_with(&previous, "FR7630006000011234567890189").unwrap();
+
+    // Act
+    let decrypted = decrypt_data_with(&rotated, &encrypted).unwrap();
+    let reencrypted = encrypt_data_with(&rotated, &decrypted).unwrap();
+    let legacy = decrypt_data_with(&rotated, LEGACY_CIPHERTEXT).unwrap();
+
+    // Assert
+    assert_eq!("FR7630006000011234567890189", decrypted);
+    assert!(needs_reencryption(&rotated, &encrypted));
+    assert!(!needs_reencryption(&rotated, &reencrypted));
+    assert_eq!("FR7630006000011234567890189", legacy);
+    assert!(needs_reencryption(&rotated, LEGACY_CIPHERTEXT));
+    assert!(decrypt_data_with(&previous, &reencrypted).is_err());
+    assert!(decrypt_data_with(&previous, LEGACY_CIPHERTEXT).is_err());
+    assert!(matches!(
+        Keyring::new("2027", vec![("2026".into(), "secret".into())]),
+        Err(Error::MissingEncryptionKey(_))
+    ));
+    assert!(matches!(
+        Keyring::new("a:b", vec![("a:b".into(), "secret".into())]),
+        Err(Error::MissingEncryptionKey(_))
+    ));
+}
+
+#[tokio::test]
+async fn bank_details_are_reencrypted_with_the_new_key() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    client
+        .post(&format!("{}/api/account/bank_details", &app.address))
+        .json(&serde_json::json!({
+            "account_holder": "toto",
+            "bank_country": "DE",
+            "iban": "DE89 3704 0044 0532 0130 00"
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let secret = std::env::var("ENCRYPTION_SECRET").unwrap();
+    let rotated = keyring(
+        "2026",
+        &[(DEFAULT_KEY_ID, &secret), ("2026", "rotated secret")],
+    );
+
+    // Act
+    let reencrypted = reencrypt_bank_details(&app.pg_pool, &rotated, 1)
+        .await
+        .unwrap();
+    let reencrypted_again = reencrypt_bank_details(&app.pg_pool, &rotated, 1)
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(2, reencrypted);
+    assert_eq!(0, reencrypted_again);
+    let ibans: Vec<(String,)> = sqlx::query_as("SELECT iban FROM bank_details ORDER BY created_at")
+        .fetch_all(&app.pg_pool)
+        .await
+        .unwrap();
+    let only_rotated = keyring("2026", &[("2026", "rotated secret")]);
+    assert_eq!(
+        vec!["FR7630006000011234567890189", "DE89370400440532013000"],
+        ibans
+            .iter()
+            .map(|(iban,)| decrypt_data_with(&only_rotated, iban).unwrap())
+            .collect::<Vec<_>>()
+    );
+}
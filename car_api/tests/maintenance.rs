@@ -0,0 +1,131 @@
+mod setup;
+
+use car_api::routes::maintenance::{ServiceDue, ServiceStatus};
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn due_services_are_computed_from_history_and_mileage() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "toto@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let car_url = format!("{}/api/cars/{}", &app.address, car_id);
+
+    for interval in [
+        serde_json::json!({ "service_type": "oil_change", "interval_km": 15000 }),
+        serde_json::json!({ "service_type": "inspection", "interval_months": 24 }),
+        serde_json::json!({ "service_type": "brakes", "interval_km": 50000 }),
+    ] {
+        let response = client
+            .put(&format!("{}/service_intervals", car_url))
+            .json(&interval)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_eq!(200, response.status().as_u16());
+    }
+
+    let response = client
+        .post(&format!("{}/maintenance", car_url))
+        .json(&serde_json::json!({
+            "service_type": "oil_change",
+            "performed_on": "2020-01-10",
+            "mileage": 10000,
+            "cost_cents": 8900,
+            "garage": "garage du coin"
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(201, response.status().as_u16());
+
+    // a later record without mileage does not reset the km interval
+    let response = client
+        .post(&format!("{}/maintenance", car_url))
+        .json(&serde_json::json!({
+            "service_type": "oil_change",
+            "performed_on": "2020-06-10",
+            "garage": "garage du coin"
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(201, response.status().as_u16());
+
+    let response = client
+        .post(&format!("{}/odometer", car_url))
+        .json(&serde_json::json!({ "mileage": 24500 }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(201, response.status().as_u16());
+
+    // Act
+    let due = client
+        .get(&format!("{}/services/due?within_km=1000", car_url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<ServiceDue>>()
+        .await
+        .expect("Failed to parse due services.");
+
+    // Assert
+    // the car was just registered so the inspection is not due and brakes are far away
+    assert_eq!(1, due.len());
+    assert_eq!("oil_change", due[0].service_type);
+    assert_eq!(ServiceStatus::Upcoming, due[0].status);
+    assert_eq!(Some(25000), due[0].due_mileage);
+    assert_eq!(Some(10000), due[0].last_mileage);
+
+    // Act
+    let due = client
+        .get(&format!("{}/services/due?within_days=800", car_url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<ServiceDue>>()
+        .await
+        .expect("Failed to parse due services.");
+
+    // Assert
+    assert_eq!(2, due.len());
+    assert_eq!("inspection", due[1].service_type);
+}
+
+#[tokio::test]
+async fn service_interval_requires_time_or_distance() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "toto@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+
+    // Act
+    let response = client
+        .put(&format!(
+            "{}/api/cars/{}/service_intervals",
+            &app.address, car_id
+        ))
+        .json(&serde_json::json!({ "service_type": "oil_change" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let blank_service_type = client
+        .put(&format!(
+            "{}/api/cars/{}/service_intervals",
+            &app.address, car_id
+        ))
+        .json(&serde_json::json!({ "service_type": "   ", "interval_km": 15000 }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(422, response.status().as_u16());
+    assert_eq!(422, blank_service_type.status().as_u16());
+}
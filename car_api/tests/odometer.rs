@@ -0,0 +1,86 @@
+mod setup;
+
+use car_api::routes::odometer::OdometerReading;
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn odometer_readings_must_increase() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "toto@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let url = format!("{}/api/cars/{}/odometer", &app.address, car_id);
+
+    // Act
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "mileage": 1000 }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(201, response.status().as_u16());
+
+    // Act
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "mileage": 900 }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(409, response.status().as_u16());
+
+    // Act
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "mileage": 10, "meter_replacement": true }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(201, response.status().as_u16());
+
+    // Act
+    let readings = client
+        .get(&url)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<OdometerReading>>()
+        .await
+        .expect("Failed to parse readings.");
+    // Assert
+    assert_eq!(2, readings.len());
+    assert_eq!(10, readings[0].mileage);
+
+    let details = get_account_details(&app, &client).await;
+    assert_eq!(Some(10), details.cars_info[0].current_mileage);
+}
+
+#[tokio::test]
+async fn odometer_rejects_other_users_car() {
+    // Arrange
+    let app = spawn_app().await;
+    let owner = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &owner, "owner@email.com").await;
+    let car_id = get_account_details(&app, &owner).await.cars_info[0].id;
+
+    let other = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &other, "other@email.com").await;
+
+    // Act
+    let response = other
+        .post(&format!("{}/api/cars/{}/odometer", &app.address, car_id))
+        .json(&serde_json::json!({ "mileage": 1000 }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(404, response.status().as_u16());
+}
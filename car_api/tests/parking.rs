@@ -0,0 +1,115 @@
+mod setup;
+
+use car_api::routes::parking::{purge_location_history, ParkedLocation};
+
+use crate::setup::*;
+
+use chrono::{Duration, Utc};
+use reqwest::Client;
+
+#[tokio::test]
+async fn latest_parked_location_is_in_car_info() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let parking_url = format!("{}/api/cars/{}/parking", &app.address, car_id);
+
+    // Act
+    let response = client
+        .get(&parking_url)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(404, response.status().as_u16());
+    assert!(get_account_details(&app, &client).await.cars_info[0]
+        .location
+        .is_none());
+
+    // Act
+    let response = client
+        .post(&parking_url)
+        .json(&serde_json::json!({
+            "latitude": 48.8566,
+            "longitude": 2.3522,
+            "photo_document_id": uuid::Uuid::new_v4()
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(422, response.status().as_u16());
+
+    // Act
+    for (level, parked_at) in [("-2", "2023-01-10T08:00:00"), ("-3", "2023-01-11T08:00:00")] {
+        let response = client
+            .post(&parking_url)
+            .json(&serde_json::json!({
+                "latitude": 48.8566,
+                "longitude": 2.3522,
+                "level": level,
+                "spot_note": "next to the lift",
+                "meter_expires_at": "2023-01-11T10:00:00",
+                "parked_at": parked_at
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        // Assert
+        assert_eq!(201, response.status().as_u16());
+    }
+
+    // Act
+    let location = client
+        .get(&parking_url)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<ParkedLocation>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(Some("-3".to_owned()), location.level);
+    let details = get_account_details(&app, &client).await;
+    assert_eq!(Some(location), details.cars_info[0].location);
+}
+
+#[tokio::test]
+async fn old_locations_are_purged_except_the_latest() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let parking_url = format!("{}/api/cars/{}/parking", &app.address, car_id);
+
+    for days_ago in [60, 45, 40] {
+        client
+            .post(&parking_url)
+            .json(&serde_json::json!({
+                "latitude": 48.8566,
+                "longitude": 2.3522,
+                "parked_at": (Utc::now() - Duration::days(days_ago)).naive_utc()
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+    }
+
+    // Act
+    let deleted = purge_location_history(&app.pg_pool, 30).await.unwrap();
+    let history = client
+        .get(&format!("{}/history", parking_url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<ParkedLocation>>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(2, deleted);
+    assert_eq!(1, history.len());
+    assert!(history[0].parked_at > (Utc::now() - Duration::days(41)).naive_utc());
+}
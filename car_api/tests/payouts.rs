@@ -0,0 +1,192 @@
+mod setup;
+
+use car_api::routes::bank_details::BankDetailsInfo;
+use car_api::routes::sepa::Payout;
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+async fn add_bank_details(app: &TestApp, client: &Client) -> BankDetailsInfo {
+    client
+        .post(&format!("{}/api/account/bank_details", &app.address))
+        .json(&serde_json::json!({
+            "account_holder": "toto",
+            "bank_country": "DE",
+            "iban": "DE89 3704 0044 0532 0130 00",
+            "bic": "COBADEFFXXX"
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn create_payout(app: &TestApp, body: serde_json::Value) -> reqwest::Response {
+    Client::new()
+        .post(&format!("{}/api/sepa/payouts", &app.address))
+        .bearer_auth(SEPA_EXPORT_TOKEN)
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn payouts_are_exported_as_pain001() {
+    // Arrange
+    configure_sepa_company();
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let user_id = get_account_details(&app, &client).await.user.id;
+    let second = add_bank_details(&app, &client).await;
+
+    let first = create_payout(
+        &app,
+        serde_json::json!({
+            "user_id": user_id,
+            "amount_cents": 4200,
+            "remittance_information": "Fuel <march>",
+            "execution_date": "2030-01-10"
+        }),
+    )
+    .await
+    .json::<Payout>()
+    .await
+    .unwrap();
+    create_payout(
+        &app,
+        serde_json::json!({
+            "user_id": user_id,
+            "bank_details_id": second.id,
+            "amount_cents": 1999,
+            "remittance_information": "Tolls",
+            "execution_date": "2030-01-10"
+        }),
+    )
+    .await;
+    create_payout(
+        &app,
+        serde_json::json!({
+            "user_id": user_id,
+            "amount_cents": 500,
+            "remittance_information": "Parking",
+            "execution_date": "2030-02-10"
+        }),
+    )
+    .await;
+
+    // Act
+    let response = Client::new()
+        .post(&format!("{}/api/sepa/transfers", &app.address))
+        .bearer_auth(SEPA_EXPORT_TOKEN)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let document = response.text().await.unwrap();
+    assert_valid_xml(&document, "pain.001.001.03.xsd");
+    let xml = roxmltree::Document::parse(&document).unwrap();
+    let text = |name: &str| -> Vec<&str> {
+        xml.descendants()
+            .filter(|node| node.has_tag_name(name))
+            .filter_map(|node| node.text())
+            .collect()
+    };
+    assert_eq!(vec!["3", "2", "1"], text("NbOfTxs"));
+    assert_eq!(vec!["66.99", "61.99", "5.00"], text("CtrlSum"));
+    assert_eq!(vec!["2030-01-10", "2030-02-10"], text("ReqdExctnDt"));
+    assert_eq!(first.id.simple().to_string(), text("EndToEndId")[0]);
+    assert_eq!(
+        vec![
+            "DE89370400440532013000",
+            "FR7630006000011234567890189",
+            "DE89370400440532013000",
+            "DE89370400440532013000",
+            "FR7630006000011234567890189"
+        ],
+        text("IBAN")
+    );
+    assert_eq!(vec!["Fuel <march>", "Tolls", "Parking"], text("Ustrd"));
+
+    // Act
+    let payouts = client
+        .get(&format!("{}/api/account/payouts", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<Payout>>()
+        .await
+        .unwrap();
+    let response = Client::new()
+        .post(&format!("{}/api/sepa/transfers", &app.address))
+        .bearer_auth(SEPA_EXPORT_TOKEN)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(3, payouts.len());
+    assert!(payouts.iter().all(|payout| payout.batch_id.is_some()));
+    assert_eq!(404, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn payouts_are_only_paid_to_the_accounts_of_the_user() {
+    // Arrange
+    configure_sepa_company();
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let user_id = get_account_details(&app, &client).await.user.id;
+    let other_client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &other_client, "other@email.com").await;
+    let other_bank_details = add_bank_details(&app, &other_client).await;
+    let payout = |bank_details_id: Option<uuid::Uuid>, amount_cents: i64| {
+        serde_json::json!({
+            "user_id": user_id,
+            "bank_details_id": bank_details_id,
+            "amount_cents": amount_cents,
+            "remittance_information": "Fuel",
+            "execution_date": "2030-01-10"
+        })
+    };
+
+    // Act
+    let other_account = create_payout(&app, payout(Some(other_bank_details.id), 100)).await;
+    let empty = create_payout(&app, payout(None, 0)).await;
+    let mut in_the_past = payout(None, 100);
+    in_the_past["execution_date"] = "2020-01-10".into();
+    let in_the_past = create_payout(&app, in_the_past).await;
+    let unauthorized = Client::new()
+        .post(&format!("{}/api/sepa/payouts", &app.address))
+        .json(&payout(None, 100))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let pending = create_payout(&app, payout(None, 100))
+        .await
+        .json::<Payout>()
+        .await
+        .unwrap();
+    let delete = client
+        .delete(&format!(
+            "{}/api/account/bank_details/{}",
+            &app.address, pending.bank_details_id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(404, other_account.status().as_u16());
+    assert_eq!(422, empty.status().as_u16());
+    assert_eq!(422, in_the_past.status().as_u16());
+    assert_eq!(403, unauthorized.status().as_u16());
+    assert_eq!(409, delete.status().as_u16());
+}
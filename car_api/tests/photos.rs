@@ -0,0 +1,202 @@
+mod setup;
+
+use car_api::routes::photo::Photo;
+
+use crate::setup::*;
+
+use image::{DynamicImage, ImageOutputFormat, RgbImage};
+use reqwest::Client;
+use std::io::Cursor;
+
+fn encode_image(width: u32, height: u32, format: ImageOutputFormat) -> Vec<u8> {
+    let mut content = Cursor::new(Vec::new());
+    DynamicImage::ImageRgb8(RgbImage::new(width, height))
+        .write_to(&mut content, format)
+        .unwrap();
+    content.into_inner()
+}
+
+/// A small jpeg whose header claims the given dimensions.
+fn jpeg_with_dimensions(width: u16, height: u16) -> Vec<u8> {
+    let mut content = encode_image(16, 16, ImageOutputFormat::Jpeg(90));
+    // the start of frame holds its length, the precision then the height and width
+    let sof = content
+        .windows(2)
+        .position(|marker| marker == [0xFF, 0xC0])
+        .unwrap();
+    content[sof + 5..sof + 7].copy_from_slice(&height.to_be_bytes());
+    content[sof + 7..sof + 9].copy_from_slice(&width.to_be_bytes());
+    content
+}
+
+/// A jpeg with an EXIF segment holding a rotation by 90 degrees and a GPS latitude.
+fn jpeg_with_exif(width: u32, height: u32) -> Vec<u8> {
+    let mut tiff = b"II\x2a\x00\x08\x00\x00\x00".to_vec();
+    // IFD0: orientation and GPS IFD pointer
+    tiff.extend_from_slice(&2u16.to_le_bytes());
+    tiff.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0]);
+    tiff.extend_from_slice(&[0x25, 0x88, 4, 0, 1, 0, 0, 0, 38, 0, 0, 0]);
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+    // GPS IFD: latitude reference
+    tiff.extend_from_slice(&1u16.to_le_bytes());
+    tiff.extend_from_slice(&[0x01, 0x00, 2, 0, 2, 0, 0, 0, b'N', 0, 0, 0]);
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+
+    let mut segment = b"Exif\x00\x00".to_vec();
+    segment.extend_from_slice(&tiff);
+
+    let jpeg = encode_image(width, height, ImageOutputFormat::Jpeg(90));
+    let mut content = jpeg[..2].to_vec();
+    content.extend_from_slice(&[0xff, 0xe1]);
+    content.extend_from_slice(&(segment.len() as u16 + 2).to_be_bytes());
+    content.extend_from_slice(&segment);
+    content.extend_from_slice(&jpeg[2..]);
+    content
+}
+
+#[tokio::test]
+async fn photos_are_resized_and_stripped_of_metadata() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let photos_url = format!("{}/api/cars/{}/photos", &app.address, car_id);
+    let upload = jpeg_with_exif(600, 300);
+    assert!(upload.windows(4).any(|window| window == b"Exif"));
+
+    // Act
+    let photo = client
+        .post(&photos_url)
+        .header("Content-Type", "image/jpeg")
+        .body(upload)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Photo>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!((300, 600), (photo.width, photo.height));
+    assert!(photo.is_primary);
+
+    for (variant, size) in [
+        ("original", (300, 600)),
+        ("medium", (300, 600)),
+        ("thumbnail", (128, 256)),
+    ] {
+        // Act
+        let content = client
+            .get(&format!("{}/{}/{}", photos_url, photo.id, variant))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .bytes()
+            .await
+            .unwrap();
+        // Assert
+        assert!(!content.windows(4).any(|window| window == b"Exif"));
+        let image = image::load_from_memory(&content).unwrap();
+        assert_eq!(size, (image.width() as i32, image.height() as i32));
+    }
+
+    // Act
+    let second = client
+        .post(&photos_url)
+        .header("Content-Type", "image/png")
+        .body(encode_image(40, 20, ImageOutputFormat::Png))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Photo>()
+        .await
+        .unwrap();
+    let second = client
+        .put(&format!("{}/{}/primary", photos_url, second.id))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Photo>()
+        .await
+        .unwrap();
+    let car = &get_account_details(&app, &client).await.cars_info[0];
+    // Assert
+    assert_eq!(Some(&second), car.primary_photo.as_ref());
+
+    // Act
+    let response = client
+        .delete(&format!("{}/{}", photos_url, second.id))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let car = &get_account_details(&app, &client).await.cars_info[0];
+    // Assert
+    assert_eq!(204, response.status().as_u16());
+    assert_eq!(
+        Some(photo.id),
+        car.primary_photo.as_ref().map(|photo| photo.id)
+    );
+}
+
+#[tokio::test]
+async fn photos_must_be_valid_images() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let photos_url = format!("{}/api/cars/{}/photos", &app.address, car_id);
+
+    for (content_type, body, status) in [
+        (
+            "image/gif",
+            encode_image(10, 10, ImageOutputFormat::Png),
+            415,
+        ),
+        ("image/png", b"not an image".to_vec(), 422),
+        (
+            "image/png",
+            encode_image(10, 10, ImageOutputFormat::Jpeg(90)),
+            422,
+        ),
+    ] {
+        // Act
+        let response = client
+            .post(&photos_url)
+            .header("Content-Type", content_type)
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        // Assert
+        assert_eq!(status, response.status().as_u16());
+    }
+
+    // Act
+    let response = client
+        .post(&photos_url)
+        .header("Content-Type", "image/jpeg")
+        .body(jpeg_with_dimensions(8000, 5000))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(422, response.status().as_u16());
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        "the image has too many pixels",
+        body["errors"]["body"][0]["message"]
+    );
+
+    // Act
+    let photos = client
+        .get(&photos_url)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<Photo>>()
+        .await
+        .unwrap();
+    // Assert
+    assert!(photos.is_empty());
+}
@@ -0,0 +1,161 @@
+mod setup;
+
+use car_api::routes::recall::{CarRecall, RecallImport};
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+const IMPORT_TOKEN: &str = "recall feed token";
+
+const RECALL_FEED: &str = "\
+campaign,manufacturer,model,vin_from,vin_to,year_from,year_to,description,published_on
+19V-001,Tesla,,5YJ3E1EA0KF000001,5YJ3E1EA0KF009999,,,Front seat belt anchor,2019-05-01
+20V-002,TESLA,tesla,,,2018,2020,Power steering assist,2020-02-01
+21V-003,Tesla,,5YJ3E1EA0KF010000,5YJ3E1EA0KF019999,,,Rear camera,2021-03-01
+21V-004,Renault,,,,2018,2020,Airbag,2021-03-01
+";
+
+#[tokio::test]
+async fn imported_recalls_are_matched_to_the_cars() {
+    // Arrange
+    std::env::set_var("RECALL_IMPORT_TOKEN", IMPORT_TOKEN);
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    // the check digit of the car is not the one of the VIN ranges
+    sqlx::query("UPDATE car SET manufacturer='Tesla', vin='5YJ3E1EA7KF000100', model_year=2019")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let import = client
+        .post(&format!("{}/api/recalls/import", &app.address))
+        .bearer_auth(IMPORT_TOKEN)
+        .header("Content-Type", "text/csv")
+        .body(RECALL_FEED)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<RecallImport>()
+        .await
+        .unwrap();
+    let import_again = client
+        .post(&format!("{}/api/recalls/import", &app.address))
+        .bearer_auth(IMPORT_TOKEN)
+        .json(&serde_json::json!([{
+            "campaign": "21V-003",
+            "manufacturer": "Tesla",
+            "vin_from": "5YJ3E1EA0KF000001",
+            "vin_to": "5YJ3E1EA0KF019999",
+            "description": "Rear camera"
+        }]))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<RecallImport>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(4, import.imported);
+    assert_eq!(1, import_again.imported);
+
+    // Act
+    let car = &get_account_details(&app, &client).await.cars_info[0];
+    // Assert
+    let campaigns: Vec<&str> = car
+        .open_recalls
+        .iter()
+        .map(|recall| recall.campaign.as_str())
+        .collect();
+    assert_eq!(vec!["20V-002", "19V-001", "21V-003"], campaigns);
+
+    // Act
+    let recall_url = format!(
+        "{}/api/cars/{}/recalls/{}/complete",
+        &app.address, car_id, car.open_recalls[0].id
+    );
+    let completed = client
+        .post(&recall_url)
+        .json(&serde_json::json!({ "completed_on": "2023-01-10" }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<CarRecall>()
+        .await
+        .unwrap();
+    let response = client
+        .post(&recall_url)
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!("20V-002", completed.recall.campaign);
+    assert_eq!(409, response.status().as_u16());
+
+    // Act
+    let recalls = client
+        .get(&format!("{}/api/cars/{}/recalls", &app.address, car_id))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<CarRecall>>()
+        .await
+        .unwrap();
+    let car = &get_account_details(&app, &client).await.cars_info[0];
+    // Assert
+    assert_eq!(3, recalls.len());
+    assert!(recalls[2].completed_on.is_some());
+    assert_eq!(2, car.open_recalls.len());
+}
+
+#[tokio::test]
+async fn invalid_recall_feeds_are_rejected() {
+    // Arrange
+    std::env::set_var("RECALL_IMPORT_TOKEN", IMPORT_TOKEN);
+    let app = spawn_app().await;
+    let client = Client::new();
+    let import_url = format!("{}/api/recalls/import", &app.address);
+    let feed = "\
+campaign,manufacturer,model,vin_from,vin_to,year_from,year_to,description,published_on
+19V-001,Tesla,,5YJ3E1EA0KF000001,,,,Front seat belt anchor,
+19V-002,Tesla,,,,2020,2018,Power steering assist,
+19V-003,Tesla,,,,,,Rear camera,
+19V-004,Tesla,,,,2018,2020,Airbag,
+19V-005,Tesla,,5YJ3E1EA0KF000001,5YJSA1E20KF000001,,,Door latch,
+";
+
+    // Act
+    let response = client
+        .post(&import_url)
+        .bearer_auth("wrong token")
+        .header("Content-Type", "text/csv")
+        .body(feed)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(403, response.status().as_u16());
+
+    // Act
+    let response = client
+        .post(&import_url)
+        .bearer_auth(IMPORT_TOKEN)
+        .header("Content-Type", "text/csv")
+        .body(feed)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(422, response.status().as_u16());
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(4, body["errors"]["records"].as_array().unwrap().len());
+    let (count,) = sqlx::query_as::<_, (i64,)>("SELECT count(*) FROM recalls")
+        .fetch_one(&app.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(0, count);
+}
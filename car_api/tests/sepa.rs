@@ -0,0 +1,179 @@
+mod setup;
+
+use car_api::routes::sepa::{Collection, Mandate};
+use car_api::sepa::SequenceType;
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+async fn create_mandate(app: &TestApp, client: &Client) -> Mandate {
+    let bank_details_id = get_account_details(app, client).await.bank_details[0].id;
+
+    client
+        .post(&format!(
+            "{}/api/account/bank_details/{}/mandates",
+            &app.address, bank_details_id
+        ))
+        .json(&serde_json::json!({ "signed_on": "2023-01-15" }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn create_collection(
+    app: &TestApp,
+    mandate: &Mandate,
+    amount_cents: i64,
+    due_on: &str,
+) -> reqwest::Response {
+    Client::new()
+        .post(&format!("{}/api/sepa/collections", &app.address))
+        .bearer_auth(SEPA_EXPORT_TOKEN)
+        .json(&serde_json::json!({
+            "mandate_id": mandate.id,
+            "amount_cents": amount_cents,
+            "remittance_information": "Subscription <car api>",
+            "due_on": due_on
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn collections_are_exported_as_pain008() {
+    // Arrange
+    configure_sepa_company();
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let mandate = create_mandate(&app, &client).await;
+    let first = create_collection(&app, &mandate, 1250, "2030-01-10")
+        .await
+        .json::<Collection>()
+        .await
+        .unwrap();
+    create_collection(&app, &mandate, 1250, "2030-02-10").await;
+
+    // Act
+    let response = Client::new()
+        .post(&format!("{}/api/sepa/batches", &app.address))
+        .bearer_auth(SEPA_EXPORT_TOKEN)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let document = response.text().await.unwrap();
+    assert_valid_xml(&document, "pain.008.001.02.xsd");
+    let xml = roxmltree::Document::parse(&document).unwrap();
+    let text = |name: &str| -> Vec<&str> {
+        xml.descendants()
+            .filter(|node| node.has_tag_name(name))
+            .filter_map(|node| node.text())
+            .collect()
+    };
+    assert_eq!(vec!["2", "1", "1"], text("NbOfTxs"));
+    assert_eq!(vec!["25.00", "12.50", "12.50"], text("CtrlSum"));
+    assert_eq!(vec!["FRST", "RCUR"], text("SeqTp"));
+    assert_eq!(vec!["2030-01-10", "2030-02-10"], text("ReqdColltnDt"));
+    assert_eq!(vec![mandate.reference.as_str(); 2], text("MndtId"));
+    assert_eq!(first.id.simple().to_string(), text("EndToEndId")[0]);
+    assert_eq!(
+        vec!["DE89370400440532013000", "FR7630006000011234567890189"],
+        text("IBAN")[..2].to_vec()
+    );
+    assert_eq!(vec!["Subscription <car api>"; 2], text("Ustrd"));
+
+    // Act
+    let mandates = client
+        .get(&format!("{}/api/account/mandates", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<Mandate>>()
+        .await
+        .unwrap();
+    create_collection(&app, &mandate, 990, "2030-03-10").await;
+    let document = Client::new()
+        .post(&format!("{}/api/sepa/batches", &app.address))
+        .bearer_auth(SEPA_EXPORT_TOKEN)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(SequenceType::Rcur, mandates[0].sequence_type);
+    assert_valid_xml(&document, "pain.008.001.02.xsd");
+    assert!(document.contains("<SeqTp>RCUR</SeqTp>"));
+    assert!(!document.contains("2030-01-10"));
+}
+
+#[tokio::test]
+async fn revoked_mandates_are_not_collected() {
+    // Arrange
+    configure_sepa_company();
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let mandate = create_mandate(&app, &client).await;
+    create_collection(&app, &mandate, 1250, "2030-01-10").await;
+    let revoke_url = format!(
+        "{}/api/account/mandates/{}/revoke",
+        &app.address, mandate.id
+    );
+
+    // Act
+    let revoked = client
+        .post(&revoke_url)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Mandate>()
+        .await
+        .unwrap();
+    let revoked_again = client
+        .post(&revoke_url)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let collection = create_collection(&app, &mandate, 1250, "2030-02-10").await;
+    let due_in_the_past = create_collection(&app, &mandate, 1250, "2020-02-10").await;
+    let batch = Client::new()
+        .post(&format!("{}/api/sepa/batches", &app.address))
+        .bearer_auth(SEPA_EXPORT_TOKEN)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let without_token = Client::new()
+        .post(&format!("{}/api/sepa/batches", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let signed_in_the_future = client
+        .post(&format!(
+            "{}/api/account/bank_details/{}/mandates",
+            &app.address, mandate.bank_details_id
+        ))
+        .json(&serde_json::json!({ "signed_on": "2999-01-01" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert!(revoked.revoked_at.is_some());
+    assert_eq!(409, revoked_again.status().as_u16());
+    assert_eq!(409, collection.status().as_u16());
+    assert_eq!(422, due_in_the_past.status().as_u16());
+    assert_eq!(404, batch.status().as_u16());
+    assert_eq!(403, without_token.status().as_u16());
+    assert_eq!(422, signed_in_the_future.status().as_u16());
+}
@@ -3,16 +3,31 @@ use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::net::TcpListener;
 use uuid::Uuid;
 
+use car_api::encrypt::Keyring;
 use car_api::startup::run;
 
+#[allow(dead_code)]
+pub const SEPA_EXPORT_TOKEN: &str = "sepa export token";
+
+/// `FR7630006000011234567890189` encrypted with the `2025` key, `previous secret`, when the key
+/// of each record was derived with PBKDF2.
+#[allow(dead_code)]
+pub const PBKDF2_CIPHERTEXT: &str = "yv4BBDIwMjVH04p/zOUscgVUI0E92z2V0eiRe5WxWKbS+SsEMF+hGZ5M99UQXHPKMLZkaS6+TyrxVjt+GpW5QoKBQIU7AWs6EAMsTCWa4E7lSacUrgm0I4xwTWwKRW3ChLOF8C6vDgSWF8f7NmrMkEhwahtO8Szk7Q3TJb46b5SWxs0=";
+
+/// `FR7630006000011234567890189` encrypted with `legacy secret`, before the ciphertexts had a header.
+#[allow(dead_code)]
+pub const LEGACY_CIPHERTEXT: &str = "DmCvAAE9/UZiKLYBqrRxy/0FQg5RSgkeCd5NhUlMfQqOax5v23jSzG/V0KPFyTcJRaF7nn4A5rKbaHftN3ETrOljAV7u6WzIspYpNUfkNugtS2cD3lVQxOrVcmey1xfh53urocacx1D2fVSnwx6FPRdprEEAi+iOKtSb";
+
 pub struct TestApp {
     pub database_name: String,
     pub address: String,
     pub pg_pool: PgPool,
 }
 
+#[allow(dead_code)]
 pub async fn spawn_app() -> TestApp {
     dotenv::dotenv().ok();
+    std::env::set_var("STORAGE_PATH", storage_path());
 
     let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind random port");
 
@@ -33,6 +48,11 @@ pub async fn spawn_app() -> TestApp {
     }
 }
 
+/// Uploaded files of the tests are kept out of the source tree.
+pub fn storage_path() -> std::path::PathBuf {
+    std::env::temp_dir().join("car_api_test_storage")
+}
+
 pub async fn configure_database(database_name: String) -> PgPool {
     let mut connection = PgConnection::connect_with(&without_db())
         .await
@@ -72,3 +92,112 @@ pub fn with_db(database_name: &str) -> PgConnectOptions {
         .database(database_name)
         .port(5432)
 }
+
+/// Creates an account with a single car for `email` and logs the client in.
+#[allow(dead_code)]
+pub async fn create_account_and_login(app: &TestApp, client: &reqwest::Client, email: &str) {
+    let body = serde_json::json!(
+        {
+            "user": {
+                "email": email,
+                "password": "my super password",
+                "user_name": "toto"
+            },
+            "car_info": {
+                "car_model": "tesla",
+                "car_plate": "42"
+            },
+            "bank_details": {
+                "account_holder": "toto",
+                "bank_country": "france",
+                "iban": "FR76 3000 6000 0112 3456 7890 189"
+            }
+        }
+    );
+
+    let response = client
+        .post(&format!("{}/api/account", &app.address))
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert!(response.status().is_success());
+
+    login(app, client, email).await;
+}
+
+/// Logs the client in an account created by `create_account_and_login`.
+#[allow(dead_code)]
+pub async fn login(app: &TestApp, client: &reqwest::Client, email: &str) {
+    let response = client
+        .post(&format!("{}/login", &app.address))
+        .json(&serde_json::json!({ "email": email, "password_hash": "my super password" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[allow(dead_code)]
+pub async fn get_account_details(
+    app: &TestApp,
+    client: &reqwest::Client,
+) -> car_api::routes::account::AccountDetails {
+    client
+        .get(&format!("{}/api/account", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .expect("Failed to parse account details.")
+}
+
+/// Validates an XML document against a schema of `tests/schemas` with `xmllint`.
+#[allow(dead_code)]
+pub fn assert_valid_xml(document: &str, schema: &str) {
+    let path = std::env::temp_dir().join(format!("{}.xml", Uuid::new_v4()));
+    std::fs::write(&path, document).unwrap();
+
+    let output = std::process::Command::new("xmllint")
+        .arg("--noout")
+        .arg("--schema")
+        .arg(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/schemas")
+                .join(schema),
+        )
+        .arg(&path)
+        .output()
+        .expect("xmllint is needed to validate the generated XML");
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stderr),
+        document
+    );
+}
+
+/// Configures the company collecting the direct debits and paying the payouts.
+#[allow(dead_code)]
+pub fn configure_sepa_company() {
+    std::env::set_var("SEPA_EXPORT_TOKEN", SEPA_EXPORT_TOKEN);
+    std::env::set_var("SEPA_COMPANY_NAME", "Car Api & Co");
+    std::env::set_var("SEPA_COMPANY_IBAN", "DE89370400440532013000");
+    std::env::set_var("SEPA_COMPANY_BIC", "COBADEFFXXX");
+    std::env::set_var("SEPA_CREDITOR_ID", "DE98ZZZ09999999999");
+}
+
+/// A keyring of the `(id, secret)` keys encrypting with the `encryption_key_id` key.
+#[allow(dead_code)]
+pub fn keyring(encryption_key_id: &str, keys: &[(&str, &str)]) -> Keyring {
+    Keyring::new(
+        encryption_key_id,
+        keys.iter()
+            .map(|(key_id, secret)| (key_id.to_string(), secret.to_string()))
+            .collect(),
+    )
+    .unwrap()
+}
@@ -0,0 +1,173 @@
+mod setup;
+
+use car_api::routes::trip::{Trip, TripDetails};
+
+use crate::setup::*;
+
+use reqwest::Client;
+
+#[tokio::test]
+async fn trip_points_are_measured_and_exported() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let trips_url = format!("{}/api/cars/{}/trips", &app.address, car_id);
+
+    // Act
+    let trip = client
+        .post(&trips_url)
+        .json(&serde_json::json!({
+            "name": "Paris <-> Lyon",
+            "started_at": "2023-01-10T08:00:00",
+            "points": [
+                { "latitude": 48.8566, "longitude": 2.3522, "recorded_at": "2023-01-10T08:00:00" }
+            ]
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Trip>()
+        .await
+        .unwrap();
+    let trip_url = format!("{}/{}", trips_url, trip.id);
+    let trip = client
+        .post(&format!("{}/points", trip_url))
+        .json(&serde_json::json!({
+            "points": [
+                { "latitude": 45.764, "longitude": 4.8357, "elevation": 170.0, "recorded_at": "2023-01-10T12:00:00" }
+            ]
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Trip>()
+        .await
+        .unwrap();
+    // Assert
+    assert!((trip.distance_m - 392_000.0).abs() < 1_000.0);
+
+    // Act
+    let details = client
+        .get(&trip_url)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<TripDetails>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(2, details.points.len());
+    assert_eq!(Some(170.0), details.points[1].elevation);
+
+    // Act
+    let gpx = client
+        .get(&format!("{}/export?format=gpx", trip_url))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+    let imported = client
+        .post(&format!("{}/gpx", trips_url))
+        .header("Content-Type", "application/gpx+xml")
+        .body(gpx)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Trip>()
+        .await
+        .unwrap();
+    // Assert
+    assert_eq!(Some("Paris <-> Lyon".to_owned()), imported.name);
+    assert_eq!(trip.started_at, imported.started_at);
+    assert_eq!(
+        Some(details.points[1].recorded_at.unwrap()),
+        imported.ended_at
+    );
+    assert!((trip.distance_m - imported.distance_m).abs() < 1.0);
+
+    // Act
+    let response = client
+        .get(&format!("{}/export?format=kml", trip_url))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(
+        "application/vnd.google-earth.kml+xml",
+        response.headers()["content-type"]
+    );
+    assert!(response.text().await.unwrap().contains("4.8357,45.764,170"));
+}
+
+#[tokio::test]
+async fn gpx_import_validates_files() {
+    // Arrange
+    let app = spawn_app().await;
+    let client = Client::builder().cookie_store(true).build().unwrap();
+    create_account_and_login(&app, &client, "email@email.com").await;
+    let car_id = get_account_details(&app, &client).await.cars_info[0].id;
+    let gpx_url = format!("{}/api/cars/{}/trips/gpx", &app.address, car_id);
+
+    // Act
+    let response = client
+        .post(&gpx_url)
+        .header("Content-Type", "text/plain")
+        .body("<gpx></gpx>")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(415, response.status().as_u16());
+
+    for body in [
+        "not xml",
+        "<gpx></gpx>",
+        r#"<gpx><trk><trkseg><trkpt lat="91" lon="0"/></trkseg></trk></gpx>"#,
+        r#"<gpx><trk><trkseg><trkpt lat="NaN" lon="0"/></trkseg></trk></gpx>"#,
+        r#"<gpx><trk><trkseg><trkpt lat="0" lon="0"><ele>inf</ele></trkpt></trkseg></trk></gpx>"#,
+    ] {
+        // Act
+        let response = client
+            .post(&gpx_url)
+            .header("Content-Type", "application/gpx+xml")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        // Assert
+        assert_eq!(422, response.status().as_u16());
+    }
+
+    let trips = client
+        .get(&format!("{}/api/cars/{}/trips", &app.address, car_id))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json::<Vec<Trip>>()
+        .await
+        .unwrap();
+    assert!(trips.is_empty());
+
+    // Act
+    let response = client
+        .post(&gpx_url)
+        .header("Content-Type", "application/gpx+xml")
+        .body(
+            r#"<gpx><trk><trkseg>
+            <trkpt lat="45.764" lon="4.8357"><time>2023-01-10T12:00:00Z</time></trkpt>
+            <trkpt lat="48.8566" lon="2.3522"><time>2023-01-10T08:00:00Z</time></trkpt>
+            </trkseg></trk></gpx>"#,
+        )
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    // Assert
+    assert_eq!(201, response.status().as_u16());
+    let trip = response.json::<Trip>().await.unwrap();
+    assert_eq!("2023-01-10T08:00:00".parse().ok(), Some(trip.started_at));
+    assert_eq!("2023-01-10T12:00:00".parse().ok(), trip.ended_at);
+}